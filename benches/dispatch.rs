@@ -0,0 +1,40 @@
+//! Criterion benchmark of the VM's instruction dispatch on arithmetic.
+//! Run it once with each strategy and compare the two:
+//!
+//! ```bash
+//! cargo bench --bench dispatch
+//! cargo bench --bench dispatch --features table-dispatch
+//! ```
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::hint::black_box;
+
+use udyr::{compiler, parser::Parser, scanner::Scanner, vm::Vm};
+
+/// About 10,000 instructions of int arithmetic, using few enough distinct
+/// numbers to fit in a chunk's constants.
+fn arithmetic() -> String {
+    format!("1{}", " + 2 * 3 - 4".repeat(1700))
+}
+
+fn dispatch(c: &mut Criterion) {
+    let tokens = Scanner::new(&arithmetic()).scan_tokens().unwrap();
+    let program = Parser::new(&tokens).parse().unwrap();
+    let chunk = compiler::compile(&program).unwrap();
+    let strategy = if cfg!(feature = "table-dispatch") {
+        "table"
+    } else {
+        "match"
+    };
+
+    let mut group = c.benchmark_group("dispatch");
+    group.throughput(Throughput::Bytes(chunk.code.len() as u64));
+    group.bench_function(strategy, |b| {
+        let mut vm = Vm::new();
+        b.iter(|| vm.interpret(black_box(&chunk)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
@@ -0,0 +1,72 @@
+//! Criterion benchmarks for each stage of the pipeline on large synthetic
+//! sources. Run with `cargo bench --bench pipeline`.
+//!
+//! There are no functions yet, so the interpreter is measured on a long
+//! chain of comparisons rather than something like `fib(30)`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+use udyr::{compiler, parser::Parser, scanner::Scanner, token::Token, vm::Vm};
+
+/// About 10,000 tokens of arithmetic in a single expression.
+fn arithmetic() -> String {
+    format!("{}1", "(12.5 + x) * 3 - \"s\" / ".repeat(1000))
+}
+
+/// `depth` nested groupings around one literal.
+fn nested(depth: usize) -> String {
+    format!("{}1{}", "(".repeat(depth), ")".repeat(depth))
+}
+
+/// About 10,000 tokens the VM can run without any constants, which a
+/// chunk only has room for 256 of.
+fn comparisons() -> String {
+    format!("!nil{}", " == !false != true".repeat(1700))
+}
+
+fn scan(source: &str) -> Vec<Token> {
+    Scanner::new(source).scan_tokens().unwrap()
+}
+
+fn scanner(c: &mut Criterion) {
+    let source = arithmetic();
+    let mut group = c.benchmark_group("scanner");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("arithmetic", |b| b.iter(|| scan(black_box(&source))));
+    group.finish();
+}
+
+fn parser(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parser");
+    let tokens = scan(&arithmetic());
+    group.throughput(Throughput::Elements(tokens.len() as u64));
+    group.bench_function("arithmetic", |b| {
+        b.iter(|| Parser::new(black_box(&tokens)).parse().unwrap())
+    });
+    for depth in [10, 50, 100] {
+        let tokens = scan(&nested(depth));
+        group.bench_with_input(BenchmarkId::new("nested", depth), &tokens, |b, tokens| {
+            b.iter(|| Parser::new(black_box(tokens)).parse().unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn interpreter(c: &mut Criterion) {
+    let program = Parser::new(&scan(&comparisons())).parse().unwrap();
+    let chunk = compiler::compile(&program).unwrap();
+    let mut group = c.benchmark_group("interpreter");
+    group.bench_function("compile", |b| {
+        b.iter(|| compiler::compile(black_box(&program)).unwrap())
+    });
+    group.throughput(Throughput::Bytes(chunk.code.len() as u64));
+    group.bench_function("run", |b| {
+        let mut vm = Vm::new();
+        b.iter(|| vm.interpret(black_box(&chunk)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, scanner, parser, interpreter);
+criterion_main!(benches);
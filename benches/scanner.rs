@@ -0,0 +1,33 @@
+//! Scans synthetic sources of doubling size and prints the time per byte.
+//! Scanning is linear when the last column stays roughly flat.
+//!
+//! Run with `cargo bench --bench scanner`.
+
+use std::time::Instant;
+
+use udyr::scanner::Scanner;
+
+const LINE: &str = "var naïve = (1_000 + 2.5e-3) * \"smørbrød\" // comment\n";
+
+fn main() {
+    println!(
+        "{:>12} {:>12} {:>12} {:>10}",
+        "bytes", "tokens", "time (us)", "ns/byte"
+    );
+    for shift in 0..6 {
+        let source = LINE.repeat(1000 << shift);
+
+        let start = Instant::now();
+        let mut scanner = Scanner::new(&source);
+        let tokens = scanner.scan_tokens().unwrap();
+        let elapsed = start.elapsed();
+
+        println!(
+            "{:>12} {:>12} {:>12} {:>10.2}",
+            source.len(),
+            tokens.len(),
+            elapsed.as_micros(),
+            elapsed.as_nanos() as f64 / source.len() as f64
+        );
+    }
+}
@@ -0,0 +1,34 @@
+/* The udyr C API. Build the library with `cargo build --release --features capi`
+ * and link against target/release/libudyr.so. */
+
+#ifndef UDYR_H
+#define UDYR_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Udyr Udyr;
+
+/* Creates an interpreter. Free it with udyr_free. */
+Udyr *udyr_new(void);
+
+/* Runs source and returns its value as a string, or NULL if it failed;
+ * then udyr_last_error has the errors. The string belongs to the
+ * interpreter and stays valid until the next udyr_eval or udyr_free. */
+const char *udyr_eval(Udyr *udyr, const char *source);
+
+/* Lets the scripts udyr runs call exec if allow is not zero. */
+void udyr_allow_exec(Udyr *udyr, int allow);
+
+/* Returns the errors of the last failed udyr_eval, one per line, or NULL
+ * if it succeeded. Valid as long as the udyr_eval result would be. */
+const char *udyr_last_error(const Udyr *udyr);
+
+void udyr_free(Udyr *udyr);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,162 @@
+use crate::{
+    ast::{Ast, Expr, ExprId, Pattern, Program, Stmt},
+    lint::{Lint, Warning},
+    optimizer,
+    token::TokenType,
+    value::Value,
+};
+
+/// Warns about code that can never run, like the else branch of
+/// `1 < 2 ? a : b`, the right operand of `1 ?? a` or a match arm after
+/// `_ => ...`. The warnings do not stop the program.
+pub fn unreachable_code(program: &Program) -> Vec<Warning> {
+    // Folding keeps ids, so the folded copy tells what each expression
+    // of the program folds to.
+    let mut folded = program.ast.clone();
+    for Stmt::Expression(expr) in &program.statements {
+        optimizer::fold_expr(&mut folded, *expr);
+    }
+    let checker = Checker {
+        ast: &program.ast,
+        folded: &folded,
+    };
+    let mut warnings = Vec::new();
+    for stmt in &program.statements {
+        match stmt {
+            Stmt::Expression(expr) => checker.check_expr(*expr, &mut warnings),
+        }
+    }
+    return warnings;
+}
+
+struct Checker<'a> {
+    ast: &'a Ast,
+    folded: &'a Ast,
+}
+
+impl Checker<'_> {
+    fn check_expr(&self, id: ExprId, warnings: &mut Vec<Warning>) {
+        match &self.ast[id] {
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } if operator.token_type == TokenType::QuestionQuestion => {
+                self.check_expr(*left, warnings);
+                match optimizer::constant(&self.folded[*left]) {
+                    Some(Value::Nil) | None => self.check_expr(*right, warnings),
+                    Some(_) => warnings.push(self.unreachable(*right)),
+                }
+            }
+            Expr::Binary { left, right, .. } => {
+                self.check_expr(*left, warnings);
+                self.check_expr(*right, warnings);
+            }
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.check_expr(*condition, warnings);
+                match optimizer::constant(&self.folded[*condition]) {
+                    Some(value) if value.is_truthy() => {
+                        self.check_expr(*then_branch, warnings);
+                        warnings.push(self.unreachable(*else_branch));
+                    }
+                    Some(_) => {
+                        warnings.push(self.unreachable(*then_branch));
+                        self.check_expr(*else_branch, warnings);
+                    }
+                    None => {
+                        self.check_expr(*then_branch, warnings);
+                        self.check_expr(*else_branch, warnings);
+                    }
+                }
+            }
+            Expr::Match { subject, arms, .. } => {
+                self.check_expr(*subject, warnings);
+                let mut matched = false;
+                for arm in arms {
+                    if matched {
+                        warnings.push(self.unreachable(arm.body));
+                        continue;
+                    }
+                    if let Some(guard) = arm.guard {
+                        self.check_expr(guard, warnings);
+                    }
+                    self.check_expr(arm.body, warnings);
+                    // `_` and a bare name match anything, so no later arm runs.
+                    matched = arm.guard.is_none()
+                        && matches!(arm.pattern, Pattern::Wildcard(_) | Pattern::Binding(_));
+                }
+            }
+            expr => {
+                for child in expr.children() {
+                    self.check_expr(child, warnings);
+                }
+            }
+        }
+    }
+
+    fn unreachable(&self, id: ExprId) -> Warning {
+        return Warning {
+            span: Some(self.ast.span(id)),
+            ..Warning::new(
+                Lint::UnreachableCode,
+                self.ast.line(id),
+                "Unreachable code.",
+            )
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::Parser, scanner::Scanner};
+
+    use super::*;
+
+    fn check(source: &str) -> Vec<String> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let warnings = unreachable_code(&Parser::new(&tokens).parse().unwrap());
+        warnings.iter().map(|warning| warning.to_string()).collect()
+    }
+
+    #[test]
+    fn test_unreachable_branches() -> Result<(), String> {
+        assert_eq!(
+            check("true ? 1 :\n2"),
+            vec![String::from("[line 2] Warning: Unreachable code.")]
+        );
+        assert_eq!(
+            check("1 > 2 ?\nx : y"),
+            vec![String::from("[line 2] Warning: Unreachable code.")]
+        );
+        assert_eq!(check("nil ? (false ? a : b) : c").len(), 1);
+        assert_eq!(check("true ? (false ? a : b) : c").len(), 2);
+        assert_eq!(
+            check("0 ??\n(x ?? y)"),
+            vec![String::from("[line 2] Warning: Unreachable code.")]
+        );
+        assert!(check("nil ?? x ?? y").is_empty());
+        assert_eq!(
+            check("match x { n => n,\n_ => 0,\n1 => 1 }"),
+            vec![
+                String::from("[line 2] Warning: Unreachable code."),
+                String::from("[line 3] Warning: Unreachable code."),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reachable_branches() -> Result<(), String> {
+        assert!(check("x ? 1 : 2").is_empty());
+        assert!(check("1 / 0 > 0 ? 1 : 2").is_empty());
+        assert!(check("1 + 2").is_empty());
+        assert!(check("match x { 1 => a, n if n > 1 => b, (_,) => c, _ => d }").is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1150 @@
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use rayon::prelude::*;
+use rustyline::history::DefaultHistory;
+
+use udyr::{
+    analysis, ast, chunk, compiler, debugger, diff, disassembler, doc, error::Diagnostic, explorer,
+    formatter, golden, highlight, interpreter, optimizer, parser, pragma, profiler, scanner,
+    serialize, template, token, tracer, types, value, vm,
+};
+
+use super::editor::Editor;
+use super::options::{Options, ProfileFormat};
+use super::reporter::Reporter;
+
+const USAGE: &str = "\
+Usage: udyr [--strict] [--define NAME]... [--print-ast] [--dump-bytecode]
+            [-O | --opt-level=0|1] [-W [error]] [--allow LINT]...
+            [--max-steps N] [--timeout MS] [--allow-exec] [--allow-net]
+            [--log-level debug|info|warn|error] [--prelude script] [--no-color]
+            [--trace] [--profile[=folded]] [--time] [--bench] [--watch]
+            [script [--] [argument]...]
+       udyr [options] -e source
+       udyr run script.udyrc [argument]...
+       udyr run project [argument]...
+       udyr compile script [-o script.udyrc]
+       udyr check [--types] path...
+       udyr debug script
+       udyr explore script
+       udyr diff [--ast] old new
+       udyr doc [--html | --md] script
+       udyr fmt [--check] [--fix] script...
+       udyr highlight [--html] script
+       udyr render template
+       udyr test path
+       udyr lsp";
+
+/// The first arguments that pick a front end other than running a script.
+const COMMANDS: &[&str] = &[
+    "check",
+    "compile",
+    "debug",
+    "diff",
+    "doc",
+    "explore",
+    "fmt",
+    "highlight",
+    "lsp",
+    "render",
+    "run",
+    "test",
+];
+
+/// The script `udyr run` starts a project directory with.
+const ENTRY_POINT: &str = "main.udyr";
+
+/// The prelude the REPL runs when `--prelude` is not given, in the home
+/// directory.
+const DEFAULT_PRELUDE: &str = ".udyrrc.udyr";
+
+/// How often `--watch` looks at the script for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How many times `--bench` runs each stage.
+const BENCH_RUNS: u32 = 100;
+
+/// Where the program comes from and what to do with it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrontEnd {
+    Repl,
+    /// Run a script, the bytecode in a `.udyrc` file, or the `main.udyr` of
+    /// a project directory, with the arguments after it.
+    File(String, Vec<String>),
+    /// Run the source given with `-e`.
+    Eval(String),
+    Bench(String),
+    Compile(String),
+    /// Check scripts, and the scripts in directories, without running them.
+    Check(Vec<String>),
+    Debug(String),
+    /// Browse the syntax tree of a script next to its source.
+    Explore(String),
+    /// Compare two versions of a script.
+    Diff(String, String),
+    /// Write documentation for a script from its doc comments.
+    Doc(String),
+    Format(Vec<String>),
+    Highlight(String),
+    /// Write a template with the values of the code in it.
+    Render(String),
+    Test(String),
+    LanguageServer,
+    Usage,
+}
+
+impl FrontEnd {
+    /// The script or directory the front end works on, if any.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            FrontEnd::File(path, _)
+            | FrontEnd::Bench(path)
+            | FrontEnd::Compile(path)
+            | FrontEnd::Debug(path)
+            | FrontEnd::Explore(path)
+            | FrontEnd::Doc(path)
+            | FrontEnd::Highlight(path)
+            | FrontEnd::Render(path)
+            | FrontEnd::Test(path) => Some(path),
+            FrontEnd::Diff(path, _) => Some(path),
+            FrontEnd::Check(paths) | FrontEnd::Format(paths) => paths.first().map(String::as_str),
+            FrontEnd::Repl | FrontEnd::Eval(_) | FrontEnd::LanguageServer | FrontEnd::Usage => None,
+        }
+    }
+
+    /// Picks the front end from the arguments left after the flags.
+    pub fn from_args(args: &[String], options: &Options) -> FrontEnd {
+        match args {
+            [] => FrontEnd::Repl,
+            [command] if command == "lsp" => FrontEnd::LanguageServer,
+            [flag, source] if flag == "-e" => FrontEnd::Eval(source.clone()),
+            // A command without the paths it needs is not a script.
+            [command] if COMMANDS.contains(&command.as_str()) => FrontEnd::Usage,
+            [script] if options.bench => FrontEnd::Bench(script.clone()),
+            [command, script] if command == "run" && options.bench => {
+                FrontEnd::Bench(script.clone())
+            }
+            // `--bench` times one script, which it runs without arguments.
+            _ if options.bench => FrontEnd::Usage,
+            [script] => FrontEnd::File(script.clone(), Vec::new()),
+            [command, script, arguments @ ..] if command == "run" => {
+                FrontEnd::File(script.clone(), arguments.to_vec())
+            }
+            [command, script] if command == "compile" => FrontEnd::Compile(script.clone()),
+            [command, paths @ ..] if command == "check" && !paths.is_empty() => {
+                FrontEnd::Check(paths.to_vec())
+            }
+            [command, script] if command == "debug" => FrontEnd::Debug(script.clone()),
+            [command, script] if command == "explore" => FrontEnd::Explore(script.clone()),
+            [command, old, new] if command == "diff" => FrontEnd::Diff(old.clone(), new.clone()),
+            [command, script] if command == "doc" => FrontEnd::Doc(script.clone()),
+            [command, path] if command == "test" => FrontEnd::Test(path.clone()),
+            [command, script] if command == "highlight" => FrontEnd::Highlight(script.clone()),
+            [command, path] if command == "render" => FrontEnd::Render(path.clone()),
+            [command, scripts @ ..] if command == "fmt" && !scripts.is_empty() => {
+                FrontEnd::Format(scripts.to_vec())
+            }
+            [script, arguments @ ..] if !COMMANDS.contains(&script.as_str()) => {
+                FrontEnd::File(script.clone(), arguments.to_vec())
+            }
+            _ => FrontEnd::Usage,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RunError {
+    messages: Vec<Diagnostic>,
+    /// The source the messages are about, so the reporter can show the
+    /// tokens they point at.
+    source: Option<String>,
+}
+
+impl RunError {
+    fn in_source(mut self, source: &str) -> RunError {
+        self.source = Some(source.to_string());
+        self
+    }
+}
+
+impl<T: Into<Diagnostic>> From<Vec<T>> for RunError {
+    fn from(messages: Vec<T>) -> RunError {
+        RunError {
+            messages: messages.into_iter().map(Into::into).collect(),
+            source: None,
+        }
+    }
+}
+
+impl From<String> for RunError {
+    fn from(message: String) -> RunError {
+        RunError::from(vec![message])
+    }
+}
+
+impl From<Diagnostic> for RunError {
+    fn from(message: Diagnostic) -> RunError {
+        RunError::from(vec![message])
+    }
+}
+
+/// The composition root of the program. It owns the configuration, the
+/// VM that runs programs, and the reporter every diagnostic goes through,
+/// and [`Application::run`] hands them to the chosen front end.
+pub struct Application {
+    options: Options,
+    front_end: FrontEnd,
+    reporter: Reporter,
+    vm: vm::Vm,
+}
+
+impl Application {
+    pub fn new(options: Options, front_end: FrontEnd) -> Application {
+        let mut vm = vm::Vm::new();
+        vm.set_limits(options.limits.clone());
+        vm.set_capabilities(options.capabilities.clone());
+        vm.set_log_level(options.log_level);
+        let reporter = Reporter::new(options.colors(io::stderr().is_terminal()));
+        if let FrontEnd::File(_, arguments) = &front_end {
+            vm.set_arguments(arguments.clone());
+        }
+        Application {
+            options,
+            front_end,
+            reporter,
+            vm,
+        }
+    }
+
+    pub fn run(&mut self) {
+        if !self.run_prelude() {
+            return;
+        }
+        match self.front_end.clone() {
+            FrontEnd::Repl => self.run_prompt(),
+            FrontEnd::File(path, _) => {
+                let path = match entry_point(Path::new(&path)) {
+                    Ok(path) => path,
+                    Err(message) => {
+                        self.reporter.report(&message);
+                        return self.reporter.fail();
+                    }
+                };
+                if self.options.watch {
+                    self.watch_file(&path)
+                } else {
+                    self.run_file(&path)
+                }
+            }
+            FrontEnd::Eval(source) => {
+                let options = &self.options;
+                let (stage, dump_bytecode, time) =
+                    (Stage::of(options), options.dump_bytecode, options.time);
+                let result = self.run_source(&source, stage, dump_bytecode, time);
+                self.report(result);
+            }
+            FrontEnd::Bench(path) => self.bench_file(&path),
+            FrontEnd::Compile(path) => self.compile_file(&path),
+            FrontEnd::Check(paths) => self.check_files(&paths),
+            FrontEnd::Debug(path) => self.debug_file(&path),
+            FrontEnd::Explore(path) => self.explore_file(&path),
+            FrontEnd::Diff(old, new) => self.diff_files(&old, &new),
+            FrontEnd::Doc(path) => self.document_file(&path),
+            FrontEnd::Format(paths) => self.format_files(&paths),
+            FrontEnd::Highlight(path) => self.highlight_file(&path),
+            FrontEnd::Render(path) => self.render_file(&path),
+            FrontEnd::Test(path) => self.test_files(&path),
+            FrontEnd::LanguageServer => self.run_language_server(),
+            FrontEnd::Usage => println!("{}", USAGE),
+        }
+    }
+
+    /// Runs the prelude, if there is one, before the program. Returns
+    /// whether to go on, which is not the case if it failed or exited.
+    fn run_prelude(&mut self) -> bool {
+        let path = match &self.options.prelude {
+            Some(path) => path.clone(),
+            None if self.front_end == FrontEnd::Repl => {
+                match env::var_os("HOME").map(|home| Path::new(&home).join(DEFAULT_PRELUDE)) {
+                    Some(path) if path.is_file() => path,
+                    _ => return true,
+                }
+            }
+            None => return true,
+        };
+        let result = std::fs::read_to_string(&path)
+            .map_err(|err| RunError::from(err.to_string()))
+            .and_then(|source| {
+                let program = self.parse(&source)?;
+                compile(&source, &program)
+            })
+            .and_then(|chunk| self.vm.interpret(&chunk).map_err(RunError::from));
+        if let Err(err) = result {
+            for message in err.messages {
+                self.reporter.report_diagnostic(
+                    err.source.as_deref(),
+                    &Diagnostic {
+                        message: format!("{}: {}", path.display(), message),
+                        ..message
+                    },
+                );
+            }
+            self.reporter.fail();
+            return false;
+        }
+        if let Some(code) = self.vm.exit_code() {
+            self.reporter.exit_with(code);
+            return false;
+        }
+        true
+    }
+
+    /// Flushes what is left of the output and tells how the program went.
+    pub fn destroy(mut self) -> ExitCode {
+        let _ = io::stdout().flush();
+        self.reporter.flush();
+        self.reporter.exit_code()
+    }
+
+    /// Reports the errors of a failed run, and makes the program exit with
+    /// an error.
+    fn report<T>(&mut self, result: Result<T, RunError>) {
+        if let Err(err) = result {
+            self.reporter
+                .report_diagnostics(err.source.as_deref(), &err.messages);
+            self.reporter.fail();
+        }
+    }
+
+    /// Reads the file at `path`, or reports why it cannot be read and
+    /// fails.
+    fn read(&mut self, path: &str) -> Option<String> {
+        let result = std::fs::read_to_string(path).map_err(|err| file_error(path, err));
+        match result {
+            Ok(source) => Some(source),
+            Err(err) => {
+                self.report::<()>(Err(err));
+                None
+            }
+        }
+    }
+
+    fn parse(&mut self, source: &str) -> Result<ast::Program, RunError> {
+        let tokens = scan(source, &self.options)?;
+        self.parse_tokens(source, &tokens)
+    }
+
+    /// Parses the tokens of `source`, reporting the warnings in it.
+    fn parse_tokens(
+        &mut self,
+        source: &str,
+        tokens: &[token::Token],
+    ) -> Result<ast::Program, RunError> {
+        let (program, warnings) =
+            parse_tokens(tokens, &self.options).map_err(|err| err.in_source(source))?;
+        self.reporter.report_diagnostics(Some(source), &warnings);
+        Ok(program)
+    }
+
+    /// Takes `source` up to `stage`, printing how long each stage took to
+    /// stderr if `time` is set.
+    fn run_source(
+        &mut self,
+        source: &str,
+        stage: Stage,
+        dump_bytecode: bool,
+        time: bool,
+    ) -> Result<(), RunError> {
+        let mut timer = Timer::new();
+        let tokens = scan(source, &self.options)?;
+        timer.stage("scan");
+        let program = self.parse_tokens(source, &tokens)?;
+        timer.stage("parse");
+        if stage == Stage::Parse {
+            program.print();
+        } else {
+            let chunk = compile(source, &program)?;
+            timer.stage("compile");
+            if dump_bytecode {
+                print!("{}", disassembler::disassemble(&chunk, "code"));
+            }
+            if stage == Stage::Run {
+                self.execute(&chunk)?;
+                timer.stage("run");
+            }
+        }
+        if time {
+            eprint!("{}", stage_table(&timer.stages));
+        }
+
+        Ok(())
+    }
+
+    fn execute(&mut self, chunk: &chunk::Chunk) -> Result<(), RunError> {
+        let result = if let Some(format) = self.options.profile {
+            let mut profiler = profiler::Profiler::new();
+            let result = self.vm.interpret_with(chunk, &mut profiler);
+            profiler.stop();
+            match format {
+                ProfileFormat::Report => eprint!("{}", profiler.report()),
+                ProfileFormat::Folded => eprint!("{}", profiler.folded()),
+            }
+            result
+        } else if self.options.trace {
+            self.vm
+                .interpret_with(chunk, &mut tracer::Tracer::new(io::stderr()))
+        } else {
+            self.vm.interpret(chunk)
+        };
+        let value = result.map_err(RunError::from)?;
+        self.finish(&value);
+
+        Ok(())
+    }
+
+    /// Prints what a program returned, unless it called `exit`.
+    fn finish(&mut self, value: &value::Value) {
+        match self.vm.exit_code() {
+            Some(code) => self.reporter.exit_with(code),
+            None if self.options.colors(io::stdout().is_terminal()) => {
+                println!("{}", highlight::highlight_value(value))
+            }
+            None => println!("{}", value),
+        }
+    }
+
+    /// Runs a script, or the bytecode in a `.udyrc` file on the VM.
+    fn run_file(&mut self, path: &str) {
+        let options = &self.options;
+        let result = if path.ends_with(".udyrc") {
+            let mut timer = Timer::new();
+            std::fs::read(path)
+                .map_err(|err| file_error(path, err))
+                .and_then(|bytes| {
+                    serialize::deserialize(&bytes)
+                        .map_err(|message| RunError::from(format!("{}: {}", path, message)))
+                })
+                .and_then(|chunk| {
+                    timer.stage("load");
+                    if self.options.dump_bytecode {
+                        print!("{}", disassembler::disassemble(&chunk, "code"));
+                    }
+                    self.execute(&chunk)?;
+                    timer.stage("run");
+                    if self.options.time {
+                        eprint!("{}", stage_table(&timer.stages));
+                    }
+                    Ok(())
+                })
+        } else {
+            let (stage, dump_bytecode, time) =
+                (Stage::of(options), options.dump_bytecode, options.time);
+            std::fs::read_to_string(path)
+                .map_err(|err| file_error(path, err))
+                .and_then(|source| self.run_source(&source, stage, dump_bytecode, time))
+        };
+        self.report(result);
+    }
+
+    /// Runs a script, then again every time it is saved, until stopped.
+    fn watch_file(&mut self, path: &str) {
+        let modified = || -> Option<SystemTime> { std::fs::metadata(path).ok()?.modified().ok() };
+        let mut last = modified();
+        self.run_file(path);
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            // A file that is missing for a moment is being saved.
+            let Some(time) = modified() else {
+                continue;
+            };
+            if last == Some(time) {
+                continue;
+            }
+            last = Some(time);
+            println!("--- {} changed, running it again ---", path);
+            self.run_file(path);
+        }
+    }
+
+    /// The mean time each stage of running `source` on the VM takes.
+    fn bench(&mut self, source: &str) -> Result<Vec<(&'static str, Duration)>, RunError> {
+        let options = &self.options;
+        let mean = |started: Instant| started.elapsed() / BENCH_RUNS;
+
+        let started = Instant::now();
+        let mut tokens = Vec::new();
+        for _ in 0..BENCH_RUNS {
+            let mut scanner = scanner::Scanner::new(source);
+            scanner.set_strict(options.strict);
+            tokens = scanner.scan_tokens().map_err(RunError::from)?;
+        }
+        let scan = mean(started);
+
+        let started = Instant::now();
+        let mut program = ast::Program::default();
+        for _ in 0..BENCH_RUNS {
+            let tokens = pragma::preprocess(&tokens, &options.defines).map_err(RunError::from)?;
+            program = parser::Parser::new(&tokens)
+                .parse()
+                .map_err(|message| RunError::from(message).in_source(source))?;
+            if options.opt_level > 0 {
+                program = optimizer::fold(program);
+            }
+        }
+        let parse = mean(started);
+
+        let started = Instant::now();
+        let mut chunk = chunk::Chunk::new();
+        for _ in 0..BENCH_RUNS {
+            chunk = compile(source, &program)?;
+        }
+        let compile = mean(started);
+
+        let started = Instant::now();
+        for _ in 0..BENCH_RUNS {
+            self.vm.interpret(&chunk).map_err(RunError::from)?;
+        }
+        let run = mean(started);
+
+        Ok(vec![
+            ("scan", scan),
+            ("parse", parse),
+            ("compile", compile),
+            ("run", run),
+        ])
+    }
+
+    fn bench_file(&mut self, path: &str) {
+        let result = std::fs::read_to_string(path)
+            .map_err(|err| file_error(path, err))
+            .and_then(|source| self.bench(&source))
+            .map(|stages| print!("{}", stage_table(&stages)));
+        self.report(result);
+    }
+
+    /// Compiles a script to bytecode, written next to it unless `-o` is given.
+    fn compile_file(&mut self, path: &str) {
+        let output = match &self.options.output {
+            Some(output) => PathBuf::from(output),
+            None => Path::new(path).with_extension("udyrc"),
+        };
+        let result = std::fs::read_to_string(path)
+            .map_err(|err| file_error(path, err))
+            .and_then(|source| {
+                let program = self.parse(&source)?;
+                compile(&source, &program)
+            })
+            .and_then(|chunk| {
+                std::fs::write(&output, serialize::serialize(&chunk))
+                    .map_err(|err| file_error(&output.display().to_string(), err))
+            });
+        self.report(result);
+    }
+
+    /// Reports problems in scripts, and in every script under the
+    /// directories among them, without running them. The scripts are
+    /// checked in parallel and reported in order of path, each with its
+    /// problems in order of line. The path is left out when there is only
+    /// one script. Fails if any script has a problem, for CI.
+    fn check_files(&mut self, paths: &[String]) {
+        let mut scripts = Vec::new();
+        for path in paths {
+            if let Err(err) = golden::find_scripts(Path::new(path), &mut scripts) {
+                self.reporter.report(&format!("{}: {}", path, err));
+                return self.reporter.fail();
+            }
+        }
+        scripts.sort();
+        scripts.dedup();
+
+        let options = &self.options;
+        let checked: Vec<(Option<String>, Vec<Diagnostic>)> = scripts
+            .par_iter()
+            .map(|script| match std::fs::read_to_string(script) {
+                Ok(source) => {
+                    let messages = check(&source, options);
+                    (Some(source), messages)
+                }
+                Err(err) => (None, vec![err.to_string().into()]),
+            })
+            .collect();
+        for (script, (source, messages)) in scripts.iter().zip(checked) {
+            if !messages.is_empty() {
+                self.reporter.fail();
+            }
+            for message in messages {
+                if scripts.len() == 1 {
+                    self.reporter.report_diagnostic(source.as_deref(), &message);
+                } else {
+                    self.reporter.report_diagnostic(
+                        source.as_deref(),
+                        &Diagnostic {
+                            message: format!("{}: {}", script.display(), message),
+                            ..message
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Runs a script on the VM under the interactive debugger.
+    fn debug_file(&mut self, path: &str) {
+        let result = std::fs::read_to_string(path)
+            .map_err(|err| file_error(path, err))
+            .and_then(|source| {
+                let program = self.parse(&source)?;
+                compile(&source, &program)
+            })
+            .and_then(|chunk| {
+                let mut debugger = debugger::Debugger::new(io::stdin().lock(), io::stdout());
+                self.vm
+                    .interpret_with(&chunk, &mut debugger)
+                    .map_err(RunError::from)
+            });
+        if let Ok(value) = &result {
+            self.finish(value);
+        }
+        self.report(result);
+    }
+
+    /// Shows the syntax tree of a script next to its source, to move
+    /// through with commands.
+    fn explore_file(&mut self, path: &str) {
+        let Some(source) = self.read(path) else {
+            return;
+        };
+        let result = self.parse(&source).and_then(|program| {
+            let mut explorer =
+                explorer::Explorer::new(&source, &program, io::stdin().lock(), io::stdout());
+            explorer
+                .run()
+                .map_err(|err| RunError::from(err.to_string()))
+        });
+        self.report(result);
+    }
+
+    /// Prints how two scripts differ, and fails if they do, so it can
+    /// check that a change only touched formatting.
+    fn diff_files(&mut self, old: &str, new: &str) {
+        let (Some(old_source), Some(new_source)) = (self.read(old), self.read(new)) else {
+            return;
+        };
+        let differences = if self.options.diff_ast {
+            diff::ast(&old_source, &new_source)
+        } else {
+            diff::tokens(&old_source, &new_source)
+        };
+        match differences {
+            Ok(lines) => {
+                for line in &lines {
+                    println!("{}", line);
+                }
+                if !lines.is_empty() {
+                    self.reporter.fail();
+                }
+            }
+            Err(messages) => {
+                self.reporter.report_all(&messages);
+                self.reporter.fail();
+            }
+        }
+    }
+
+    /// Prints documentation for a script, as Markdown unless `--html` is
+    /// given.
+    fn document_file(&mut self, path: &str) {
+        let Some(source) = self.read(path) else {
+            return;
+        };
+        let format = if self.options.html {
+            doc::Format::Html
+        } else {
+            doc::Format::Markdown
+        };
+        let name = Path::new(path)
+            .file_name()
+            .map_or(path.into(), |name| name.to_string_lossy());
+        match doc::generate(&name, &source, format) {
+            Ok(documentation) => print!("{}", documentation),
+            Err(messages) => {
+                self.reporter.report_all(&messages);
+                self.reporter.fail();
+            }
+        }
+    }
+
+    /// Formats scripts in place, with `--fix` fixing invalid escapes
+    /// first. Fails if a script cannot be scanned, or with `--check` if one
+    /// is not formatted.
+    fn format_files(&mut self, paths: &[String]) {
+        for path in paths {
+            let Some(source) = self.read(path) else {
+                continue;
+            };
+            let fixed = if self.options.fix {
+                formatter::fix(&source)
+            } else {
+                source.clone()
+            };
+            match formatter::format(&fixed) {
+                Ok(formatted) if formatted == source => {}
+                Ok(_) if self.options.check_formatting => {
+                    self.reporter.report(&format!("{} is not formatted.", path));
+                    self.reporter.fail();
+                }
+                Ok(formatted) => {
+                    if let Err(err) = std::fs::write(path, formatted) {
+                        self.report::<()>(Err(file_error(path, err)));
+                    }
+                }
+                Err(messages) => {
+                    for message in messages {
+                        self.reporter.report(&format!("{}: {}", path, message));
+                    }
+                    self.reporter.fail();
+                }
+            }
+        }
+    }
+
+    /// Runs the golden-file tests under `path`, failing if any of them do.
+    fn test_files(&mut self, path: &str) {
+        let summary = match golden::run_tests(Path::new(path)) {
+            Ok(summary) => summary,
+            Err(err) => {
+                self.reporter.report(&format!("{}: {}", path, err));
+                return self.reporter.fail();
+            }
+        };
+        for failure in &summary.failures {
+            println!("FAIL {}", failure.path.display());
+            for message in &failure.messages {
+                println!("     {}", message);
+            }
+        }
+        println!(
+            "{} passed, {} failed.",
+            summary.passed,
+            summary.failures.len()
+        );
+        if !summary.failures.is_empty() {
+            self.reporter.fail();
+        }
+    }
+
+    fn highlight_file(&mut self, path: &str) {
+        let Some(source) = self.read(path) else {
+            return;
+        };
+        let format = if self.options.html {
+            highlight::Format::Html
+        } else {
+            highlight::Format::Ansi
+        };
+        print!("{}", highlight::highlight(&source, format));
+    }
+
+    /// Prints a template with the values of the code in it, which runs
+    /// with the same limits and permissions as a script.
+    fn render_file(&mut self, path: &str) {
+        let Some(source) = self.read(path) else {
+            return;
+        };
+        let mut interpreter = interpreter::Interpreter::new();
+        interpreter.set_limits(self.options.limits.clone());
+        interpreter.set_capabilities(self.options.capabilities.clone());
+        interpreter.set_defines(self.options.defines.clone());
+        interpreter.set_opt_level(self.options.opt_level);
+        match template::render(&source, &mut interpreter) {
+            Ok(output) => print!("{}", output),
+            Err(messages) => {
+                self.reporter.report_all(&messages);
+                self.reporter.fail();
+            }
+        }
+    }
+
+    #[cfg(feature = "lsp")]
+    fn run_language_server(&mut self) {
+        if let Err(err) = udyr::lsp::serve(io::stdin().lock(), io::stdout().lock()) {
+            self.reporter.report(&err.to_string());
+            self.reporter.fail();
+        }
+    }
+
+    #[cfg(not(feature = "lsp"))]
+    fn run_language_server(&mut self) {
+        self.reporter
+            .report("udyr was built without the lsp feature.");
+        self.reporter.fail();
+    }
+
+    fn run_prompt(&mut self) {
+        let colors = self
+            .options
+            .colors(io::stdin().is_terminal() && io::stdout().is_terminal());
+        let prompt = self
+            .options
+            .prompt
+            .clone()
+            .unwrap_or_else(|| String::from("> "));
+        let mut history = self.options.history.as_ref().and_then(|path| {
+            let file = OpenOptions::new().create(true).append(true).open(path);
+            if let Err(err) = &file {
+                self.reporter
+                    .report(&format!("{}: {}", path.display(), err));
+            }
+            file.ok()
+        });
+        let mut editor = match rustyline::Editor::<Editor, DefaultHistory>::new() {
+            Ok(editor) => editor,
+            Err(err) => {
+                self.reporter.report(&err.to_string());
+                self.reporter.fail();
+                return;
+            }
+        };
+        editor.set_helper(Some(Editor { colors }));
+        loop {
+            // An empty line, end of input or Ctrl-C ends the session.
+            let line = match editor.readline(&prompt) {
+                Ok(line) if !line.is_empty() => line,
+                _ => break,
+            };
+            let _ = editor.add_history_entry(line.as_str());
+            if let Some(history) = &mut history {
+                let _ = writeln!(history, "{}", line);
+            }
+            // `:bytecode <code>` shows the bytecode for a snippet without
+            // running it, and `:time <code>` how long each stage of running
+            // it takes.
+            let (stage, dump_bytecode, time) = (
+                Stage::of(&self.options),
+                self.options.dump_bytecode,
+                self.options.time,
+            );
+            let result = if let Some(snippet) = line.strip_prefix(":bytecode") {
+                self.run_source(snippet, Stage::Compile, true, false)
+            } else if let Some(snippet) = line.strip_prefix(":time") {
+                self.run_source(snippet, stage, dump_bytecode, true)
+            } else {
+                self.run_source(&line, stage, dump_bytecode, time)
+            };
+            // A mistake on one line does not fail the session.
+            if let Err(err) = result {
+                self.reporter
+                    .report_diagnostics(err.source.as_deref(), &err.messages);
+            }
+            if self.vm.exit_code().is_some() {
+                break;
+            }
+        }
+    }
+}
+
+/// How far to take a program.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    /// Print its syntax tree.
+    Parse,
+    Compile,
+    /// Compile it and run it on the VM.
+    Run,
+}
+
+impl Stage {
+    /// Programs run unless `--print-ast` is given.
+    fn of(options: &Options) -> Stage {
+        if options.print_ast {
+            return Stage::Parse;
+        }
+        Stage::Run
+    }
+}
+
+/// Times the stages of running a program, for `--time`.
+struct Timer {
+    started: Instant,
+    stages: Vec<(&'static str, Duration)>,
+}
+
+impl Timer {
+    fn new() -> Timer {
+        Timer {
+            started: Instant::now(),
+            stages: Vec::new(),
+        }
+    }
+
+    /// Ends the stage called `name` and starts the next.
+    fn stage(&mut self, name: &'static str) {
+        self.stages.push((name, self.started.elapsed()));
+        self.started = Instant::now();
+    }
+}
+
+/// One line per stage with how long it took, for `--time` and `--bench`.
+fn stage_table(stages: &[(&'static str, Duration)]) -> String {
+    stages
+        .iter()
+        .map(|(stage, time)| format!("{:<8} {:>12.3?}\n", stage, time))
+        .collect()
+}
+
+/// Scans `source` and applies its pragmas.
+fn scan(source: &str, options: &Options) -> Result<Vec<token::Token>, RunError> {
+    let mut scanner = scanner::Scanner::new(source);
+    scanner.set_strict(options.strict);
+    let tokens = scanner.scan_tokens().map_err(RunError::from)?;
+    pragma::preprocess(&tokens, &options.defines).map_err(RunError::from)
+}
+
+/// Parses `tokens` and returns the program with the warnings found in it.
+fn parse_tokens(
+    tokens: &[token::Token],
+    options: &Options,
+) -> Result<(ast::Program, Vec<Diagnostic>), RunError> {
+    let program = parser::Parser::new(tokens)
+        .parse()
+        .map_err(RunError::from)?;
+    let warnings = options
+        .lints
+        .apply(analysis::unreachable_code(&program))
+        .map_err(RunError::from)?;
+    if options.opt_level > 0 {
+        return Ok((optimizer::fold(program), warnings));
+    }
+    Ok((program, warnings))
+}
+
+/// The errors and warnings in `source`, in order of line, for `udyr check`.
+fn check(source: &str, options: &Options) -> Vec<Diagnostic> {
+    let mut messages = match scan(source, options).and_then(|tokens| parse_tokens(&tokens, options))
+    {
+        Ok((program, mut warnings)) => {
+            if options.check_types {
+                warnings.extend(types::check(&program));
+            }
+            warnings
+        }
+        Err(err) => err.messages,
+    };
+    messages.sort_by_key(|message| line_of(&message.message));
+    messages
+}
+
+/// The line a diagnostic like `[line 3] Error: ...` is about, or 0 if it
+/// does not say.
+fn line_of(message: &str) -> usize {
+    message
+        .strip_prefix("[line ")
+        .and_then(|rest| rest.split(']').next())
+        .and_then(|line| line.parse().ok())
+        .unwrap_or(0)
+}
+
+/// The error for a file that cannot be read or written.
+fn file_error(path: &str, err: io::Error) -> RunError {
+    RunError::from(format!("{}: {}", path, err))
+}
+
+/// Compiles `program`, which was parsed from `source`.
+fn compile(source: &str, program: &ast::Program) -> Result<chunk::Chunk, RunError> {
+    compiler::compile(program).map_err(|message| RunError::from(message).in_source(source))
+}
+
+/// The script to run for `path`, which is `main.udyr` in it if it is a
+/// project directory.
+fn entry_point(path: &Path) -> Result<String, String> {
+    if !path.is_dir() {
+        return Ok(path.to_string_lossy().into_owned());
+    }
+    let main = path.join(ENTRY_POINT);
+    if !main.is_file() {
+        return Err(format!("{} has no {} to run.", path.display(), ENTRY_POINT));
+    }
+    Ok(main.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn front_end(args: &[&str]) -> FrontEnd {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let (options, args) = Options::from_args(args);
+        FrontEnd::from_args(&args, &options)
+    }
+
+    #[test]
+    fn test_front_end() -> Result<(), String> {
+        assert_eq!(front_end(&[]), FrontEnd::Repl);
+        assert_eq!(
+            front_end(&["a.udyr"]),
+            FrontEnd::File(String::from("a.udyr"), Vec::new())
+        );
+        assert_eq!(
+            front_end(&["run", "a.udyr", "b", "--", "--strict"]),
+            FrontEnd::File(
+                String::from("a.udyr"),
+                vec![String::from("b"), String::from("--strict")]
+            )
+        );
+        assert_eq!(
+            front_end(&["--bench", "a.udyr"]),
+            FrontEnd::Bench(String::from("a.udyr"))
+        );
+        assert_eq!(
+            front_end(&["run", "--bench", "a.udyr"]),
+            FrontEnd::Bench(String::from("a.udyr"))
+        );
+        assert_eq!(front_end(&["--bench", "a.udyr", "arg"]), FrontEnd::Usage);
+        assert_eq!(
+            front_end(&["run", "--bench", "a.udyr", "arg"]),
+            FrontEnd::Usage
+        );
+        assert_eq!(
+            front_end(&["-e", "1 + 2"]),
+            FrontEnd::Eval(String::from("1 + 2"))
+        );
+        assert_eq!(
+            front_end(&["fmt", "a.udyr", "b.udyr"]),
+            FrontEnd::Format(vec![String::from("a.udyr"), String::from("b.udyr")])
+        );
+        assert_eq!(
+            front_end(&["check", "a.udyr", "src"]),
+            FrontEnd::Check(vec![String::from("a.udyr"), String::from("src")])
+        );
+        assert_eq!(
+            front_end(&["diff", "--ast", "a.udyr", "b.udyr"]),
+            FrontEnd::Diff(String::from("a.udyr"), String::from("b.udyr"))
+        );
+        assert_eq!(
+            front_end(&["doc", "--md", "a.udyr"]),
+            FrontEnd::Doc(String::from("a.udyr"))
+        );
+        for command in ["check", "fmt", "test", "doc", "run", "--bench compile"] {
+            assert_eq!(
+                front_end(&command.split(' ').collect::<Vec<_>>()),
+                FrontEnd::Usage
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Runs the front end for `args` with `source` as the script, and
+    /// returns what the program exits with.
+    fn exit_code(args: &[&str], source: &str) -> ExitCode {
+        let script = env::temp_dir().join(format!(
+            "udyr-exit-{}-{}.udyr",
+            std::process::id(),
+            args.join("-")
+        ));
+        std::fs::write(&script, source).unwrap();
+        let script = script.display().to_string();
+        let exit_code = run(&[args, &[script.as_str()]].concat());
+        std::fs::remove_file(&script).unwrap();
+        exit_code
+    }
+
+    /// Runs the front end for `args` and returns what the program exits
+    /// with.
+    fn run(args: &[&str]) -> ExitCode {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let (options, args) = Options::from_args(args);
+        let front_end = FrontEnd::from_args(&args, &options);
+        let mut application = Application::new(options, front_end);
+        application.run();
+        application.destroy()
+    }
+
+    #[test]
+    fn test_exit_code() -> Result<(), String> {
+        assert_eq!(exit_code(&[], "1 + 2"), ExitCode::SUCCESS);
+        assert_eq!(exit_code(&[], "1 - \"a\""), ExitCode::FAILURE);
+        assert_eq!(exit_code(&[], "1 +"), ExitCode::FAILURE);
+        assert_eq!(exit_code(&[], "exit(3)"), ExitCode::from(3));
+        assert_eq!(exit_code(&["check"], "1 + 2"), ExitCode::SUCCESS);
+        assert_eq!(exit_code(&["check"], "1 +"), ExitCode::FAILURE);
+        assert_eq!(exit_code(&["check"], "-nil"), ExitCode::SUCCESS);
+        assert_eq!(exit_code(&["check", "--types"], "-nil"), ExitCode::FAILURE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_files() -> Result<(), String> {
+        let missing = env::temp_dir().join("udyr-missing.udyr");
+        let missing = missing.to_str().unwrap();
+        for args in [
+            vec![missing],
+            vec!["--bench", missing],
+            vec![missing.trim_end_matches(".udyr"), "x"],
+            vec!["run", "missing.udyrc"],
+            vec!["compile", missing],
+            vec!["debug", missing],
+            vec!["explore", missing],
+            vec!["diff", missing, missing],
+            vec!["doc", missing],
+            vec!["fmt", missing],
+            vec!["highlight", missing],
+            vec!["render", missing],
+            vec!["test", missing],
+        ] {
+            assert_eq!(run(&args), ExitCode::FAILURE, "udyr {}", args.join(" "));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check() -> Result<(), String> {
+        let (options, _) = Options::from_args(vec![String::from("--types")]);
+        let check = |source: &str| -> Vec<String> {
+            let messages = check(source, &options);
+            messages.into_iter().map(String::from).collect()
+        };
+        assert_eq!(check("1 + 2"), Vec::<String>::new());
+        assert_eq!(
+            check("\"a\nb\" + @"),
+            vec!["[line 2] Error: Unexpected character."]
+        );
+        assert_eq!(
+            check("-nil +\n(true ? 1 : 2)"),
+            vec![
+                "[line 1] Error at '-': Operand must be a number.",
+                "[line 2] Warning: Unreachable code.",
+            ]
+        );
+        assert_eq!(line_of("[line 12] Error: Expected expression."), 12);
+        assert_eq!(line_of("a.udyr is not formatted."), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_point() -> Result<(), String> {
+        let project = env::temp_dir().join(format!("udyr-project-{}", std::process::id()));
+        std::fs::create_dir_all(&project).unwrap();
+        assert_eq!(
+            entry_point(&project),
+            Err(format!("{} has no main.udyr to run.", project.display()))
+        );
+
+        let main = project.join("main.udyr");
+        std::fs::write(&main, "1").unwrap();
+        assert_eq!(entry_point(&project), Ok(main.display().to_string()));
+        assert_eq!(entry_point(&main), Ok(main.display().to_string()));
+        std::fs::remove_dir_all(&project).unwrap();
+
+        Ok(())
+    }
+}
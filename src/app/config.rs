@@ -0,0 +1,195 @@
+//! Settings from a `udyr.toml` file, which the command line flags
+//! override:
+//!
+//! ```toml
+//! opt-level = 1
+//! warnings-are-errors = false
+//! prompt = "udyr> "
+//! history = ".udyr_history"
+//! log-level = "warn"
+//!
+//! [lints]
+//! unreachable_code = "allow"
+//!
+//! [sandbox]
+//! max-steps = 1000000
+//! max-stack-depth = 4096
+//! timeout = 5000 # milliseconds
+//! allow-exec = false
+//! allow-net = false
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use toml::{Table, Value};
+use udyr::{lint, natives};
+
+use super::options::Options;
+
+pub const FILE_NAME: &str = "udyr.toml";
+
+/// The `udyr.toml` in `directory` or the closest of its ancestors.
+pub fn find(directory: &Path) -> Option<PathBuf> {
+    directory
+        .ancestors()
+        .map(|directory| directory.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Reads the settings in `path` into `options`.
+pub fn load(path: &Path, options: &mut Options) -> Result<(), String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    apply(&text, options).map_err(|message| format!("{}: {}", path.display(), message))
+}
+
+pub fn apply(text: &str, options: &mut Options) -> Result<(), String> {
+    let table: Table = text
+        .parse()
+        .map_err(|err: toml::de::Error| err.message().to_string())?;
+    for (key, value) in &table {
+        match (key.as_str(), value) {
+            ("opt-level", Value::Integer(level)) if (0..=1).contains(level) => {
+                options.opt_level = *level as u8;
+            }
+            ("warnings-are-errors", Value::Boolean(errors)) => {
+                options.lints.set_warnings_are_errors(*errors);
+            }
+            ("prompt", Value::String(prompt)) => options.prompt = Some(prompt.clone()),
+            ("history", Value::String(history)) => options.history = Some(PathBuf::from(history)),
+            ("log-level", Value::String(level)) => {
+                options.log_level =
+                    natives::LogLevel::from_name(level).ok_or_else(|| invalid(key))?;
+            }
+            ("lints", Value::Table(lints)) => apply_lints(lints, options)?,
+            ("sandbox", Value::Table(sandbox)) => apply_sandbox(sandbox, options)?,
+            _ => return Err(invalid(key)),
+        }
+    }
+    Ok(())
+}
+
+fn apply_lints(lints: &Table, options: &mut Options) -> Result<(), String> {
+    for (name, level) in lints {
+        let lint =
+            lint::Lint::from_name(name).ok_or_else(|| format!("Unknown lint '{}'.", name))?;
+        let level = match level.as_str() {
+            Some("allow") => lint::Level::Allow,
+            Some("warn") => lint::Level::Warn,
+            _ => {
+                return Err(format!(
+                    "The level of '{}' must be \"allow\" or \"warn\".",
+                    name
+                ))
+            }
+        };
+        options.lints.set_level(lint, level);
+    }
+    Ok(())
+}
+
+fn apply_sandbox(sandbox: &Table, options: &mut Options) -> Result<(), String> {
+    for (key, value) in sandbox {
+        match (key.as_str(), value) {
+            ("allow-exec", Value::Boolean(allow)) => options.capabilities.exec = *allow,
+            ("allow-net", Value::Boolean(allow)) => options.capabilities.net = *allow,
+            (key, Value::Integer(value)) if *value >= 0 => match key {
+                "max-steps" => options.limits.max_steps = Some(*value as u64),
+                "max-stack-depth" => options.limits.max_stack_depth = *value as usize,
+                "timeout" => options.limits.timeout = Some(Duration::from_millis(*value as u64)),
+                _ => return Err(invalid(&format!("sandbox.{}", key))),
+            },
+            _ => return Err(invalid(&format!("sandbox.{}", key))),
+        }
+    }
+    Ok(())
+}
+
+fn invalid(key: &str) -> String {
+    format!("Unknown or invalid setting '{}'.", key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() -> Result<(), String> {
+        let mut options = Options::default();
+        apply(
+            "opt-level = 1\n\
+             prompt = \"udyr> \"\n\
+             history = \".udyr_history\"\n\
+             log-level = \"debug\"\n\
+             [lints]\n\
+             unreachable_code = \"allow\"\n\
+             [sandbox]\n\
+             max-steps = 1000\n\
+             timeout = 50\n\
+             allow-exec = true\n",
+            &mut options,
+        )?;
+
+        assert_eq!(options.opt_level, 1);
+        assert_eq!(options.prompt.as_deref(), Some("udyr> "));
+        assert_eq!(options.history, Some(PathBuf::from(".udyr_history")));
+        assert_eq!(options.log_level, natives::LogLevel::Debug);
+        assert_eq!(
+            options.lints.level(lint::Lint::UnreachableCode),
+            lint::Level::Allow
+        );
+        assert_eq!(options.limits.max_steps, Some(1000));
+        assert_eq!(options.limits.timeout, Some(Duration::from_millis(50)));
+        assert!(options.capabilities.exec);
+        assert!(!options.capabilities.net);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_flags_override() -> Result<(), String> {
+        let mut options = Options::default();
+        apply(
+            "opt-level = 1\n[sandbox]\nmax-steps = 5\nallow-net = false",
+            &mut options,
+        )?;
+        let (options, _) = options
+            .with_args(["--opt-level=0", "--max-steps", "7", "--allow-net"].map(String::from));
+
+        assert_eq!(options.opt_level, 0);
+        assert_eq!(options.limits.max_steps, Some(7));
+        assert!(options.capabilities.net);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid() -> Result<(), String> {
+        let apply = |text: &str| apply(text, &mut Options::default()).unwrap_err();
+
+        assert_eq!(
+            apply("opt-level = 3"),
+            "Unknown or invalid setting 'opt-level'."
+        );
+        assert_eq!(
+            apply("[sandbox]\nfiles = 1"),
+            "Unknown or invalid setting 'sandbox.files'."
+        );
+        assert_eq!(
+            apply("[sandbox]\nallow-exec = 1"),
+            "Unknown or invalid setting 'sandbox.allow-exec'."
+        );
+        assert_eq!(
+            apply("[sandbox]\ntimeout = true"),
+            "Unknown or invalid setting 'sandbox.timeout'."
+        );
+        assert_eq!(
+            apply("[lints]\nunused = \"allow\""),
+            "Unknown lint 'unused'."
+        );
+        assert!(!apply("prompt = ").is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+//! The line editor behind the REPL: completion and highlighting as the
+//! user types.
+
+use std::borrow::Cow;
+
+use rustyline::completion::Completer;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use udyr::{completion, highlight};
+
+pub struct Editor {
+    /// Highlight the line being typed.
+    pub colors: bool,
+}
+
+impl Completer for Editor {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        position: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(completion::complete(line, position))
+    }
+}
+
+impl Highlighter for Editor {
+    fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+        if !self.colors {
+            return Cow::Borrowed(line);
+        }
+        Cow::Owned(highlight::highlight(line, highlight::Format::Ansi))
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(&'s self, prompt: &'p str, _: bool) -> Cow<'b, str> {
+        if !self.colors {
+            return Cow::Borrowed(prompt);
+        }
+        Cow::Owned(format!("\x1b[1;34m{}\x1b[0m", prompt))
+    }
+
+    fn highlight_char(&self, _: &str, _: usize, _: CmdKind) -> bool {
+        self.colors
+    }
+}
+
+impl Hinter for Editor {
+    type Hint = String;
+}
+
+impl Validator for Editor {}
+
+impl Helper for Editor {}
@@ -0,0 +1,8 @@
+//! The command line program: its options, how it reports problems, and the
+//! [`application::Application`] tying them to the library.
+
+pub mod application;
+pub mod config;
+pub mod editor;
+pub mod options;
+pub mod reporter;
@@ -0,0 +1,211 @@
+use std::collections::HashSet;
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use udyr::{lint, natives, vm};
+
+/// How `--profile` reports where the VM spent its time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProfileFormat {
+    /// A table of source lines, the slowest first.
+    Report,
+    /// Folded stacks for flamegraph tools.
+    Folded,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub strict: bool,
+    pub defines: HashSet<String>,
+    /// Print the syntax tree of the program instead of running it.
+    pub print_ast: bool,
+    /// Print the bytecode of the program before running it.
+    pub dump_bytecode: bool,
+    /// Where `udyr compile` writes the bytecode.
+    pub output: Option<String>,
+    /// 0 runs the program as written, 1 folds constant expressions first.
+    pub opt_level: u8,
+    pub lints: lint::Lints,
+    /// Make `udyr check` look for type errors too.
+    pub check_types: bool,
+    pub limits: vm::Limits,
+    /// What the program may do outside itself, like running commands.
+    pub capabilities: natives::Capabilities,
+    /// The least important messages `log.info(...)` and friends write.
+    pub log_level: natives::LogLevel,
+    /// Log every instruction the VM runs to stderr.
+    pub trace: bool,
+    /// Profile the program on the VM and print the result to stderr.
+    pub profile: Option<ProfileFormat>,
+    /// Make `udyr fmt` report unformatted files instead of rewriting them.
+    pub check_formatting: bool,
+    /// Make `udyr fmt` rewrite invalid escapes to the form their error
+    /// suggests.
+    pub fix: bool,
+    /// Make `udyr highlight` write HTML instead of terminal colors, and
+    /// `udyr doc` HTML instead of Markdown.
+    pub html: bool,
+    /// Make `udyr diff` compare syntax trees instead of tokens.
+    pub diff_ast: bool,
+    /// Time each stage of running the script instead of printing its value.
+    pub bench: bool,
+    /// Print how long each stage of running the program took to stderr.
+    pub time: bool,
+    /// Run the script again every time it changes.
+    pub watch: bool,
+    /// What the REPL prints before each line, `> ` unless set.
+    pub prompt: Option<String>,
+    /// A file the REPL appends every line entered to.
+    pub history: Option<PathBuf>,
+    /// Never write colors, even to a terminal.
+    pub no_color: bool,
+    /// A script run before the program, instead of `~/.udyrrc.udyr` in the
+    /// REPL.
+    pub prelude: Option<PathBuf>,
+}
+
+impl Options {
+    /// Whether to write colors to a stream, which should be a terminal.
+    /// `--no-color` and a non-empty `NO_COLOR` variable turn them off.
+    pub fn colors(&self, terminal: bool) -> bool {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        terminal && !self.no_color && !no_color
+    }
+
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> (Options, Vec<String>) {
+        Options::default().with_args(args)
+    }
+
+    /// Reads the flags from `args` over these options, returning the
+    /// result and the remaining arguments. A flag missing its value is left
+    /// in the arguments, so it shows up as a usage error. Everything after
+    /// `--` is left as it is, for the script.
+    pub fn with_args(self, args: impl IntoIterator<Item = String>) -> (Options, Vec<String>) {
+        let mut options = self;
+        let mut rest: Vec<String> = Vec::new();
+        let mut args = args.into_iter().peekable();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--" => {
+                    rest.extend(args.by_ref());
+                }
+                "--strict" => options.strict = true,
+                "--print-ast" => options.print_ast = true,
+                "--dump-bytecode" => options.dump_bytecode = true,
+                "--trace" => options.trace = true,
+                "--profile" => options.profile = Some(ProfileFormat::Report),
+                "--profile=folded" => options.profile = Some(ProfileFormat::Folded),
+                "-O" => options.opt_level = 1,
+                "--types" => options.check_types = true,
+                "--check" => options.check_formatting = true,
+                "--fix" => options.fix = true,
+                "--html" => options.html = true,
+                "--md" => options.html = false,
+                "--ast" => options.diff_ast = true,
+                "--bench" => options.bench = true,
+                "--time" => options.time = true,
+                "--watch" => options.watch = true,
+                "--no-color" => options.no_color = true,
+                "--allow-exec" => options.capabilities.exec = true,
+                "--allow-net" => options.capabilities.net = true,
+                level if level.starts_with("--opt-level=") => {
+                    match level["--opt-level=".len()..].parse() {
+                        Ok(level) if level <= 1 => options.opt_level = level,
+                        _ => rest.push(arg),
+                    }
+                }
+                "-o" => match args.next() {
+                    Some(output) => options.output = Some(output),
+                    None => rest.push(arg),
+                },
+                // The source to evaluate stays with the arguments, as the
+                // front end to use.
+                "-e" | "--eval" => match args.next() {
+                    Some(source) => rest.extend([String::from("-e"), source]),
+                    None => rest.push(arg),
+                },
+                "-W" => {
+                    if args.next_if(|arg| arg == "error").is_some() {
+                        options.lints.set_warnings_are_errors(true);
+                    } else {
+                        options.lints.warn_all();
+                    }
+                }
+                "--allow" => match args.next().as_deref().and_then(lint::Lint::from_name) {
+                    Some(lint) => options.lints.set_level(lint, lint::Level::Allow),
+                    None => rest.push(arg),
+                },
+                "--log-level" => match args
+                    .next()
+                    .as_deref()
+                    .and_then(natives::LogLevel::from_name)
+                {
+                    Some(level) => options.log_level = level,
+                    None => rest.push(arg),
+                },
+                "--max-steps" => match args.next().and_then(|steps| steps.parse().ok()) {
+                    Some(steps) => options.limits.max_steps = Some(steps),
+                    None => rest.push(arg),
+                },
+                "--timeout" => match args.next().and_then(|ms| ms.parse().ok()) {
+                    Some(ms) => options.limits.timeout = Some(Duration::from_millis(ms)),
+                    None => rest.push(arg),
+                },
+                "--prelude" => match args.next() {
+                    Some(path) => options.prelude = Some(PathBuf::from(path)),
+                    None => rest.push(arg),
+                },
+                "--define" => match args.next() {
+                    Some(name) => {
+                        options.defines.insert(name);
+                    }
+                    None => rest.push(arg),
+                },
+                _ => rest.push(arg),
+            }
+        }
+        (options, rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_from_args() -> Result<(), String> {
+        let (options, rest) = Options::from_args(args(
+            "--print-ast run -O --define A script.udyr --max-steps 10 -W error --watch",
+        ));
+
+        assert!(options.print_ast);
+        assert_eq!(options.opt_level, 1);
+        assert!(options.defines.contains("A"));
+        assert_eq!(options.limits.max_steps, Some(10));
+        assert!(options.watch);
+        assert_eq!(rest, args("run script.udyr"));
+
+        let (options, rest) = Options::from_args(args("--prelude p.udyr --eval 1+2 --timeout"));
+        assert_eq!(options.prelude, Some(PathBuf::from("p.udyr")));
+        assert_eq!(rest, args("-e 1+2 --timeout"));
+
+        let (options, rest) =
+            Options::from_args(args("--allow-exec --no-color a.udyr x -- --strict"));
+        assert!(!options.strict);
+        assert!(options.capabilities.exec);
+        assert!(!options.colors(true));
+        assert!(!options.watch);
+        assert_eq!(rest, args("a.udyr x --strict"));
+
+        let (options, rest) = Options::from_args(args("--log-level warn --log-level loud"));
+        assert_eq!(options.log_level, natives::LogLevel::Warn);
+        assert_eq!(rest, args("--log-level"));
+
+        Ok(())
+    }
+}
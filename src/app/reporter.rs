@@ -0,0 +1,179 @@
+use std::io::{self, Write};
+use std::process::ExitCode;
+
+use udyr::error::Diagnostic;
+
+/// Writes diagnostics to stderr and remembers what the program should exit
+/// with.
+#[derive(Debug, Default)]
+pub struct Reporter {
+    /// Color errors red and warnings yellow, and show the source line a
+    /// diagnostic is about with `^^^` under its token.
+    colors: bool,
+    failed: bool,
+    /// The code a script asked to exit with.
+    exit_code: Option<u8>,
+}
+
+impl Reporter {
+    pub fn new(colors: bool) -> Reporter {
+        Reporter {
+            colors,
+            ..Reporter::default()
+        }
+    }
+
+    pub fn report(&mut self, message: &str) {
+        eprintln!("{}", self.color(message));
+    }
+
+    fn color(&self, message: &str) -> String {
+        self.paint(message, message)
+    }
+
+    /// `text` in the color of `message`: red for an error and yellow for a
+    /// warning.
+    fn paint(&self, message: &str, text: &str) -> String {
+        let color = if !self.colors {
+            None
+        } else if message.contains("] Error") {
+            Some("\x1b[31m")
+        } else if message.contains("] Warning") {
+            Some("\x1b[33m")
+        } else {
+            None
+        };
+        match color {
+            Some(color) => format!("{}{}\x1b[0m", color, text),
+            None => text.to_string(),
+        }
+    }
+
+    /// Reports `diagnostic`, which is about `source` if it is given.
+    pub fn report_diagnostic(&mut self, source: Option<&str>, diagnostic: &Diagnostic) {
+        self.report(&diagnostic.message);
+        if let Some(carets) = source.and_then(|source| self.carets(source, diagnostic)) {
+            eprintln!("{}", carets);
+        }
+    }
+
+    /// The line of `source` that `diagnostic` is about, with `^^^` under
+    /// its token, when colors are on.
+    fn carets(&self, source: &str, diagnostic: &Diagnostic) -> Option<String> {
+        let span = diagnostic.span.clone().filter(|_| self.colors)?;
+        let start = span.start.min(source.len());
+        if !source.is_char_boundary(start) {
+            return None;
+        }
+        let line_start = source[..start].rfind('\n').map_or(0, |newline| newline + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |newline| start + newline);
+        let line = source[line_start..line_end].trim_end_matches('\r');
+        // Tabs are kept so the carets line up however wide they are shown.
+        let indent: String = source[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let end = span.end.clamp(start, line_end);
+        let width = source
+            .get(start..end)
+            .map_or(0, |token| token.chars().count());
+        let width = width.max(1);
+        let carets = self.paint(&diagnostic.message, &"^".repeat(width));
+        Some(format!("{}\n{}{}", line, indent, carets))
+    }
+
+    pub fn report_all(&mut self, messages: &[String]) {
+        for message in messages {
+            self.report(message);
+        }
+    }
+
+    pub fn report_diagnostics(&mut self, source: Option<&str>, diagnostics: &[Diagnostic]) {
+        for diagnostic in diagnostics {
+            self.report_diagnostic(source, diagnostic);
+        }
+    }
+
+    /// Makes the program exit with an error, like when `udyr fmt --check`
+    /// finds a file that is not formatted.
+    pub fn fail(&mut self) {
+        self.failed = true;
+    }
+
+    /// Makes the program exit with `code`, like when a script calls `exit`.
+    pub fn exit_with(&mut self, code: u8) {
+        self.exit_code = Some(code);
+    }
+
+    /// What the program exits with: the code a script asked for, or
+    /// whether anything failed.
+    pub fn exit_code(&self) -> ExitCode {
+        match self.exit_code {
+            Some(code) => ExitCode::from(code),
+            None if self.failed => ExitCode::FAILURE,
+            None => ExitCode::SUCCESS,
+        }
+    }
+
+    pub fn flush(&mut self) {
+        let _ = io::stderr().flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use udyr::{parser::Parser, scanner::Scanner};
+
+    use super::*;
+
+    #[test]
+    fn test_color() -> Result<(), String> {
+        let error = "[line 1] Error at '+': Expected expression.";
+        let warning = "[line 2] Warning: Unreachable code.";
+
+        assert_eq!(Reporter::new(false).color(error), error);
+        assert_eq!(
+            Reporter::new(true).color(error),
+            format!("\x1b[31m{}\x1b[0m", error)
+        );
+        assert_eq!(
+            Reporter::new(true).color(warning),
+            format!("\x1b[33m{}\x1b[0m", warning)
+        );
+        assert_eq!(Reporter::new(true).color("1 passed."), "1 passed.");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_carets() -> Result<(), String> {
+        let carets = |source: &str, colors: bool| {
+            let tokens = Scanner::new(source).scan_tokens().unwrap();
+            let error = Parser::new(&tokens).parse().unwrap_err();
+            Reporter::new(colors).carets(source, &error)
+        };
+
+        assert_eq!(
+            carets("1 +\n\t(2 * ) + 3", true),
+            Some(String::from("\t(2 * ) + 3\n\t     \x1b[31m^\x1b[0m"))
+        );
+        assert_eq!(carets("1 +\n\t(2 * ) + 3", false), None);
+        assert_eq!(
+            carets("\"é\" + \"ab\" \"cd\"", true),
+            Some(String::from(
+                "\"é\" + \"ab\" \"cd\"\n           \x1b[31m^^^^\x1b[0m"
+            ))
+        );
+        // The end of the source, which has no token to underline.
+        assert_eq!(
+            carets("1 +", true),
+            Some(String::from("1 +\n   \x1b[31m^\x1b[0m"))
+        );
+        let message = Diagnostic::from(String::from("a.udyr: not found"));
+        assert_eq!(Reporter::new(true).carets("1 +", &message), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,373 @@
+use std::fmt;
+use std::ops::{Index, IndexMut, Range};
+
+use crate::token::Token;
+
+/// Where an expression is in the [`Ast`] that holds it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(u32);
+
+/// An expression, whose subexpressions are in the same [`Ast`] as it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Binary {
+        left: ExprId,
+        operator: Token,
+        right: ExprId,
+    },
+    /// A call, which for now can only be a method call like `s.trim()`.
+    Call {
+        callee: ExprId,
+        paren: Token,
+        arguments: Vec<ExprId>,
+    },
+    Conditional {
+        condition: ExprId,
+        then_branch: ExprId,
+        else_branch: ExprId,
+    },
+    /// `object.name`
+    Get {
+        object: ExprId,
+        name: Token,
+    },
+    Grouping(ExprId),
+    /// `object[index]`, with the `[` to report errors at.
+    Index {
+        object: ExprId,
+        bracket: Token,
+        index: ExprId,
+    },
+    Literal(Token),
+    /// `match subject { pattern => body, ... }`, whose value is the body of
+    /// the first arm that matches.
+    Match {
+        keyword: Token,
+        subject: ExprId,
+        arms: Vec<Arm>,
+    },
+    /// `(a, b)`, with the `(` to report errors at. A tuple of one element
+    /// is written `(a,)`.
+    Tuple {
+        paren: Token,
+        elements: Vec<ExprId>,
+    },
+    Unary {
+        operator: Token,
+        right: ExprId,
+    },
+    Variable(Token),
+}
+
+/// `pattern if guard => body`, one arm of a [`Expr::Match`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub guard: Option<ExprId>,
+    pub body: ExprId,
+}
+
+/// What a value must look like for a match arm to be taken. Names in it
+/// are bound for the guard and body of the arm.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// `_`, which matches anything.
+    Wildcard(Token),
+    /// A name, which matches anything and is bound to it.
+    Binding(Token),
+    /// A literal, which matches values equal to it.
+    Literal(Token),
+    /// `(a, b)`, which matches tuples of as many elements as it has, when
+    /// each element matches.
+    Tuple {
+        paren: Token,
+        elements: Vec<Pattern>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Expression(ExprId),
+}
+
+/// The expressions of a program, all in one arena. They refer to each
+/// other by [`ExprId`] rather than owning each other, so a tree is built
+/// without allocating every node on its own, and a pass can replace a
+/// node in place.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Ast {
+    exprs: Vec<Expr>,
+}
+
+/// A parsed program: its statements and the expressions they are made of.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+    pub ast: Ast,
+    pub statements: Vec<Stmt>,
+}
+
+impl Program {
+    /// Prints the tree of every statement, for `--print-ast`.
+    pub fn print(&self) {
+        for stmt in &self.statements {
+            match stmt {
+                Stmt::Expression(expr) => {
+                    println!("Expression");
+                    self.ast.print(*expr, 2);
+                }
+            }
+        }
+    }
+}
+
+impl Ast {
+    pub fn new() -> Ast {
+        return Ast::default();
+    }
+
+    /// Adds `expr` to the tree and returns where it is.
+    pub fn add(&mut self, expr: Expr) -> ExprId {
+        self.exprs.push(expr);
+        return ExprId(self.exprs.len() as u32 - 1);
+    }
+
+    /// Every expression in the tree, including those a pass replaced and
+    /// nothing refers to any more.
+    pub fn ids(&self) -> impl Iterator<Item = ExprId> {
+        return (0..self.exprs.len() as u32).map(ExprId);
+    }
+
+    /// Moves every expression of `other` into this tree, and returns where
+    /// `expr`, one of them, ends up.
+    pub fn graft(&mut self, other: Ast, expr: ExprId) -> ExprId {
+        let offset = self.exprs.len() as u32;
+        for mut moved in other.exprs {
+            for child in moved.children_mut() {
+                child.0 += offset;
+            }
+            self.exprs.push(moved);
+        }
+        return ExprId(expr.0 + offset);
+    }
+
+    /// The line the expression starts on, or its operator's line.
+    pub fn line(&self, id: ExprId) -> usize {
+        match &self[id] {
+            Expr::Binary { operator, .. } | Expr::Unary { operator, .. } => operator.line,
+            Expr::Call { paren, .. } => paren.line,
+            Expr::Conditional { condition, .. } => self.line(*condition),
+            Expr::Get { name, .. } => name.line,
+            Expr::Grouping(expr) => self.line(*expr),
+            Expr::Index { bracket, .. } => bracket.line,
+            Expr::Match { keyword, .. } => keyword.line,
+            Expr::Tuple { paren, .. } => paren.line,
+            Expr::Literal(token) | Expr::Variable(token) => token.line,
+        }
+    }
+
+    /// The bytes of the source the expression covers, from its first token
+    /// to its last. Closing brackets and the parentheses of a grouping are
+    /// not kept in the tree, so they are left out.
+    pub fn span(&self, id: ExprId) -> Range<usize> {
+        let token = match &self[id] {
+            Expr::Binary { operator, .. } | Expr::Unary { operator, .. } => Some(operator),
+            Expr::Call { paren, .. } | Expr::Tuple { paren, .. } => Some(paren),
+            Expr::Get { name, .. } => Some(name),
+            Expr::Index { bracket, .. } => Some(bracket),
+            Expr::Match { keyword, .. } => Some(keyword),
+            Expr::Literal(token) | Expr::Variable(token) => Some(token),
+            Expr::Conditional { .. } | Expr::Grouping(_) => None,
+        };
+        let spans = token.map(|token| token.span()).into_iter().chain(
+            self[id]
+                .children()
+                .into_iter()
+                .map(|child| self.span(child)),
+        );
+        return spans
+            .reduce(|span, other| span.start.min(other.start)..span.end.max(other.end))
+            .unwrap_or(0..0);
+    }
+
+    /// Writes the expression fully parenthesized in prefix form, e.g.
+    /// `(+ 1 (* 2 3))`.
+    pub fn display(&self, id: ExprId) -> ExprDisplay<'_> {
+        return ExprDisplay { ast: self, id };
+    }
+
+    fn print(&self, id: ExprId, indent: usize) {
+        println!("{}{}", " ".repeat(indent), self[id].label());
+        for child in self[id].children() {
+            self.print(child, indent + 2);
+        }
+    }
+}
+
+impl Index<ExprId> for Ast {
+    type Output = Expr;
+
+    fn index(&self, id: ExprId) -> &Expr {
+        return &self.exprs[id.0 as usize];
+    }
+}
+
+impl IndexMut<ExprId> for Ast {
+    fn index_mut(&mut self, id: ExprId) -> &mut Expr {
+        return &mut self.exprs[id.0 as usize];
+    }
+}
+
+impl Expr {
+    /// What the expression is, the way `--print-ast` prints it, e.g.
+    /// `Binary +`.
+    pub fn label(&self) -> String {
+        match self {
+            Expr::Binary { operator, .. } => format!("Binary {}", operator.lexeme),
+            Expr::Call { .. } => String::from("Call"),
+            Expr::Conditional { .. } => String::from("Conditional"),
+            Expr::Get { name, .. } => format!("Get {}", name.lexeme),
+            Expr::Grouping(_) => String::from("Grouping"),
+            Expr::Index { .. } => String::from("Index"),
+            Expr::Literal(token) => format!("Literal {}", token.lexeme),
+            Expr::Match { .. } => String::from("Match"),
+            Expr::Tuple { .. } => String::from("Tuple"),
+            Expr::Unary { operator, .. } => format!("Unary {}", operator.lexeme),
+            Expr::Variable(name) => format!("Variable {}", name.lexeme),
+        }
+    }
+
+    /// The expressions directly inside this one, in source order.
+    pub fn children(&self) -> Vec<ExprId> {
+        match self {
+            Expr::Binary { left, right, .. } => vec![*left, *right],
+            Expr::Call {
+                callee, arguments, ..
+            } => std::iter::once(callee).chain(arguments).copied().collect(),
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => vec![*condition, *then_branch, *else_branch],
+            Expr::Get { object, .. } => vec![*object],
+            Expr::Grouping(expr) => vec![*expr],
+            Expr::Index { object, index, .. } => vec![*object, *index],
+            Expr::Match { subject, arms, .. } => {
+                let arms = arms
+                    .iter()
+                    .flat_map(|arm| arm.guard.iter().chain([&arm.body]));
+                std::iter::once(subject).chain(arms).copied().collect()
+            }
+            Expr::Tuple { elements, .. } => elements.clone(),
+            Expr::Unary { right, .. } => vec![*right],
+            Expr::Literal(_) | Expr::Variable(_) => Vec::new(),
+        }
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut ExprId> {
+        match self {
+            Expr::Binary { left, right, .. } => vec![left, right],
+            Expr::Call {
+                callee, arguments, ..
+            } => std::iter::once(callee).chain(arguments).collect(),
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => vec![condition, then_branch, else_branch],
+            Expr::Get { object, .. } => vec![object],
+            Expr::Grouping(expr) => vec![expr],
+            Expr::Index { object, index, .. } => vec![object, index],
+            Expr::Match { subject, arms, .. } => {
+                let arms = arms
+                    .iter_mut()
+                    .flat_map(|arm| arm.guard.iter_mut().chain([&mut arm.body]));
+                std::iter::once(subject).chain(arms).collect()
+            }
+            Expr::Tuple { elements, .. } => elements.iter_mut().collect(),
+            Expr::Unary { right, .. } => vec![right],
+            Expr::Literal(_) | Expr::Variable(_) => Vec::new(),
+        }
+    }
+}
+
+/// An expression written by [`Ast::display`].
+pub struct ExprDisplay<'a> {
+    ast: &'a Ast,
+    id: ExprId,
+}
+
+impl fmt::Display for ExprDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |id: &ExprId| self.ast.display(*id);
+        match &self.ast[self.id] {
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => write!(f, "({} {} {})", operator.lexeme, show(left), show(right)),
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                write!(f, "(call {}", show(callee))?;
+                for argument in arguments {
+                    write!(f, " {}", show(argument))?;
+                }
+                write!(f, ")")
+            }
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => write!(
+                f,
+                "(? {} {} {})",
+                show(condition),
+                show(then_branch),
+                show(else_branch)
+            ),
+            Expr::Get { object, name } => write!(f, "(. {} {})", show(object), name.lexeme),
+            Expr::Grouping(expr) => write!(f, "(group {})", show(expr)),
+            Expr::Index { object, index, .. } => {
+                write!(f, "(index {} {})", show(object), show(index))
+            }
+            Expr::Literal(token) => write!(f, "{}", token.lexeme),
+            Expr::Match { subject, arms, .. } => {
+                write!(f, "(match {}", show(subject))?;
+                for arm in arms {
+                    write!(f, " ({}", arm.pattern)?;
+                    if let Some(guard) = &arm.guard {
+                        write!(f, " if {}", show(guard))?;
+                    }
+                    write!(f, " => {})", show(&arm.body))?;
+                }
+                write!(f, ")")
+            }
+            Expr::Tuple { elements, .. } => {
+                write!(f, "(tuple")?;
+                for element in elements {
+                    write!(f, " {}", show(element))?;
+                }
+                write!(f, ")")
+            }
+            Expr::Unary { operator, right } => write!(f, "({} {})", operator.lexeme, show(right)),
+            Expr::Variable(name) => write!(f, "{}", name.lexeme),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Wildcard(token) | Pattern::Binding(token) | Pattern::Literal(token) => {
+                write!(f, "{}", token.lexeme)
+            }
+            Pattern::Tuple { elements, .. } => {
+                write!(f, "(tuple")?;
+                for element in elements {
+                    write!(f, " {}", element)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! The C API, built with `--features capi`. See `include/udyr.h`.
+
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::ptr;
+
+use crate::interpreter::Interpreter;
+use crate::natives::Capabilities;
+
+/// An interpreter plus the strings last handed out to C, which stay valid
+/// until the next call to [`udyr_eval`] or [`udyr_free`].
+pub struct Udyr {
+    interpreter: Interpreter,
+    result: Option<CString>,
+    error: Option<CString>,
+}
+
+fn c_string(string: &str) -> CString {
+    return CString::new(string.replace('\0', "\\0")).unwrap();
+}
+
+#[no_mangle]
+pub extern "C" fn udyr_new() -> *mut Udyr {
+    let udyr = Udyr {
+        interpreter: Interpreter::new(),
+        result: None,
+        error: None,
+    };
+    return Box::into_raw(Box::new(udyr));
+}
+
+/// Runs `source` and returns its value as a string, or NULL if it failed;
+/// then [`udyr_last_error`] has the errors.
+///
+/// # Safety
+///
+/// `udyr` must come from [`udyr_new`] and `source` must be a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn udyr_eval(udyr: *mut Udyr, source: *const c_char) -> *const c_char {
+    let Some(udyr) = udyr.as_mut() else {
+        return ptr::null();
+    };
+    udyr.result = None;
+    udyr.error = None;
+    if source.is_null() {
+        udyr.error = Some(c_string("Source is NULL."));
+        return ptr::null();
+    }
+
+    let source = CStr::from_ptr(source).to_string_lossy();
+    match udyr.interpreter.eval(&source) {
+        Ok(value) => {
+            let result = udyr.result.insert(c_string(&value.to_string()));
+            return result.as_ptr();
+        }
+        Err(errors) => {
+            udyr.error = Some(c_string(&errors.join("\n")));
+            return ptr::null();
+        }
+    }
+}
+
+/// Lets the scripts `udyr` runs call `exec` if `allow` is not zero.
+///
+/// # Safety
+///
+/// `udyr` must come from [`udyr_new`].
+#[no_mangle]
+pub unsafe extern "C" fn udyr_allow_exec(udyr: *mut Udyr, allow: c_int) {
+    if let Some(udyr) = udyr.as_mut() {
+        udyr.interpreter.set_capabilities(Capabilities {
+            exec: allow != 0,
+            ..Capabilities::default()
+        });
+    }
+}
+
+/// Returns the errors of the last failed [`udyr_eval`], one per line, or
+/// NULL if it succeeded.
+///
+/// # Safety
+///
+/// `udyr` must come from [`udyr_new`].
+#[no_mangle]
+pub unsafe extern "C" fn udyr_last_error(udyr: *const Udyr) -> *const c_char {
+    match udyr.as_ref().and_then(|udyr| udyr.error.as_ref()) {
+        Some(error) => error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// # Safety
+///
+/// `udyr` must come from [`udyr_new`] and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn udyr_free(udyr: *mut Udyr) {
+    if !udyr.is_null() {
+        drop(Box::from_raw(udyr));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn string(pointer: *const c_char) -> Option<String> {
+        if pointer.is_null() {
+            return None;
+        }
+        return Some(CStr::from_ptr(pointer).to_str().unwrap().to_string());
+    }
+
+    #[test]
+    fn test_eval() -> Result<(), String> {
+        unsafe {
+            let udyr = udyr_new();
+
+            let result = udyr_eval(udyr, c"\"a\" + \"b\"".as_ptr());
+            assert_eq!(string(result), Some(String::from("ab")));
+            assert_eq!(string(udyr_last_error(udyr)), None);
+
+            assert!(udyr_eval(udyr, c"1 +".as_ptr()).is_null());
+            assert_eq!(
+                string(udyr_last_error(udyr)),
+                Some(String::from("[line 1] Error at end: Expected expression."))
+            );
+
+            let exec = c"exec(\"true\", ())[0]".as_ptr();
+            assert!(udyr_eval(udyr, exec).is_null());
+            udyr_allow_exec(udyr, 1);
+            assert_eq!(string(udyr_eval(udyr, exec)), Some(String::from("0")));
+
+            assert!(udyr_eval(udyr, ptr::null()).is_null());
+            assert!(udyr_eval(ptr::null_mut(), c"1".as_ptr()).is_null());
+
+            udyr_free(udyr);
+            udyr_free(ptr::null_mut());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_declares_every_function() -> Result<(), String> {
+        let header = include_str!("../include/udyr.h");
+        for function in [
+            "udyr_new",
+            "udyr_eval",
+            "udyr_allow_exec",
+            "udyr_last_error",
+            "udyr_free",
+        ] {
+            assert!(header.contains(&format!("{}(", function)), "{}", function);
+        }
+
+        Ok(())
+    }
+}
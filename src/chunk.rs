@@ -0,0 +1,167 @@
+use crate::value::Value;
+
+/// The instructions of the virtual machine. Operands follow the opcode
+/// byte in the code: one byte for a constant index, a tuple's length or a
+/// local's slot, two bytes (big endian) for a jump offset, and a name's
+/// constant index followed by the argument count for `Invoke` and
+/// `CallNative`.
+///
+/// Locals are the values match patterns bind, kept apart from the stack.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum OpCode {
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Jump,
+    JumpIfFalse,
+    Return,
+    Tuple,
+    Index,
+    JumpIfNotNil,
+    GetProperty,
+    Invoke,
+    CallNative,
+    /// Moves the top of the stack into a new local.
+    Bind,
+    GetLocal,
+    /// Drops the locals from a slot on.
+    Unbind,
+    /// Whether the top of the stack is a tuple of the given length.
+    MatchTuple,
+    /// Fails with the value on top of the stack, which no match arm matched.
+    NoMatch,
+}
+
+impl OpCode {
+    pub(crate) const ALL: [OpCode; 30] = [
+        OpCode::Constant,
+        OpCode::Nil,
+        OpCode::True,
+        OpCode::False,
+        OpCode::Pop,
+        OpCode::Equal,
+        OpCode::Greater,
+        OpCode::GreaterEqual,
+        OpCode::Less,
+        OpCode::LessEqual,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Not,
+        OpCode::Negate,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+        OpCode::Return,
+        OpCode::Tuple,
+        OpCode::Index,
+        OpCode::JumpIfNotNil,
+        OpCode::GetProperty,
+        OpCode::Invoke,
+        OpCode::CallNative,
+        OpCode::Bind,
+        OpCode::GetLocal,
+        OpCode::Unbind,
+        OpCode::MatchTuple,
+        OpCode::NoMatch,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        return OpCode::ALL.get(byte as usize).copied();
+    }
+
+    /// The name the disassembler shows, e.g. `OP_CONSTANT`.
+    pub fn name(self) -> &'static str {
+        match self {
+            OpCode::Constant => "OP_CONSTANT",
+            OpCode::Nil => "OP_NIL",
+            OpCode::True => "OP_TRUE",
+            OpCode::False => "OP_FALSE",
+            OpCode::Pop => "OP_POP",
+            OpCode::Equal => "OP_EQUAL",
+            OpCode::Greater => "OP_GREATER",
+            OpCode::GreaterEqual => "OP_GREATER_EQUAL",
+            OpCode::Less => "OP_LESS",
+            OpCode::LessEqual => "OP_LESS_EQUAL",
+            OpCode::Add => "OP_ADD",
+            OpCode::Subtract => "OP_SUBTRACT",
+            OpCode::Multiply => "OP_MULTIPLY",
+            OpCode::Divide => "OP_DIVIDE",
+            OpCode::Not => "OP_NOT",
+            OpCode::Negate => "OP_NEGATE",
+            OpCode::Jump => "OP_JUMP",
+            OpCode::JumpIfFalse => "OP_JUMP_IF_FALSE",
+            OpCode::Return => "OP_RETURN",
+            OpCode::Tuple => "OP_TUPLE",
+            OpCode::Index => "OP_INDEX",
+            OpCode::JumpIfNotNil => "OP_JUMP_IF_NOT_NIL",
+            OpCode::GetProperty => "OP_GET_PROPERTY",
+            OpCode::Invoke => "OP_INVOKE",
+            OpCode::CallNative => "OP_CALL_NATIVE",
+            OpCode::Bind => "OP_BIND",
+            OpCode::GetLocal => "OP_GET_LOCAL",
+            OpCode::Unbind => "OP_UNBIND",
+            OpCode::MatchTuple => "OP_MATCH_TUPLE",
+            OpCode::NoMatch => "OP_NO_MATCH",
+        }
+    }
+}
+
+/// A compiled sequence of bytecode with the line of every byte and the
+/// constants it refers to.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub lines: Vec<usize>,
+    pub constants: Vec<Value>,
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        return Chunk::default();
+    }
+
+    pub fn write(&mut self, byte: u8, line: usize) {
+        self.code.push(byte);
+        self.lines.push(line);
+    }
+
+    pub fn write_op(&mut self, op: OpCode, line: usize) {
+        self.write(op as u8, line);
+    }
+
+    /// Adds a constant to the pool and returns its index.
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        self.constants.push(value);
+        return self.constants.len() - 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opcode_round_trip() -> Result<(), String> {
+        for op in OpCode::ALL {
+            assert_eq!(OpCode::from_byte(op as u8), Some(op));
+        }
+        assert_eq!(OpCode::from_byte(OpCode::ALL.len() as u8), None);
+
+        Ok(())
+    }
+}
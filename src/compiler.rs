@@ -0,0 +1,546 @@
+use crate::{
+    ast::{Ast, Expr, ExprId, Pattern, Program, Stmt},
+    chunk::{Chunk, OpCode},
+    error::{error, report_at, Diagnostic},
+    natives,
+    token::{Token, TokenType},
+    value::Value,
+};
+
+/// Compiles a program to bytecode for the [`crate::vm::Vm`]. The value of
+/// the last expression statement is left for `Return`.
+pub fn compile(program: &Program) -> Result<Chunk, Diagnostic> {
+    let mut compiler = Compiler {
+        ast: &program.ast,
+        chunk: Chunk::new(),
+        line: 0,
+        locals: Vec::new(),
+    };
+
+    let statements = &program.statements;
+    for (i, stmt) in statements.iter().enumerate() {
+        compiler.statement(stmt).map_err(|error| *error)?;
+        if i + 1 < statements.len() {
+            compiler.emit(OpCode::Pop);
+        }
+    }
+    if statements.is_empty() {
+        compiler.emit(OpCode::Nil);
+    }
+    compiler.emit(OpCode::Return);
+
+    return Ok(compiler.chunk);
+}
+
+/// Errors are boxed inside the compiler, as it recurses once for every
+/// level of an expression and each level keeps its own results.
+struct Compiler<'a> {
+    ast: &'a Ast,
+    chunk: Chunk,
+    /// The line of the token being compiled, recorded for every byte.
+    line: usize,
+    /// The names of the VM's locals by slot, for the values match patterns
+    /// bind. Values a pattern only looks into have no name.
+    locals: Vec<Option<String>>,
+}
+
+impl<'a> Compiler<'a> {
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), Box<Diagnostic>> {
+        match stmt {
+            Stmt::Expression(expr) => self.expression(*expr),
+        }
+    }
+
+    fn expression(&mut self, id: ExprId) -> Result<(), Box<Diagnostic>> {
+        let ast = self.ast;
+        match &ast[id] {
+            // Only evaluates the right operand when the left one is nil.
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } if operator.token_type == TokenType::QuestionQuestion => {
+                self.expression(*left)?;
+                self.line = operator.line;
+                let end_jump = self.emit_jump(OpCode::JumpIfNotNil);
+                self.emit(OpCode::Pop);
+                self.expression(*right)?;
+                self.patch_jump(end_jump)?;
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(*left)?;
+                self.expression(*right)?;
+                self.line = operator.line;
+                self.binary(operator);
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                if arguments.len() > u8::MAX as usize {
+                    return Err(report_at(paren, " at '('", "Too many arguments.").into());
+                }
+                let (op, name, token) = match (self.native(*callee), &ast[*callee]) {
+                    (Some((native, token)), _) => (OpCode::CallNative, native, token),
+                    (None, Expr::Get { object, name }) => {
+                        if let Expr::Variable(namespace) = &ast[*object] {
+                            if self.resolve(namespace).is_none()
+                                && natives::is_namespace(&namespace.lexeme)
+                            {
+                                let message = format!(
+                                    "Undefined function '{}.{}'.",
+                                    namespace.lexeme, name.lexeme
+                                );
+                                return Err(report_at(name, "", &message).into());
+                            }
+                        }
+                        self.expression(*object)?;
+                        (OpCode::Invoke, name.lexeme.to_string(), name)
+                    }
+                    (None, Expr::Variable(name)) if self.resolve(name).is_none() => {
+                        let message = format!("Undefined function '{}'.", name.lexeme);
+                        return Err(report_at(name, "", &message).into());
+                    }
+                    _ => {
+                        let message = "Can only call methods and built-in functions.";
+                        return Err(report_at(paren, " at '('", message).into());
+                    }
+                };
+                for argument in arguments {
+                    self.expression(*argument)?;
+                }
+                self.line = paren.line;
+                let index = self.make_constant(Value::from(name), token)?;
+                self.emit(op);
+                self.chunk.write(index, self.line);
+                self.chunk.write(arguments.len() as u8, self.line);
+            }
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(*condition)?;
+                let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit(OpCode::Pop);
+                self.expression(*then_branch)?;
+                let end_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(else_jump)?;
+                self.emit(OpCode::Pop);
+                self.expression(*else_branch)?;
+                self.patch_jump(end_jump)?;
+            }
+            Expr::Get { object, name } => {
+                self.expression(*object)?;
+                self.line = name.line;
+                let index = self.make_constant(Value::from(name.lexeme.as_str()), name)?;
+                self.emit(OpCode::GetProperty);
+                self.chunk.write(index, self.line);
+            }
+            Expr::Grouping(expr) => self.expression(*expr)?,
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                self.expression(*object)?;
+                self.expression(*index)?;
+                self.line = bracket.line;
+                self.emit(OpCode::Index);
+            }
+            Expr::Literal(token) => self.literal(token)?,
+            Expr::Match {
+                keyword,
+                subject,
+                arms,
+            } => {
+                self.expression(*subject)?;
+                self.line = keyword.line;
+                let base = self.locals.len();
+                self.bind(keyword)?;
+
+                let mut end_jumps = Vec::new();
+                for arm in arms {
+                    // Every failed test jumps to the next arm with `false`
+                    // on the stack.
+                    let mut fail_jumps = Vec::new();
+                    self.pattern(&arm.pattern, base, base + 1, &mut fail_jumps)?;
+                    if let Some(guard) = arm.guard {
+                        self.expression(guard)?;
+                        fail_jumps.push(self.emit_jump(OpCode::JumpIfFalse));
+                        self.emit(OpCode::Pop);
+                    }
+                    self.expression(arm.body)?;
+                    self.line = keyword.line;
+                    self.unbind(base);
+                    end_jumps.push(self.emit_jump(OpCode::Jump));
+
+                    for jump in &fail_jumps {
+                        self.patch_jump(*jump)?;
+                    }
+                    if !fail_jumps.is_empty() {
+                        self.emit(OpCode::Pop);
+                    }
+                    self.locals.truncate(base + 1);
+                    self.locals[base] = None;
+                    self.unbind(base + 1);
+                }
+                self.get_local(base);
+                self.emit(OpCode::NoMatch);
+
+                for jump in end_jumps {
+                    self.patch_jump(jump)?;
+                }
+                self.locals.truncate(base);
+            }
+            Expr::Tuple { paren, elements } => {
+                if elements.len() > u8::MAX as usize {
+                    return Err(
+                        report_at(paren, " at '('", "Too many elements in one tuple.").into(),
+                    );
+                }
+                for element in elements {
+                    self.expression(*element)?;
+                }
+                self.line = paren.line;
+                self.emit(OpCode::Tuple);
+                self.chunk.write(elements.len() as u8, self.line);
+            }
+            Expr::Unary { operator, right } => {
+                self.expression(*right)?;
+                self.line = operator.line;
+                match operator.token_type {
+                    TokenType::BANG => self.emit(OpCode::Not),
+                    _ => self.emit(OpCode::Negate),
+                }
+            }
+            Expr::Variable(name) => {
+                let Some(slot) = self.resolve(name) else {
+                    let message = format!("Undefined variable '{}'.", name.lexeme);
+                    return Err(report_at(name, "", &message).into());
+                };
+                self.line = name.line;
+                self.get_local(slot);
+            }
+        }
+        return Ok(());
+    }
+
+    /// Tests whether the value in local `slot` matches `pattern`, binding
+    /// the names in it to slots from `arm_base` on. Each test leaves a bool
+    /// and adds a jump for when it is false to `fail_jumps`.
+    fn pattern(
+        &mut self,
+        pattern: &Pattern,
+        slot: usize,
+        arm_base: usize,
+        fail_jumps: &mut Vec<usize>,
+    ) -> Result<(), Box<Diagnostic>> {
+        match pattern {
+            Pattern::Wildcard(_) => {}
+            Pattern::Binding(name) => {
+                let bound = self.locals[arm_base..]
+                    .iter()
+                    .any(|local| local.as_deref() == Some(&name.lexeme));
+                if bound {
+                    let location = format!(" at '{}'", name.lexeme);
+                    return Err(report_at(
+                        name,
+                        &location,
+                        "Name is already bound in this pattern.",
+                    )
+                    .into());
+                }
+                self.locals[slot] = Some(name.lexeme.to_string());
+            }
+            Pattern::Literal(token) => {
+                self.get_local(slot);
+                self.literal(token)?;
+                self.emit(OpCode::Equal);
+                fail_jumps.push(self.emit_jump(OpCode::JumpIfFalse));
+                self.emit(OpCode::Pop);
+            }
+            Pattern::Tuple { paren, elements } => {
+                if elements.len() > u8::MAX as usize {
+                    return Err(report_at(
+                        paren,
+                        " at '('",
+                        "Too many elements in one tuple pattern.",
+                    )
+                    .into());
+                }
+                self.line = paren.line;
+                self.get_local(slot);
+                self.emit(OpCode::MatchTuple);
+                self.chunk.write(elements.len() as u8, self.line);
+                fail_jumps.push(self.emit_jump(OpCode::JumpIfFalse));
+                self.emit(OpCode::Pop);
+                for (index, element) in elements.iter().enumerate() {
+                    if matches!(element, Pattern::Wildcard(_)) {
+                        continue;
+                    }
+                    self.line = paren.line;
+                    self.get_local(slot);
+                    self.emit_constant(Value::Int(index as i64), paren)?;
+                    self.emit(OpCode::Index);
+                    let element_slot = self.bind(paren)?;
+                    self.pattern(element, element_slot, arm_base, fail_jumps)?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    /// Moves the value on top of the stack into a new local, and returns
+    /// its slot.
+    fn bind(&mut self, token: &Token) -> Result<usize, Box<Diagnostic>> {
+        if self.locals.len() > u8::MAX as usize {
+            let location = format!(" at '{}'", token.lexeme);
+            return Err(report_at(token, &location, "Too many values bound in one match.").into());
+        }
+        self.emit(OpCode::Bind);
+        self.locals.push(None);
+        return Ok(self.locals.len() - 1);
+    }
+
+    /// Drops every local from `slot` on.
+    fn unbind(&mut self, slot: usize) {
+        self.emit(OpCode::Unbind);
+        self.chunk.write(slot as u8, self.line);
+    }
+
+    fn get_local(&mut self, slot: usize) {
+        self.emit(OpCode::GetLocal);
+        self.chunk.write(slot as u8, self.line);
+    }
+
+    /// The slot of the local bound to `name`, if any.
+    fn resolve(&self, name: &Token) -> Option<usize> {
+        return self
+            .locals
+            .iter()
+            .rposition(|local| local.as_deref() == Some(&name.lexeme));
+    }
+
+    /// The native `callee` names, like `clock` or `log.info`, and the token
+    /// to report errors at. A name bound by a pattern is not a native.
+    fn native(&self, callee: ExprId) -> Option<(String, &'a Token)> {
+        let ast = self.ast;
+        let (name, token) = match &ast[callee] {
+            Expr::Variable(name) if self.resolve(name).is_none() => (name.lexeme.to_string(), name),
+            Expr::Get { object, name } => match &ast[*object] {
+                Expr::Variable(namespace) if self.resolve(namespace).is_none() => {
+                    (format!("{}.{}", namespace.lexeme, name.lexeme), name)
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        if !natives::is_native(&name) {
+            return None;
+        }
+        return Some((name, token));
+    }
+
+    fn binary(&mut self, operator: &Token) {
+        match operator.token_type {
+            TokenType::BangEqual => {
+                self.emit(OpCode::Equal);
+                self.emit(OpCode::Not);
+            }
+            TokenType::EqualEqual => self.emit(OpCode::Equal),
+            TokenType::GREATER => self.emit(OpCode::Greater),
+            TokenType::GreaterEqual => self.emit(OpCode::GreaterEqual),
+            TokenType::LESS => self.emit(OpCode::Less),
+            TokenType::LessEqual => self.emit(OpCode::LessEqual),
+            TokenType::Plus => self.emit(OpCode::Add),
+            TokenType::Minus => self.emit(OpCode::Subtract),
+            TokenType::STAR => self.emit(OpCode::Multiply),
+            TokenType::SLASH => self.emit(OpCode::Divide),
+            _ => unreachable!("not a binary operator: {:?}", operator.token_type),
+        }
+    }
+
+    fn literal(&mut self, token: &Token) -> Result<(), Box<Diagnostic>> {
+        self.line = token.line;
+        match token.token_type {
+            TokenType::TRUE => self.emit(OpCode::True),
+            TokenType::FALSE => self.emit(OpCode::False),
+            TokenType::NIL => self.emit(OpCode::Nil),
+            TokenType::NUMBER => {
+                self.emit_constant(Value::number(&token.literal), token)?;
+            }
+            _ => self.emit_constant(Value::from(token.literal.as_str()), token)?,
+        }
+        return Ok(());
+    }
+
+    fn emit(&mut self, op: OpCode) {
+        self.chunk.write_op(op, self.line);
+    }
+
+    fn emit_constant(&mut self, value: Value, token: &Token) -> Result<(), Box<Diagnostic>> {
+        let index = self.make_constant(value, token)?;
+        self.emit(OpCode::Constant);
+        self.chunk.write(index, self.line);
+        return Ok(());
+    }
+
+    /// Adds `value` to the constants and returns its index, reporting an
+    /// error at `token` when there are too many.
+    fn make_constant(&mut self, value: Value, token: &Token) -> Result<u8, Box<Diagnostic>> {
+        let existing = self
+            .chunk
+            .constants
+            .iter()
+            .position(|constant| identical(constant, &value));
+        let index = match existing {
+            Some(index) => index,
+            None => self.chunk.add_constant(value),
+        };
+        if index > u8::MAX as usize {
+            let location = format!(" at '{}'", token.lexeme);
+            return Err(report_at(token, &location, "Too many constants in one chunk.").into());
+        }
+        return Ok(index as u8);
+    }
+
+    /// Emits a jump with a placeholder offset and returns where the offset
+    /// starts, for [`Compiler::patch_jump`].
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit(op);
+        self.chunk.write(0xff, self.line);
+        self.chunk.write(0xff, self.line);
+        return self.chunk.code.len() - 2;
+    }
+
+    /// Points the jump at `offset` to the next instruction.
+    fn patch_jump(&mut self, offset: usize) -> Result<(), Box<Diagnostic>> {
+        let jump = self.chunk.code.len() - offset - 2;
+        if jump > u16::MAX as usize {
+            return Err(Box::new(
+                error(self.line, "Too much code to jump over.").into(),
+            ));
+        }
+        self.chunk.code[offset..offset + 2].copy_from_slice(&(jump as u16).to_be_bytes());
+        return Ok(());
+    }
+}
+
+/// Whether two constants can share a slot: equal, with numbers compared
+/// by their bits, so `0.0` and `-0.0` are kept apart.
+fn identical(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => return a.to_bits() == b.to_bits(),
+        (Value::Tuple(a), Value::Tuple(b)) => {
+            return a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| identical(a, b));
+        }
+        _ => return a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::Parser, scanner::Scanner};
+
+    use super::*;
+
+    fn compile_source(source: &str) -> Result<Chunk, String> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let program = Parser::new(&tokens).parse()?;
+        Ok(compile(&program)?)
+    }
+
+    #[test]
+    fn test_compile_binary() -> Result<(), String> {
+        let chunk = compile_source("1 + 2 * 3")?;
+
+        assert_eq!(
+            chunk.code,
+            vec![
+                OpCode::Constant as u8,
+                0,
+                OpCode::Constant as u8,
+                1,
+                OpCode::Constant as u8,
+                2,
+                OpCode::Multiply as u8,
+                OpCode::Add as u8,
+                OpCode::Return as u8,
+            ]
+        );
+        assert_eq!(
+            chunk.constants,
+            vec![Value::Int(1), Value::Int(2), Value::Int(3)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_constants_shared() -> Result<(), String> {
+        let chunk = compile_source("(\"a\", 1, \"a\", 1.0, 0.0, -0.0, 1, 0.0)")?;
+        assert_eq!(
+            chunk.constants,
+            vec![
+                Value::from("a"),
+                Value::Int(1),
+                Value::Number(1.0),
+                Value::Number(0.0),
+            ]
+        );
+
+        let source = vec!["1"; 300].join(" + ");
+        assert!(compile_source(&source).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_conditional() -> Result<(), String> {
+        let chunk = compile_source("true ? 1 : 2")?;
+
+        assert_eq!(
+            chunk.code,
+            vec![
+                OpCode::True as u8,
+                OpCode::JumpIfFalse as u8,
+                0,
+                6,
+                OpCode::Pop as u8,
+                OpCode::Constant as u8,
+                0,
+                OpCode::Jump as u8,
+                0,
+                3,
+                OpCode::Pop as u8,
+                OpCode::Constant as u8,
+                1,
+                OpCode::Return as u8,
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_errors() -> Result<(), String> {
+        assert_eq!(
+            compile_source("1 + answer").unwrap_err(),
+            "[line 1] Error: Undefined variable 'answer'."
+        );
+
+        let numbers: Vec<String> = (0..257).map(|number| number.to_string()).collect();
+        assert_eq!(
+            compile_source(&numbers.join(" + ")).unwrap_err(),
+            "[line 1] Error at '256': Too many constants in one chunk."
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+//! Completion of the word being typed, for the REPL. It knows the
+//! keywords, the natives and the properties and methods of built-in values.
+
+use crate::{methods, natives, scanner};
+
+/// Where the word at the end of `text` starts.
+fn word_start(text: &str) -> usize {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    match text.char_indices().rev().find(|(_, c)| !is_word(*c)) {
+        Some((index, c)) => return index + c.len_utf8(),
+        None => return 0,
+    }
+}
+
+/// Completes the word that ends at byte `position` of `line`. Returns
+/// where the word starts and what it could become, sorted.
+pub fn complete(line: &str, position: usize) -> (usize, Vec<String>) {
+    let before = &line[..position];
+    let start = word_start(before);
+    let word = &before[start..];
+
+    let mut candidates: Vec<String> = match before[..start].strip_suffix('.') {
+        Some(receiver) => {
+            let receiver = &receiver[word_start(receiver)..];
+            let namespace = format!("{}.", receiver);
+            let members: Vec<String> = natives::NAMES
+                .iter()
+                .filter_map(|name| name.strip_prefix(&namespace))
+                .map(String::from)
+                .collect();
+            if members.is_empty() {
+                methods::PROPERTIES
+                    .iter()
+                    .chain(methods::METHODS)
+                    .map(|name| name.to_string())
+                    .collect()
+            } else {
+                members
+            }
+        }
+        None => scanner::keywords()
+            .keys()
+            .copied()
+            .chain(
+                natives::NAMES
+                    .iter()
+                    .map(|name| name.split('.').next().unwrap()),
+            )
+            .map(String::from)
+            .collect(),
+    };
+    candidates.retain(|candidate| candidate.starts_with(word));
+    candidates.sort();
+    candidates.dedup();
+    return (start, candidates);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete() -> Result<(), String> {
+        assert_eq!(
+            complete("1 + cl", 6),
+            (4, vec![String::from("class"), String::from("clock")])
+        );
+        assert_eq!(complete("lo", 2), (0, vec![String::from("log")]));
+        assert_eq!(complete("log.w", 5), (4, vec![String::from("warn")]));
+        assert_eq!(
+            complete("\"ø\".", 5),
+            (
+                5,
+                vec![
+                    String::from("format"),
+                    String::from("indexOf"),
+                    String::from("length"),
+                    String::from("replace"),
+                    String::from("slice"),
+                    String::from("trim"),
+                ]
+            )
+        );
+        assert_eq!(complete("(høst", 6), (1, vec![]));
+        assert_eq!(complete("€.le", 6).1, vec![String::from("length")]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+
+use crate::{chunk::Chunk, disassembler, value::Value, vm::Observer};
+
+const HELP: &str = "\
+break N   stop before line N runs
+step      run one instruction
+next      run to the next line
+continue  run to the next breakpoint
+stack     show the value stack
+help      show this help
+";
+
+/// An interactive debugger stepping through the VM's instructions. It
+/// stops before the first instruction so breakpoints can be set, and runs
+/// to the end once `input` runs out.
+pub struct Debugger<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    breakpoints: HashSet<usize>,
+    mode: Mode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Step,
+    /// Stop at the first instruction on another line than this one.
+    Next(usize),
+    Continue,
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    pub fn new(input: R, output: W) -> Debugger<R, W> {
+        return Debugger {
+            input,
+            output,
+            breakpoints: HashSet::new(),
+            mode: Mode::Step,
+        };
+    }
+
+    fn should_stop(&self, line: usize, new_line: bool) -> bool {
+        match self.mode {
+            Mode::Step => true,
+            Mode::Next(from) => line != from || (new_line && self.breakpoints.contains(&line)),
+            Mode::Continue => new_line && self.breakpoints.contains(&line),
+        }
+    }
+
+    /// Reads commands until one of them resumes the program.
+    fn prompt(&mut self, chunk: &Chunk, offset: usize, stack: &[Value]) -> std::io::Result<()> {
+        let mut instruction = String::new();
+        disassembler::disassemble_instruction(chunk, offset, &mut instruction);
+        write!(self.output, "{}", instruction)?;
+
+        loop {
+            write!(self.output, "(udyr) ")?;
+            self.output.flush()?;
+            let mut command = String::new();
+            if self.input.read_line(&mut command)? == 0 {
+                self.mode = Mode::Continue;
+                self.breakpoints.clear();
+                return Ok(());
+            }
+
+            let mut words = command.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("break" | "b"), Some(line)) => match line.parse() {
+                    Ok(line) => {
+                        self.breakpoints.insert(line);
+                        writeln!(self.output, "Breakpoint at line {}.", line)?;
+                    }
+                    Err(_) => writeln!(self.output, "Expected a line number.")?,
+                },
+                (Some("step" | "s"), None) => {
+                    self.mode = Mode::Step;
+                    return Ok(());
+                }
+                (Some("next" | "n"), None) => {
+                    self.mode = Mode::Next(chunk.lines[offset]);
+                    return Ok(());
+                }
+                (Some("continue" | "c"), None) => {
+                    self.mode = Mode::Continue;
+                    return Ok(());
+                }
+                (Some("stack"), None) => {
+                    let values: Vec<String> = stack.iter().map(|value| value.to_string()).collect();
+                    writeln!(self.output, "[{}]", values.join(", "))?;
+                }
+                (Some("help" | "h"), None) => write!(self.output, "{}", HELP)?,
+                (None, _) => {}
+                _ => writeln!(self.output, "Unknown command. Type 'help' for a list.")?,
+            }
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Observer for Debugger<R, W> {
+    fn before_instruction(&mut self, chunk: &Chunk, offset: usize, stack: &[Value]) {
+        let line = chunk.lines[offset];
+        let new_line = offset == 0 || chunk.lines[offset - 1] != line;
+        if self.should_stop(line, new_line) {
+            // The debugger has nowhere to report a broken terminal, so it
+            // lets the program run on.
+            if self.prompt(chunk, offset, stack).is_err() {
+                self.mode = Mode::Continue;
+                self.breakpoints.clear();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compiler, parser::Parser, scanner::Scanner, vm::Vm};
+
+    use super::*;
+
+    fn debug(source: &str, commands: &str) -> (Value, String) {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let chunk = compiler::compile(&Parser::new(&tokens).parse().unwrap()).unwrap();
+        let mut output = Vec::new();
+        let mut debugger = Debugger::new(commands.as_bytes(), &mut output);
+        let value = Vm::new().interpret_with(&chunk, &mut debugger).unwrap();
+        (value, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_step_and_stack() -> Result<(), String> {
+        let (value, output) = debug("1 + 2", "step\nstep\nstack\nc\n");
+
+        assert_eq!(value, Value::Int(3));
+        assert_eq!(
+            output,
+            "0000    1 OP_CONSTANT         0 '1'\n\
+             (udyr) 0002    | OP_CONSTANT         1 '2'\n\
+             (udyr) 0004    | OP_ADD\n\
+             (udyr) [1, 2]\n\
+             (udyr) "
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_breakpoints() -> Result<(), String> {
+        let (value, output) = debug("1 +\n2 *\n3", "break 3\nbogus\ncontinue\nnext\nstack\n");
+
+        assert_eq!(value, Value::Int(7));
+        assert_eq!(
+            output,
+            "0000    1 OP_CONSTANT         0 '1'\n\
+             (udyr) Breakpoint at line 3.\n\
+             (udyr) Unknown command. Type 'help' for a list.\n\
+             (udyr) 0004    3 OP_CONSTANT         2 '3'\n\
+             (udyr) 0006    2 OP_MULTIPLY\n\
+             (udyr) [1, 2, 3]\n\
+             (udyr) "
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+//! `udyr diff`: how two versions of a script differ, token by token or in
+//! the structure of their syntax trees. Positions are written
+//! `line:column`, both counted from 1.
+
+use std::ops::Range;
+
+use crate::{
+    ast::{Ast, ExprId, Program, Stmt},
+    parser::Parser,
+    scanner::Scanner,
+    token::{Token, TokenType},
+};
+
+/// The tokens removed from `old` and added in `new`, as lines like
+/// `- 1:5 '1'` and `+ 1:5 '2'`. Empty if the scripts have the same tokens,
+/// even if their whitespace and comments differ.
+pub fn tokens(old: &str, new: &str) -> Result<Vec<String>, Vec<String>> {
+    let old_tokens = scan(old)?;
+    let new_tokens = scan(new)?;
+    let same = |a: &Token, b: &Token| a.token_type == b.token_type && a.lexeme == b.lexeme;
+
+    // The longest common subsequence, kept from each index to the end.
+    let (n, m) = (old_tokens.len(), new_tokens.len());
+    let mut common = vec![vec![0; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            common[i][j] = if same(&old_tokens[i], &new_tokens[j]) {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && same(&old_tokens[i], &new_tokens[j]) {
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && common[i + 1][j] >= common[i][j + 1]) {
+            let token = &old_tokens[i];
+            lines.push(format!(
+                "- {} '{}'",
+                position(old, token.offset),
+                token.lexeme
+            ));
+            i += 1;
+        } else {
+            let token = &new_tokens[j];
+            lines.push(format!(
+                "+ {} '{}'",
+                position(new, token.offset),
+                token.lexeme
+            ));
+            j += 1;
+        }
+    }
+    return Ok(lines);
+}
+
+/// The nodes changed, removed and added between the syntax trees of `old`
+/// and `new`, as lines like `changed 1:5-1:6 -> 1:5-1:6: Literal 1 ->
+/// Literal 2`. Nodes are matched by where they are in the tree, so a
+/// change to a node is reported once, not for everything inside it.
+pub fn ast(old: &str, new: &str) -> Result<Vec<String>, Vec<String>> {
+    let old_program = parse(old)?;
+    let new_program = parse(new)?;
+    let (old_statements, new_statements) = (&old_program.statements, &new_program.statements);
+    let sources = Sources {
+        old,
+        new,
+        old_ast: &old_program.ast,
+        new_ast: &new_program.ast,
+    };
+
+    let mut lines = Vec::new();
+    for (old_stmt, new_stmt) in old_statements.iter().zip(new_statements) {
+        match (old_stmt, new_stmt) {
+            (Stmt::Expression(a), Stmt::Expression(b)) => sources.diff(*a, *b, &mut lines),
+        }
+    }
+    for Stmt::Expression(expr) in old_statements.iter().skip(new_statements.len()) {
+        lines.push(format!(
+            "removed {}: {}",
+            span(old, old_program.ast.span(*expr)),
+            old_program.ast[*expr].label()
+        ));
+    }
+    for Stmt::Expression(expr) in new_statements.iter().skip(old_statements.len()) {
+        lines.push(format!(
+            "added {}: {}",
+            span(new, new_program.ast.span(*expr)),
+            new_program.ast[*expr].label()
+        ));
+    }
+    return Ok(lines);
+}
+
+struct Sources<'a> {
+    old: &'a str,
+    new: &'a str,
+    old_ast: &'a Ast,
+    new_ast: &'a Ast,
+}
+
+impl Sources<'_> {
+    fn diff(&self, old: ExprId, new: ExprId, lines: &mut Vec<String>) {
+        let (old_expr, new_expr) = (&self.old_ast[old], &self.new_ast[new]);
+        if old_expr.label() != new_expr.label() {
+            lines.push(format!(
+                "changed {} -> {}: {} -> {}",
+                span(self.old, self.old_ast.span(old)),
+                span(self.new, self.new_ast.span(new)),
+                old_expr.label(),
+                new_expr.label()
+            ));
+            return;
+        }
+        let (old_children, new_children) = (old_expr.children(), new_expr.children());
+        for (a, b) in old_children.iter().zip(&new_children) {
+            self.diff(*a, *b, lines);
+        }
+        for expr in old_children.iter().skip(new_children.len()) {
+            lines.push(format!(
+                "removed {}: {}",
+                span(self.old, self.old_ast.span(*expr)),
+                self.old_ast[*expr].label()
+            ));
+        }
+        for expr in new_children.iter().skip(old_children.len()) {
+            lines.push(format!(
+                "added {}: {}",
+                span(self.new, self.new_ast.span(*expr)),
+                self.new_ast[*expr].label()
+            ));
+        }
+    }
+}
+
+fn scan(source: &str) -> Result<Vec<Token>, Vec<String>> {
+    let mut tokens = Scanner::new(source).scan_tokens()?;
+    tokens.retain(|token| token.token_type != TokenType::EOF);
+    return Ok(tokens);
+}
+
+fn parse(source: &str) -> Result<Program, Vec<String>> {
+    let tokens = Scanner::new(source).scan_tokens()?;
+    return Parser::new(&tokens)
+        .parse()
+        .map_err(|error| vec![error.into()]);
+}
+
+/// Where byte `offset` of `source` is, as `line:column`.
+fn position(source: &str, offset: usize) -> String {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before[before.rfind('\n').map_or(0, |index| index + 1)..]
+        .chars()
+        .count()
+        + 1;
+    return format!("{}:{}", line, column);
+}
+
+fn span(source: &str, span: Range<usize>) -> String {
+    return format!(
+        "{}-{}",
+        position(source, span.start),
+        position(source, span.end)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens() -> Result<(), Vec<String>> {
+        assert_eq!(tokens("1 + 2", "1   +  2 // same")?, Vec::<String>::new());
+        assert_eq!(
+            tokens("1 + 2\n", "1 +\n  3 * 2")?,
+            vec!["+ 2:3 '3'", "+ 2:5 '*'"]
+        );
+        assert_eq!(tokens("-a", "a")?, vec!["- 1:1 '-'"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ast() -> Result<(), Vec<String>> {
+        assert_eq!(ast("(1 + 2) * 3", "(1+2)*3")?, Vec::<String>::new());
+        assert_eq!(
+            ast("1 + \"høst\".length", "1 + \"høst\".size")?,
+            vec!["changed 1:5-1:18 -> 1:5-1:16: Get length -> Get size"]
+        );
+        assert_eq!(
+            ast("(1, 2)", "(1, 2, 3)")?,
+            vec!["added 1:8-1:9: Literal 3"]
+        );
+        assert_eq!(
+            ast("-1", "1")?,
+            vec!["changed 1:1-1:3 -> 1:1-1:2: Unary - -> Literal 1"]
+        );
+        assert_eq!(
+            ast("1 +", "1").unwrap_err(),
+            vec!["[line 1] Error at end: Expected expression."]
+        );
+
+        Ok(())
+    }
+}
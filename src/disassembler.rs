@@ -0,0 +1,166 @@
+use std::fmt::Write;
+
+use crate::chunk::{Chunk, OpCode};
+
+/// Lists every instruction in `chunk` under a `== name ==` header, one
+/// per line with its offset, source line (`|` when unchanged) and operands.
+pub fn disassemble(chunk: &Chunk, name: &str) -> String {
+    let mut output = format!("== {} ==\n", name);
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        offset = disassemble_instruction(chunk, offset, &mut output);
+    }
+    return output;
+}
+
+/// Writes the instruction at `offset` and returns the offset of the next one.
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize, output: &mut String) -> usize {
+    write!(output, "{:04} ", offset).unwrap();
+    if offset > 0 && chunk.lines[offset] == chunk.lines[offset - 1] {
+        output.push_str("   | ");
+    } else {
+        write!(output, "{:4} ", chunk.lines[offset]).unwrap();
+    }
+
+    let byte = chunk.code[offset];
+    let op = match OpCode::from_byte(byte) {
+        Some(op) => op,
+        None => {
+            writeln!(output, "Unknown opcode {}", byte).unwrap();
+            return offset + 1;
+        }
+    };
+
+    match op {
+        OpCode::Constant => {
+            let index = chunk.code[offset + 1];
+            let constant = &chunk.constants[index as usize];
+            writeln!(output, "{:<16} {:4} '{}'", op.name(), index, constant).unwrap();
+            return offset + 2;
+        }
+        OpCode::GetProperty => {
+            let index = chunk.code[offset + 1];
+            let name = &chunk.constants[index as usize];
+            writeln!(output, "{:<16} {:4} '{}'", op.name(), index, name).unwrap();
+            return offset + 2;
+        }
+        OpCode::Invoke | OpCode::CallNative => {
+            let index = chunk.code[offset + 1];
+            let count = chunk.code[offset + 2];
+            let name = &chunk.constants[index as usize];
+            writeln!(
+                output,
+                "{:<16} ({} args) {:4} '{}'",
+                op.name(),
+                count,
+                index,
+                name
+            )
+            .unwrap();
+            return offset + 3;
+        }
+        OpCode::Tuple | OpCode::GetLocal | OpCode::Unbind | OpCode::MatchTuple => {
+            let length = chunk.code[offset + 1];
+            writeln!(output, "{:<16} {:4}", op.name(), length).unwrap();
+            return offset + 2;
+        }
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::JumpIfNotNil => {
+            let jump = u16::from_be_bytes([chunk.code[offset + 1], chunk.code[offset + 2]]);
+            let target = offset + 3 + jump as usize;
+            writeln!(output, "{:<16} {:4} -> {}", op.name(), offset, target).unwrap();
+            return offset + 3;
+        }
+        _ => {
+            writeln!(output, "{}", op.name()).unwrap();
+            return offset + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compiler, parser::Parser, scanner::Scanner, value::Value};
+
+    use super::*;
+
+    #[test]
+    fn test_disassemble() -> Result<(), String> {
+        let tokens = Scanner::new("-1.2 <\n(false ? 3 : 4)")
+            .scan_tokens()
+            .unwrap();
+        let program = Parser::new(&tokens).parse()?;
+        let chunk = compiler::compile(&program)?;
+
+        assert_eq!(
+            disassemble(&chunk, "code"),
+            "== code ==\n\
+             0000    1 OP_CONSTANT         0 '1.2'\n\
+             0002    | OP_NEGATE\n\
+             0003    2 OP_FALSE\n\
+             0004    | OP_JUMP_IF_FALSE    4 -> 13\n\
+             0007    | OP_POP\n\
+             0008    | OP_CONSTANT         1 '3'\n\
+             0010    | OP_JUMP            10 -> 16\n\
+             0013    | OP_POP\n\
+             0014    | OP_CONSTANT         2 '4'\n\
+             0016    1 OP_LESS\n\
+             0017    | OP_RETURN\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tuple() -> Result<(), String> {
+        let tokens = Scanner::new("(nil, true)[1]").scan_tokens().unwrap();
+        let chunk = compiler::compile(&Parser::new(&tokens).parse()?)?;
+
+        assert_eq!(
+            disassemble(&chunk, "code"),
+            "== code ==\n\
+             0000    1 OP_NIL\n\
+             0001    | OP_TRUE\n\
+             0002    | OP_TUPLE            2\n\
+             0004    | OP_CONSTANT         0 '1'\n\
+             0006    | OP_INDEX\n\
+             0007    | OP_RETURN\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_methods() -> Result<(), String> {
+        let tokens = Scanner::new("\"ab\".slice(0, 1).length")
+            .scan_tokens()
+            .unwrap();
+        let chunk = compiler::compile(&Parser::new(&tokens).parse()?)?;
+
+        assert_eq!(
+            disassemble(&chunk, "code"),
+            "== code ==\n\
+             0000    1 OP_CONSTANT         0 'ab'\n\
+             0002    | OP_CONSTANT         1 '0'\n\
+             0004    | OP_CONSTANT         2 '1'\n\
+             0006    | OP_INVOKE        (2 args)    3 'slice'\n\
+             0009    | OP_GET_PROPERTY     4 'length'\n\
+             0011    | OP_RETURN\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_opcode() -> Result<(), String> {
+        let mut chunk = Chunk::new();
+        chunk.write(0xfe, 7);
+        chunk.add_constant(Value::Nil);
+
+        assert_eq!(
+            disassemble(&chunk, "bad"),
+            "== bad ==\n0000    7 Unknown opcode 254\n"
+        );
+
+        Ok(())
+    }
+}
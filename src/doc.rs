@@ -0,0 +1,97 @@
+//! `udyr doc`: documentation for a script from its `///` doc comments.
+//! There are no declarations in the language yet, so the one thing to
+//! document is the script itself, by the doc comment right above its
+//! expression.
+
+use crate::{highlight::escape_html, scanner::Scanner};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Markdown,
+    Html,
+}
+
+/// The doc comment right above the expression in `source`, without the
+/// `///` and the space after it, or `None` if there is none. A blank line
+/// or another comment between them detaches it.
+pub fn doc_comment(source: &str) -> Result<Option<String>, Vec<String>> {
+    let mut scanner = Scanner::new(source);
+    scanner.set_trivia(true);
+    let tokens = scanner.scan_tokens()?;
+    let leading = tokens[0]
+        .trivia
+        .as_ref()
+        .map_or("", |trivia| &trivia.leading);
+
+    let mut lines = Vec::new();
+    for line in leading.lines().rev() {
+        let Some(text) = line.trim().strip_prefix("///") else {
+            break;
+        };
+        lines.push(text.strip_prefix(' ').unwrap_or(text));
+    }
+    if lines.is_empty() {
+        return Ok(None);
+    }
+    lines.reverse();
+    return Ok(Some(lines.join("\n")));
+}
+
+/// Documentation for the script called `name`, with its doc comment as
+/// the text.
+pub fn generate(name: &str, source: &str, format: Format) -> Result<String, Vec<String>> {
+    let text = doc_comment(source)?;
+    let text = text.as_deref().unwrap_or("*Not documented.*");
+    match format {
+        Format::Markdown => return Ok(format!("# {}\n\n{}\n", name, text)),
+        Format::Html => {
+            let mut output = format!("<h1>{}</h1>\n", escape_html(name));
+            for paragraph in text.split("\n\n") {
+                output.push_str(&format!("<p>{}</p>\n", escape_html(paragraph.trim())));
+            }
+            return Ok(output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_doc_comment() -> Result<(), Vec<String>> {
+        assert_eq!(
+            doc_comment("// Not this.\n\n/// The answer,\n///computed.\n40 + 2")?,
+            Some(String::from("The answer,\ncomputed."))
+        );
+        assert_eq!(doc_comment("/// Detached.\n\n1")?, None);
+        assert_eq!(doc_comment("/// Mine.\n// Not a doc comment.\n1")?, None);
+        assert_eq!(doc_comment("1 /// Not above it.")?, None);
+        assert_eq!(doc_comment("")?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate() -> Result<(), Vec<String>> {
+        let source = "/// Adds <two> numbers.\n///\n/// Twice.\n1 + 2";
+        assert_eq!(
+            generate("add.udyr", source, Format::Markdown)?,
+            "# add.udyr\n\nAdds <two> numbers.\n\nTwice.\n"
+        );
+        assert_eq!(
+            generate("add.udyr", source, Format::Html)?,
+            "<h1>add.udyr</h1>\n<p>Adds &lt;two&gt; numbers.</p>\n<p>Twice.</p>\n"
+        );
+        assert_eq!(
+            generate("a.udyr", "1", Format::Markdown)?,
+            "# a.udyr\n\n*Not documented.*\n"
+        );
+        assert_eq!(
+            generate("a.udyr", "\"a", Format::Markdown).unwrap_err(),
+            vec![String::from("[line 1] Error: Unterminated string.")]
+        );
+
+        Ok(())
+    }
+}
@@ -1,3 +1,38 @@
+use std::{fmt, ops::Range};
+
+use crate::token::Token;
+
+/// An error or warning, and where the token it is about is in the source,
+/// so the token can be pointed at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    /// The offset and length of the token in bytes, as a range. `None` for
+    /// diagnostics that are not about a token.
+    pub span: Option<Range<usize>>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<String> for Diagnostic {
+    fn from(message: String) -> Diagnostic {
+        return Diagnostic {
+            message,
+            span: None,
+        };
+    }
+}
+
+impl From<Diagnostic> for String {
+    fn from(diagnostic: Diagnostic) -> String {
+        return diagnostic.message;
+    }
+}
+
 pub fn error(line: usize, message: &str) -> String {
     report(line, "", message)
 }
@@ -5,3 +40,16 @@ pub fn error(line: usize, message: &str) -> String {
 pub fn report(line: usize, location: &str, message: &str) -> String {
     format!("[line {}] Error{}: {}", line, location, message)
 }
+
+/// Like [`report`], for an error about `token`.
+pub fn report_at(token: &Token, location: &str, message: &str) -> Diagnostic {
+    return Diagnostic {
+        message: report(token.line, location, message),
+        span: Some(token.span()),
+    };
+}
+
+/// Formats a diagnostic that does not stop the program.
+pub fn warning(line: usize, message: &str) -> String {
+    format!("[line {}] Warning: {}", line, message)
+}
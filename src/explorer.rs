@@ -0,0 +1,254 @@
+//! `udyr explore`: the source of a script next to its syntax tree. Moving
+//! through the tree highlights the source of the selected node, and going
+//! to a place in the source selects the innermost node there.
+
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+use crate::ast::{Ast, ExprId, Program, Stmt};
+
+const HELP: &str = "\
+next      select the next node
+prev      select the previous node
+up        select the parent node
+down      select the first child node
+goto L:C  select the innermost node at line L, column C
+quit      leave the explorer
+help      show this help
+";
+
+/// A node of the tree, in the order the tree prints.
+#[derive(Debug)]
+struct Node {
+    label: String,
+    depth: usize,
+    span: Range<usize>,
+    parent: Option<usize>,
+}
+
+pub struct Explorer<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    source: String,
+    nodes: Vec<Node>,
+    selected: usize,
+}
+
+impl<R: BufRead, W: Write> Explorer<R, W> {
+    pub fn new(source: &str, program: &Program, input: R, output: W) -> Explorer<R, W> {
+        let mut nodes = Vec::new();
+        for stmt in &program.statements {
+            match stmt {
+                Stmt::Expression(expr) => {
+                    nodes.push(Node {
+                        label: String::from("Expression"),
+                        depth: 0,
+                        span: program.ast.span(*expr),
+                        parent: None,
+                    });
+                    flatten(&program.ast, *expr, 1, nodes.len() - 1, &mut nodes);
+                }
+            }
+        }
+        return Explorer {
+            input,
+            output,
+            source: source.to_string(),
+            nodes,
+            selected: 0,
+        };
+    }
+
+    /// Shows the script and reads commands until `quit` or the end of
+    /// the input.
+    pub fn run(&mut self) -> io::Result<()> {
+        if self.nodes.is_empty() {
+            return writeln!(self.output, "Nothing to explore.");
+        }
+        write!(self.output, "{}", self.render())?;
+        loop {
+            write!(self.output, "(explore) ")?;
+            self.output.flush()?;
+            let mut command = String::new();
+            if self.input.read_line(&mut command)? == 0 {
+                return Ok(());
+            }
+
+            let mut words = command.split_whitespace();
+            let selected = match (words.next(), words.next()) {
+                (Some("next" | "n"), None) => Ok(self.selected + 1),
+                (Some("prev" | "p"), None) => {
+                    self.selected.checked_sub(1).ok_or("At the first node.")
+                }
+                (Some("up" | "u"), None) => self.nodes[self.selected].parent.ok_or("At the top."),
+                (Some("down" | "d"), None) => match self.nodes.get(self.selected + 1) {
+                    Some(child) if child.parent == Some(self.selected) => Ok(self.selected + 1),
+                    _ => Err("The node has no children."),
+                },
+                (Some("goto" | "g"), Some(position)) => self.node_at(position),
+                (Some("quit" | "q"), None) => return Ok(()),
+                (Some("help" | "h"), None) => {
+                    write!(self.output, "{}", HELP)?;
+                    continue;
+                }
+                (None, _) => continue,
+                _ => Err("Unknown command. Type 'help' for a list."),
+            };
+            match selected {
+                Ok(selected) if selected < self.nodes.len() => {
+                    self.selected = selected;
+                    write!(self.output, "{}", self.render())?;
+                }
+                Ok(_) => writeln!(self.output, "At the last node.")?,
+                Err(message) => writeln!(self.output, "{}", message)?,
+            }
+        }
+    }
+
+    /// The innermost node covering `position`, written `line:column`.
+    fn node_at(&self, position: &str) -> Result<usize, &'static str> {
+        let (line, column) = position
+            .split_once(':')
+            .and_then(|(line, column)| Some((line.parse().ok()?, column.parse().ok()?)))
+            .ok_or("Expected a position like 2:5.")?;
+        let offset = offset_at(&self.source, line, column).ok_or("No such position.")?;
+        // Later nodes are deeper, so they win ties.
+        return self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.span.contains(&offset))
+            .min_by_key(|(index, node)| (node.span.len(), usize::MAX - index))
+            .map(|(index, _)| index)
+            .ok_or("Nothing there.");
+    }
+
+    /// The source, with the selected node's part in reverse video, next to
+    /// the tree, with the selected node marked.
+    fn render(&self) -> String {
+        let span = &self.nodes[self.selected].span;
+        let mut left = Vec::new();
+        let mut offset = 0;
+        for line in self.source.split_inclusive('\n') {
+            let line = line.trim_end_matches('\n');
+            // Spans start and end on tokens, so these are character boundaries.
+            let start = span.start.clamp(offset, offset + line.len()) - offset;
+            let end = span.end.clamp(offset, offset + line.len()) - offset;
+            let text = if start < end {
+                let (before, after) = (&line[..start], &line[end..]);
+                format!("{}\x1b[7m{}\x1b[27m{}", before, &line[start..end], after)
+            } else {
+                line.to_string()
+            };
+            left.push((text, line.chars().count()));
+            offset += line.len() + 1;
+        }
+        let right: Vec<String> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| {
+                let marker = if index == self.selected { "> " } else { "  " };
+                format!("{}{}{}", marker, "  ".repeat(node.depth), node.label)
+            })
+            .collect();
+
+        let width = left.iter().map(|(_, width)| *width).max().unwrap_or(0);
+        let mut output = String::new();
+        for row in 0..left.len().max(right.len()) {
+            let (text, length) = left
+                .get(row)
+                .map_or(("", 0), |(text, length)| (text, *length));
+            let node = right.get(row).map_or("", String::as_str);
+            let line = format!("{}{} | {}", text, " ".repeat(width - length), node);
+            output.push_str(line.trim_end());
+            output.push('\n');
+        }
+        return output;
+    }
+}
+
+/// Adds `expr` and everything in it to `nodes`, below the node at `parent`.
+fn flatten(ast: &Ast, expr: ExprId, depth: usize, parent: usize, nodes: &mut Vec<Node>) {
+    let index = nodes.len();
+    nodes.push(Node {
+        label: ast[expr].label(),
+        depth,
+        span: ast.span(expr),
+        parent: Some(parent),
+    });
+    for child in ast[expr].children() {
+        flatten(ast, child, depth + 1, index, nodes);
+    }
+}
+
+/// The byte offset of `line` and `column`, both counted from 1, with the
+/// column in characters.
+fn offset_at(source: &str, line: usize, column: usize) -> Option<usize> {
+    let mut offset = 0;
+    for (number, text) in source.split_inclusive('\n').enumerate() {
+        if number + 1 == line {
+            let text = text.trim_end_matches('\n');
+            let (index, _) = text.char_indices().nth(column.checked_sub(1)?)?;
+            return Some(offset + index);
+        }
+        offset += text.len();
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::Parser, scanner::Scanner};
+
+    use super::*;
+
+    fn explore(source: &str, commands: &str) -> String {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let program = Parser::new(&tokens).parse().unwrap();
+        let mut output = Vec::new();
+        Explorer::new(source, &program, commands.as_bytes(), &mut output)
+            .run()
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_navigate() -> Result<(), String> {
+        let output = explore(
+            "1 +\n-2",
+            "down\ndown\nnext\nup\nprev\nprev\nprev\nquit\nnext\n",
+        );
+        let screens: Vec<&str> = output.split("(explore) ").collect();
+
+        assert_eq!(
+            screens[0],
+            "\x1b[7m1 +\x1b[27m | > Expression\n\
+             \x1b[7m-2\x1b[27m  |     Binary +\n    \
+             |       Literal 1\n    \
+             |       Unary -\n    \
+             |         Literal 2\n"
+        );
+        assert!(screens[2].starts_with("\x1b[7m1\x1b[27m + |   Expression\n"));
+        assert!(screens[3].contains(">     Unary -"));
+        assert!(screens[3].contains("\x1b[7m-2\x1b[27m"));
+        assert!(screens[4].contains(">   Binary +"));
+        assert_eq!(screens[7], "At the first node.\n");
+        assert_eq!(screens.len(), 9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_goto() -> Result<(), String> {
+        let output = explore("(1, \"ab\")", "goto 1:6\ngoto 1:20\ngoto x\n");
+        let screens: Vec<&str> = output.split("(explore) ").collect();
+
+        assert!(screens[1].contains("(1, \x1b[7m\"ab\"\x1b[27m)"));
+        assert!(screens[1].contains(">     Literal \"ab\""));
+        assert_eq!(screens[2], "No such position.\n");
+        assert_eq!(screens[3], "Expected a position like 2:5.\n");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+//! The formatter behind `udyr fmt`. It works on the token stream, so
+//! comments survive and any program that scans can be formatted.
+
+use crate::{
+    scanner::Scanner,
+    token::{Token, TokenType},
+};
+
+const INDENT: &str = "    ";
+
+/// Formats `source` with one space around binary operators, none inside
+/// parentheses or after unary operators, and four spaces of indentation
+/// per open parenthesis or brace. Line breaks are kept, but blank lines are
+/// collapsed to one and trailing whitespace is removed.
+pub fn format(source: &str) -> Result<String, Vec<String>> {
+    let mut scanner = Scanner::new(source);
+    scanner.set_comments(true);
+    let tokens = scanner.scan_tokens()?;
+
+    let mut output = String::new();
+    let mut depth: usize = 0;
+    let mut previous: Option<&Token> = None;
+    // The last token that is not a comment, and whether it was a unary operator.
+    let mut previous_code: Option<&Token> = None;
+    let mut unary = false;
+
+    for token in &tokens {
+        if token.token_type == TokenType::EOF {
+            break;
+        }
+        // Strings and comments may span lines, and a token's line is where it ends.
+        let line = token.line - token.lexeme.matches('\n').count();
+        let line_start = previous.is_none_or(|previous| line > previous.line);
+
+        if let Some(previous) = previous {
+            if line > previous.line + 1 {
+                output.push_str("\n\n");
+            } else if line_start {
+                output.push('\n');
+            } else if spaced(previous, token, unary) {
+                output.push(' ');
+            }
+        }
+        if matches!(
+            token.token_type,
+            TokenType::RightParen | TokenType::RightBrace
+        ) {
+            depth = depth.saturating_sub(1);
+        }
+        if line_start && token.token_type != TokenType::Hash {
+            output.push_str(&INDENT.repeat(depth));
+        }
+
+        if token.token_type == TokenType::COMMENT {
+            output.push_str(token.lexeme.trim_end());
+        } else {
+            output.push_str(&token.lexeme);
+            unary = matches!(token.token_type, TokenType::BANG | TokenType::Minus)
+                && starts_operand(previous_code);
+            previous_code = Some(token);
+        }
+        if matches!(
+            token.token_type,
+            TokenType::LeftParen | TokenType::LeftBrace
+        ) {
+            depth += 1;
+        }
+        previous = Some(token);
+    }
+
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    return Ok(output);
+}
+
+/// Rewrites each invalid escape like `\q` in a string to the `\\q` its
+/// error suggests, for `udyr fmt --fix`. Everything else is left as it is.
+pub fn fix(source: &str) -> String {
+    let mut scanner = Scanner::new(source);
+    let _ = scanner.scan_tokens();
+
+    let mut fixed = String::with_capacity(source.len() + scanner.fixes.len());
+    let mut copied = 0;
+    for &backslash in &scanner.fixes {
+        fixed.push_str(&source[copied..backslash]);
+        fixed.push('\\');
+        copied = backslash;
+    }
+    fixed.push_str(&source[copied..]);
+    return fixed;
+}
+
+/// Whether a space goes between two tokens on the same line.
+fn spaced(previous: &Token, next: &Token, unary: bool) -> bool {
+    if next.token_type == TokenType::COMMENT {
+        return true;
+    }
+    if unary {
+        return false;
+    }
+    let call = next.token_type == TokenType::LeftParen
+        && matches!(
+            previous.token_type,
+            TokenType::IDENTIFIER | TokenType::RightParen | TokenType::RightBracket
+        );
+    if call || previous.token_type == TokenType::Dot || next.token_type == TokenType::Dot {
+        return false;
+    }
+    return !matches!(
+        previous.token_type,
+        TokenType::LeftParen | TokenType::LeftBracket | TokenType::Hash
+    ) && !matches!(
+        next.token_type,
+        TokenType::RightParen | TokenType::LeftBracket | TokenType::RightBracket | TokenType::Comma
+    );
+}
+
+/// Whether the token after `previous` begins an operand, which makes a
+/// `-` or `!` there a unary operator.
+fn starts_operand(previous: Option<&Token>) -> bool {
+    let Some(previous) = previous else {
+        return true;
+    };
+    return matches!(
+        previous.token_type,
+        TokenType::LeftParen
+            | TokenType::LeftBracket
+            | TokenType::LeftBrace
+            | TokenType::Comma
+            | TokenType::Question
+            | TokenType::Colon
+            | TokenType::Minus
+            | TokenType::Plus
+            | TokenType::SLASH
+            | TokenType::STAR
+            | TokenType::BANG
+            | TokenType::BangEqual
+            | TokenType::EQUAL
+            | TokenType::EqualEqual
+            | TokenType::GREATER
+            | TokenType::GreaterEqual
+            | TokenType::LESS
+            | TokenType::LessEqual
+            | TokenType::QuestionQuestion
+            | TokenType::FatArrow
+            | TokenType::AND
+            | TokenType::IF
+            | TokenType::MATCH
+            | TokenType::OR
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formatted(source: &str) -> String {
+        format(source).unwrap()
+    }
+
+    #[test]
+    fn test_operator_spacing() -> Result<(), String> {
+        assert_eq!(formatted("1+2*  3"), "1 + 2 * 3\n");
+        assert_eq!(formatted("-1 - - 2"), "-1 - -2\n");
+        assert_eq!(formatted("! true==( false )"), "!true == (false)\n");
+        assert_eq!(formatted("x?-1:2"), "x ? -1 : 2\n");
+        assert_eq!(formatted("( 1,-2 ,) [ -1 ]"), "(1, -2,)[-1]\n");
+        assert_eq!(
+            formatted("s . trim ( ) .slice (0,1)"),
+            "s.trim().slice(0, 1)\n"
+        );
+        assert_eq!(formatted(""), "");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lines_and_indentation() -> Result<(), String> {
+        assert_eq!(
+            formatted("\n\n  (1 +\n2) *   \n\n\n\n(3\n    - 4\n)"),
+            "(1 +\n    2) *\n\n(3\n    - 4\n)\n"
+        );
+        assert_eq!(
+            formatted("#if  DEBUG\n  1\n#else\n2\n# end"),
+            "#if DEBUG\n1\n#else\n2\n#end\n"
+        );
+        assert_eq!(
+            formatted("match (1,2) {\n(a, b) if a>b=>a,\n_ => match  nil {\n_=>-1\n}\n}"),
+            "match (1, 2) {\n    (a, b) if a > b => a,\n    _ => match nil {\n        _ => -1\n    }\n}\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_comments_preserved() -> Result<(), String> {
+        assert_eq!(
+            formatted("// total  \n1+/* two */2   // done"),
+            "// total\n1 + /* two */ 2 // done\n"
+        );
+        assert_eq!(
+            formatted("1 /* a\n  b */ +\n\"x\n y\""),
+            "1 /* a\n  b */ +\n\"x\n y\"\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_idempotent() -> Result<(), String> {
+        for source in [
+            "1+2",
+            "( 1 +\n2)//c\n*3",
+            "#if A\n-(1)\n#end",
+            "1 /* a\n  b */ +\n2",
+        ] {
+            let once = formatted(source);
+            assert_eq!(formatted(&once), once);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fix() -> Result<(), String> {
+        assert_eq!(fix("\"a\\qb\" + \"\\d\\n\""), "\"a\\\\qb\" + \"\\\\d\\n\"");
+        assert_eq!(fix("// \\q\n\"\\\\q\""), "// \\q\n\"\\\\q\"");
+        assert_eq!(formatted(&fix("\"\\w+\"+1")), "\"\\\\w+\" + 1\n");
+        assert_eq!(fix("\"\\u{zz}\""), "\"\\u{zz}\"");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_errors() -> Result<(), String> {
+        assert_eq!(
+            format("1 + \"a").unwrap_err(),
+            vec![String::from("[line 1] Error: Unterminated string.")]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+//! Golden-file tests for `udyr test`, in the format of the Crafting
+//! Interpreters test suite. A script states what it should do in comments:
+//!
+//! ```text
+//! 1 + 2 // expect: 3
+//! "a" - 1 // expect runtime error: Operands must be numbers.
+//! (1 + // [line 4] Error at end: Expected expression.
+//! ```
+//!
+//! An `// Error...` comment without a line number expects the error on the
+//! comment's own line.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{interpreter::Interpreter, scanner::Scanner, token::TokenType};
+
+/// What a script is expected to print and report.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Expectations {
+    /// Each line of output, with the line of the comment that expects it.
+    pub output: Vec<(String, usize)>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub path: PathBuf,
+    pub messages: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub passed: usize,
+    pub failures: Vec<Failure>,
+}
+
+/// Reads the expectations from the comments in `source`.
+pub fn expectations(source: &str) -> Expectations {
+    let mut scanner = Scanner::new(source);
+    scanner.set_comments(true);
+
+    let mut expectations = Expectations::default();
+    for token in scanner.flatten() {
+        if token.token_type != TokenType::COMMENT {
+            continue;
+        }
+        let Some(comment) = token.lexeme.strip_prefix("//") else {
+            continue;
+        };
+        let comment = comment.trim();
+        if let Some(output) = comment.strip_prefix("expect: ") {
+            expectations.output.push((output.to_string(), token.line));
+        } else if let Some(message) = comment.strip_prefix("expect runtime error: ") {
+            expectations
+                .errors
+                .push(format!("[line {}] Error: {}", token.line, message));
+        } else if comment.starts_with("Error") {
+            expectations
+                .errors
+                .push(format!("[line {}] {}", token.line, comment));
+        } else if comment.starts_with("[line ") {
+            expectations.errors.push(comment.to_string());
+        }
+    }
+    return expectations;
+}
+
+/// Runs a script on the VM and describes every way its output and errors
+/// differ from what its comments expect.
+pub fn run_test(source: &str) -> Result<(), Vec<String>> {
+    let expected = expectations(source);
+    let (output, errors) = match Interpreter::new().eval(source) {
+        Ok(value) => (vec![value.to_string()], Vec::new()),
+        Err(errors) => (Vec::new(), errors),
+    };
+
+    let mut messages = Vec::new();
+    for (index, (expected, line)) in expected.output.iter().enumerate() {
+        match output.get(index) {
+            Some(output) if output == expected => {}
+            Some(output) => messages.push(format!(
+                "Expected output '{}' on line {} and got '{}'.",
+                expected, line, output
+            )),
+            None => messages.push(format!(
+                "Missing expected output '{}' on line {}.",
+                expected, line
+            )),
+        }
+    }
+    for output in output.iter().skip(expected.output.len()) {
+        messages.push(format!("Got output '{}' when none was expected.", output));
+    }
+    for error in &expected.errors {
+        if !errors.contains(error) {
+            messages.push(format!("Missing expected error: {}", error));
+        }
+    }
+    for error in &errors {
+        if !expected.errors.contains(error) {
+            messages.push(format!("Unexpected error: {}", error));
+        }
+    }
+
+    if messages.is_empty() {
+        return Ok(());
+    }
+    return Err(messages);
+}
+
+/// Runs every `.udyr` script under `path`, or `path` itself if it is a file.
+pub fn run_tests(path: &Path) -> io::Result<Summary> {
+    let mut scripts = Vec::new();
+    find_scripts(path, &mut scripts)?;
+    scripts.sort();
+
+    let mut summary = Summary::default();
+    for script in scripts {
+        match run_test(&std::fs::read_to_string(&script)?) {
+            Ok(()) => summary.passed += 1,
+            Err(messages) => summary.failures.push(Failure {
+                path: script,
+                messages,
+            }),
+        }
+    }
+    return Ok(summary);
+}
+
+/// Adds `path` to `scripts` if it is a file, or every `.udyr` script
+/// under it if it is a directory.
+pub fn find_scripts(path: &Path, scripts: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        scripts.push(path.to_path_buf());
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_scripts(&path, scripts)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "udyr")
+        {
+            scripts.push(path);
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expectations() -> Result<(), String> {
+        let expectations = expectations(
+            "\"// expect: no\" // expect: yes\n\
+             // expect runtime error: Oops.\n\
+             ( // Error at '(': Bad.\n\
+             // [line 9] Error at end: Worse.\n",
+        );
+
+        assert_eq!(expectations.output, vec![(String::from("yes"), 1)]);
+        assert_eq!(
+            expectations.errors,
+            vec![
+                String::from("[line 2] Error: Oops."),
+                String::from("[line 3] Error at '(': Bad."),
+                String::from("[line 9] Error at end: Worse."),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_test() -> Result<(), String> {
+        assert_eq!(run_test("1 + 2 // expect: 3"), Ok(()));
+        assert_eq!(
+            run_test("1 + 2 // expect: 4"),
+            Err(vec![String::from(
+                "Expected output '4' on line 1 and got '3'."
+            )])
+        );
+        assert_eq!(
+            run_test("-\"a\" // expect: a"),
+            Err(vec![
+                String::from("Missing expected output 'a' on line 1."),
+                String::from("Unexpected error: [line 1] Error: Operand must be a number."),
+            ])
+        );
+        assert_eq!(
+            run_test("1 +\n// [line 3] Error at end: Expected expression.\n"),
+            Ok(())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_suite() -> Result<(), String> {
+        let summary = run_tests(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")).unwrap();
+
+        assert_eq!(summary.failures, Vec::new());
+        assert!(summary.passed > 0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+//! Syntax highlighting for `udyr highlight` and the REPL, using the
+//! scanner's token types to classify the source.
+
+use crate::{scanner::Scanner, token::TokenType, value::Value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Colors for a terminal.
+    Ansi,
+    /// `<span>`s with `udyr-*` CSS classes inside a `<pre class="udyr">`.
+    Html,
+}
+
+/// The kinds of tokens that are highlighted. Everything else, like
+/// identifiers and operators, is written as it is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Class {
+    Keyword,
+    Literal,
+    String,
+    Comment,
+    Pragma,
+}
+
+impl Class {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Class::Keyword => "keyword",
+            Class::Literal => "literal",
+            Class::String => "string",
+            Class::Comment => "comment",
+            Class::Pragma => "pragma",
+        }
+    }
+
+    fn ansi(&self) -> &'static str {
+        match self {
+            Class::Keyword => "\x1b[35m",
+            Class::Literal => "\x1b[33m",
+            Class::String => "\x1b[32m",
+            Class::Comment => "\x1b[90m",
+            Class::Pragma => "\x1b[36m",
+        }
+    }
+}
+
+pub fn class(token_type: &TokenType) -> Option<Class> {
+    match token_type {
+        TokenType::NUMBER | TokenType::TRUE | TokenType::FALSE | TokenType::NIL => {
+            Some(Class::Literal)
+        }
+        TokenType::STRING => Some(Class::String),
+        TokenType::COMMENT => Some(Class::Comment),
+        TokenType::Hash => Some(Class::Pragma),
+        TokenType::AND
+        | TokenType::CLASS
+        | TokenType::ELSE
+        | TokenType::FUN
+        | TokenType::FOR
+        | TokenType::IF
+        | TokenType::MATCH
+        | TokenType::OR
+        | TokenType::PRINT
+        | TokenType::RETURN
+        | TokenType::SUPER
+        | TokenType::THIS
+        | TokenType::VAR
+        | TokenType::WHILE => Some(Class::Keyword),
+        _ => None,
+    }
+}
+
+/// Highlights `source`, keeping its text exactly. Anything the scanner
+/// rejects is left unhighlighted.
+pub fn highlight(source: &str, format: Format) -> String {
+    let mut scanner = Scanner::new(source);
+    scanner.set_comments(true);
+
+    let mut output = String::new();
+    if format == Format::Html {
+        output.push_str("<pre class=\"udyr\">");
+    }
+    let mut position = 0;
+    let mut pragma = false;
+    for token in scanner.flatten() {
+        if token.token_type == TokenType::EOF {
+            break;
+        }
+        write_text(&mut output, &source[position..token.offset], format);
+        // The directive after a `#` belongs to the pragma.
+        let class = if pragma {
+            Some(Class::Pragma)
+        } else {
+            class(&token.token_type)
+        };
+        pragma = token.token_type == TokenType::Hash;
+
+        match (class, format) {
+            (None, _) => write_text(&mut output, &token.lexeme, format),
+            (Some(class), Format::Ansi) => {
+                output.push_str(class.ansi());
+                output.push_str(&token.lexeme);
+                output.push_str("\x1b[0m");
+            }
+            (Some(class), Format::Html) => {
+                output.push_str(&format!("<span class=\"udyr-{}\">", class.name()));
+                write_text(&mut output, &token.lexeme, format);
+                output.push_str("</span>");
+            }
+        }
+        position = token.offset + token.lexeme.len();
+    }
+    write_text(&mut output, &source[position..], format);
+    if format == Format::Html {
+        output.push_str("</pre>\n");
+    }
+    return output;
+}
+
+/// Colors a value for a terminal the way it prints. Strings print without
+/// quotes, so they are colored whole.
+pub fn highlight_value(value: &Value) -> String {
+    match value {
+        Value::String(string) => return format!("{}{}\x1b[0m", Class::String.ansi(), string),
+        value => return highlight(&value.to_string(), Format::Ansi),
+    }
+}
+
+fn write_text(output: &mut String, text: &str, format: Format) {
+    match format {
+        Format::Ansi => output.push_str(text),
+        Format::Html => output.push_str(&escape_html(text)),
+    }
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut output = String::new();
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            _ => output.push(c),
+        }
+    }
+    return output;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ansi() -> Result<(), String> {
+        assert_eq!(
+            highlight("x < 1 // one\n", Format::Ansi),
+            "x < \x1b[33m1\x1b[0m \x1b[90m// one\x1b[0m\n"
+        );
+        assert_eq!(
+            highlight("#if A\nnil", Format::Ansi),
+            "\x1b[36m#\x1b[0m\x1b[36mif\x1b[0m A\n\x1b[33mnil\x1b[0m"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_highlight_value() -> Result<(), String> {
+        assert_eq!(highlight_value(&Value::from("a b")), "\x1b[32ma b\x1b[0m");
+        let tuple = Value::Tuple(vec![Value::Nil, Value::from("c")].into());
+        assert_eq!(
+            highlight_value(&tuple),
+            "(\x1b[33mnil\x1b[0m, \x1b[32m\"c\"\x1b[0m)"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_html() -> Result<(), String> {
+        assert_eq!(
+            highlight("\"<a>\" and x", Format::Html),
+            "<pre class=\"udyr\"><span class=\"udyr-string\">&quot;&lt;a&gt;&quot;</span> \
+             <span class=\"udyr-keyword\">and</span> x</pre>\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_source_kept() -> Result<(), String> {
+        assert_eq!(
+            highlight("1 @ \"open", Format::Ansi),
+            "\x1b[33m1\x1b[0m @ \"open"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,340 @@
+//! A script kept scanned and parsed as it is edited, for editors and the
+//! language server. An edit rescans only the tokens around it, and keeps
+//! the syntax tree when it leaves the kind of every token alone, as
+//! renaming, changing a literal or editing whitespace and comments do.
+
+use std::{ops::Range, sync::Arc};
+
+use crate::{
+    ast::{Expr, Pattern, Program},
+    interner::Interner,
+    parser::Parser,
+    scanner::Scanner,
+    token::{Token, TokenType},
+};
+
+pub struct Document {
+    /// Shared with the lexemes of the tokens.
+    source: Arc<str>,
+    interner: Interner,
+    /// The tokens of `source`, ending with EOF, or why it could not be
+    /// scanned.
+    tokens: Result<Vec<Token>, Vec<String>>,
+    program: Result<Program, Vec<String>>,
+    reused_tokens: usize,
+    reused_tree: bool,
+}
+
+impl Document {
+    pub fn new(source: &str) -> Document {
+        return Document::shared(Arc::from(source));
+    }
+
+    fn shared(source: Arc<str>) -> Document {
+        let mut scanner = Scanner::shared(Arc::clone(&source));
+        let tokens = scanner.scan_tokens();
+        let program = parse(&tokens);
+        return Document {
+            source,
+            interner: scanner.into_interner(),
+            tokens,
+            program,
+            reused_tokens: 0,
+            reused_tree: false,
+        };
+    }
+
+    pub fn source(&self) -> &str {
+        return &self.source;
+    }
+
+    pub fn tokens(&self) -> Result<&[Token], &[String]> {
+        return self.tokens.as_deref().map_err(Vec::as_slice);
+    }
+
+    pub fn program(&self) -> Result<&Program, &[String]> {
+        return self.program.as_ref().map_err(Vec::as_slice);
+    }
+
+    /// The names of the identifiers in the document, by symbol.
+    pub fn interner(&self) -> &Interner {
+        return &self.interner;
+    }
+
+    /// How many tokens, not counting EOF, the last edit kept from before it.
+    pub fn reused_tokens(&self) -> usize {
+        return self.reused_tokens;
+    }
+
+    /// Whether the last edit kept the syntax tree.
+    pub fn reused_tree(&self) -> bool {
+        return self.reused_tree;
+    }
+
+    /// Replaces the bytes in `range` with `text`.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) -> Result<(), String> {
+        if range.start > range.end
+            || range.end > self.source.len()
+            || !self.source.is_char_boundary(range.start)
+            || !self.source.is_char_boundary(range.end)
+        {
+            return Err(format!(
+                "Cannot replace bytes {}..{} of a document of {} bytes.",
+                range.start,
+                range.end,
+                self.source.len()
+            ));
+        }
+        let mut source = self.source.to_string();
+        source.replace_range(range.clone(), text);
+        let source: Arc<str> = Arc::from(source);
+
+        // Errors carry line numbers that an edit can move, so a document
+        // that did not scan is scanned again in full.
+        let rescanned = match std::mem::replace(&mut self.tokens, Ok(Vec::new())) {
+            Ok(old) => self
+                .rescan(&old, &source, range, text.len())
+                .map(|rescanned| (old, rescanned)),
+            Err(_) => None,
+        };
+        let Some((old, (tokens, reused))) = rescanned else {
+            *self = Document::shared(source);
+            return Ok(());
+        };
+
+        let same_shape = old.len() == tokens.len()
+            && old
+                .iter()
+                .zip(&tokens)
+                .all(|(a, b)| a.token_type == b.token_type);
+        // The parser only looks at the kinds of tokens, so the tree has the
+        // same shape and only needs the new tokens.
+        self.reused_tree = same_shape && self.program.is_ok();
+        if self.reused_tree {
+            if let Ok(program) = &mut self.program {
+                for id in program.ast.ids() {
+                    retoken(&mut program.ast[id], &old, &tokens);
+                }
+            }
+        }
+        self.source = source;
+        self.tokens = Ok(tokens);
+        if !self.reused_tree {
+            self.program = parse(&self.tokens);
+        }
+        self.reused_tokens = reused;
+        return Ok(());
+    }
+
+    /// The tokens of `source`, which is the old source with `range`
+    /// replaced by `length` bytes, and how many of them were kept. Returns
+    /// `None` if the new source does not scan.
+    fn rescan(
+        &mut self,
+        old: &[Token],
+        source: &Arc<str>,
+        range: Range<usize>,
+        length: usize,
+    ) -> Option<(Vec<Token>, usize)> {
+        let end = |token: &Token| token.offset + token.lexeme.len();
+        // A token ending where the edit starts can grow into it, and the one
+        // before that can look ahead into it, as `1` does in `1.5`.
+        let touched = old
+            .iter()
+            .position(|token| token.token_type == TokenType::EOF || end(token) >= range.start)
+            .expect("the tokens end with EOF");
+        let first = touched.saturating_sub(1);
+        let (offset, line) = if first < touched {
+            // Only strings span lines, and their line is where they end.
+            let token = &old[first];
+            (
+                token.offset,
+                token.line - token.lexeme.matches('\n').count(),
+            )
+        } else {
+            (0, 1)
+        };
+
+        let shift = length as isize - range.len() as isize;
+        let edited = range.start + length;
+        let interner = std::mem::take(&mut self.interner);
+        // Kept tokens are moved into the new source, so the old one can go.
+        let moved = |token: &Token, shift: isize| {
+            let mut token = token.clone();
+            token.lexeme = token.lexeme.moved(&self.source, source, shift);
+            token.literal = token.literal.moved(&self.source, source, shift);
+            token.offset = (token.offset as isize + shift) as usize;
+            return token;
+        };
+        let mut scanner = Scanner::resume(Arc::clone(source), offset, line, interner);
+        let mut tokens: Vec<Token> = old[..first].iter().map(|token| moved(token, 0)).collect();
+        let mut next = first;
+        let mut reused = first;
+        for token in scanner.by_ref() {
+            let Ok(token) = token else {
+                self.interner = scanner.into_interner();
+                return None;
+            };
+            // Past the edit, the new source is the old one moved by `shift`.
+            // Once a token starts where an old one did, the rest follows
+            // as before.
+            if token.offset >= edited {
+                let old_offset = (token.offset as isize - shift) as usize;
+                while old[next].offset < old_offset {
+                    next += 1;
+                }
+                let same = &old[next];
+                if same.offset == old_offset && same.token_type == token.token_type {
+                    let lines = token.line as isize - same.line as isize;
+                    reused += old.len() - next - 1;
+                    tokens.extend(old[next..].iter().map(|token| {
+                        let mut token = moved(token, shift);
+                        token.line = (token.line as isize + lines) as usize;
+                        return token;
+                    }));
+                    break;
+                }
+            }
+            tokens.push(token);
+        }
+        self.interner = scanner.into_interner();
+        return Some((tokens, reused));
+    }
+}
+
+fn parse(tokens: &Result<Vec<Token>, Vec<String>>) -> Result<Program, Vec<String>> {
+    let tokens = tokens.as_ref().map_err(Clone::clone)?;
+    return Parser::new(tokens)
+        .parse()
+        .map_err(|error| vec![error.into()]);
+}
+
+/// Swaps each token of `expr`, but not of the expressions in it, found
+/// by where it is in `old`, for the token in the same place in `new`.
+fn retoken(expr: &mut Expr, old: &[Token], new: &[Token]) {
+    let swap = |token: &mut Token| {
+        if let Ok(index) = old.binary_search_by_key(&token.offset, |token| token.offset) {
+            *token = new[index].clone();
+        }
+    };
+    match expr {
+        Expr::Binary { operator, .. } | Expr::Unary { operator, .. } => swap(operator),
+        Expr::Call { paren, .. } | Expr::Tuple { paren, .. } => swap(paren),
+        Expr::Get { name, .. } => swap(name),
+        Expr::Index { bracket, .. } => swap(bracket),
+        Expr::Literal(token) | Expr::Variable(token) => swap(token),
+        Expr::Match { keyword, arms, .. } => {
+            swap(keyword);
+            for arm in arms {
+                retoken_pattern(&mut arm.pattern, &swap);
+            }
+        }
+        Expr::Conditional { .. } | Expr::Grouping(_) => {}
+    }
+}
+
+fn retoken_pattern(pattern: &mut Pattern, swap: &impl Fn(&mut Token)) {
+    match pattern {
+        Pattern::Wildcard(token) | Pattern::Binding(token) | Pattern::Literal(token) => swap(token),
+        Pattern::Tuple { paren, elements } => {
+            swap(paren);
+            for element in elements {
+                retoken_pattern(element, swap);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Stmt;
+
+    use super::*;
+
+    /// Edits `source` and checks the result against scanning and parsing
+    /// the edited source from scratch.
+    fn check(source: &str, range: Range<usize>, text: &str) -> Document {
+        let mut document = Document::new(source);
+        document.edit(range.clone(), text).unwrap();
+
+        let mut edited = source.to_string();
+        edited.replace_range(range, text);
+        let fresh = Document::new(&edited);
+        assert_eq!(document.source(), edited);
+        assert_eq!(
+            document.tokens(),
+            fresh.tokens(),
+            "after editing {:?}",
+            source
+        );
+        assert_eq!(
+            document.program(),
+            fresh.program(),
+            "after editing {:?}",
+            source
+        );
+        document
+    }
+
+    #[test]
+    fn test_edit() -> Result<(), String> {
+        check("1 + 2", 4..5, "20");
+        check("1 + 2", 5..5, " * 3");
+        check("1.x", 2..3, "5");
+        check("1 = 2", 2..3, "==");
+        check("1 // two\n+ 2", 2..4, "");
+        check("1 + \"a\nb\" +\n3", 6..7, "\n\n");
+        check("\"a\" + 1", 0..1, "");
+        check("\"a + 1", 0..0, "\"");
+        check("(1, 2)", 0..6, "");
+        check("", 0..0, "true ? nil : 1");
+        check("match x { (a, 1) => a }", 14..15, "\"c\", _");
+        check("match x { 1 => x }", 10..11, "(nil, 2.5)");
+
+        let mut document = Document::new("1");
+        assert_eq!(
+            document.edit(0..2, ""),
+            Err(String::from(
+                "Cannot replace bytes 0..2 of a document of 1 bytes."
+            ))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reuse() -> Result<(), String> {
+        let document = check("1 + 2 + 3 + 4", 4..5, "20");
+        assert_eq!(document.reused_tokens(), 5);
+        assert!(document.reused_tree());
+        // Kept tokens are moved into the edited source too.
+        let source = document.source().as_bytes().as_ptr_range();
+        for token in &document.tokens().unwrap()[..7] {
+            assert!(source.contains(&token.lexeme.as_ptr()));
+        }
+
+        let document = check("1 +\n\n2 * 3", 3..5, "");
+        assert_eq!(document.reused_tokens(), 3);
+        assert!(document.reused_tree());
+
+        let document = check("1 + 2 * 3", 2..3, "-");
+        assert!(!document.reused_tree());
+
+        let mut document = Document::new("a.b + a");
+        document.edit(2..3, "size").unwrap();
+        let program = document.program().unwrap();
+        let [Stmt::Expression(sum)] = program.statements[..] else {
+            panic!("expected one expression");
+        };
+        let Expr::Binary { left, .. } = program.ast[sum] else {
+            panic!("expected a binary expression");
+        };
+        let Expr::Get { name, .. } = &program.ast[left] else {
+            panic!("expected a property");
+        };
+        assert_eq!(name.lexeme, "size");
+        assert_eq!(document.interner().resolve(name.symbol.unwrap()), "size");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+/// A small id standing in for an interned string. Two symbols from the
+/// same [`Interner`] are equal exactly when their strings are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+#[derive(Debug, Default)]
+pub struct Interner {
+    symbols: HashMap<String, Symbol>,
+    names: Vec<String>,
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        return Interner::default();
+    }
+
+    /// Returns the symbol for `name`, adding it the first time it is seen.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+
+        let symbol = Symbol(self.names.len() as u32);
+        self.symbols.insert(name.to_string(), symbol);
+        self.names.push(name.to_string());
+        return symbol;
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        return &self.names[symbol.0 as usize];
+    }
+
+    pub fn len(&self) -> usize {
+        return self.names.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.names.is_empty();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() -> Result<(), String> {
+        let mut interner = Interner::new();
+
+        let a = interner.intern("a");
+        let b = interner.intern("b");
+        assert_ne!(a, b);
+        assert_eq!(interner.intern("a"), a);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.resolve(a), "a");
+        assert_eq!(interner.resolve(b), "b");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+use std::{collections::HashSet, ops::ControlFlow};
+
+use crate::{
+    chunk::Chunk, compiler, natives::Capabilities, optimizer, parser::Parser, pragma,
+    scanner::Scanner, token::Token, value::Value, vm::Limits, vm::Vm,
+};
+
+/// Runs udyr source on the bytecode VM. This is the entry point for
+/// programs that embed udyr rather than going through the CLI.
+///
+/// Interpreters and compiled chunks are `Send` and `Sync`. A threaded host
+/// can give each thread an interpreter of its own, and run a chunk it
+/// compiled once on all of them; one interpreter runs one program at a
+/// time.
+#[derive(Debug, Default)]
+pub struct Interpreter {
+    vm: Vm,
+    defines: HashSet<String>,
+    opt_level: u8,
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        return Interpreter::default();
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.vm.set_limits(limits);
+    }
+
+    /// Allows the natives that reach outside the program, like `exec`.
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.vm.set_capabilities(capabilities);
+    }
+
+    /// Names that are defined for `#if` pragmas.
+    pub fn set_defines(&mut self, defines: HashSet<String>) {
+        self.defines = defines;
+    }
+
+    /// 0 runs programs as written, 1 folds constant expressions first.
+    pub fn set_opt_level(&mut self, opt_level: u8) {
+        self.opt_level = opt_level;
+    }
+
+    /// Scans, parses, compiles and runs `source`, returning its value or
+    /// every error from the first stage that failed.
+    pub fn eval(&mut self, source: &str) -> Result<Value, Vec<String>> {
+        let tokens = Scanner::new(source).scan_tokens()?;
+        return self.eval_tokens(&tokens);
+    }
+
+    /// Like [`Interpreter::eval`], for source that is already scanned. The
+    /// tokens must end with EOF.
+    pub fn eval_tokens(&mut self, tokens: &[Token]) -> Result<Value, Vec<String>> {
+        let chunk = self.compile_tokens(tokens)?;
+        return self.run(&chunk);
+    }
+
+    /// Like [`Interpreter::eval`], calling `on_yield` after every `every`
+    /// instructions the program runs, so a GUI or server can handle its
+    /// events in between. Breaking from `on_yield` stops the program with
+    /// an "Execution cancelled." error.
+    pub fn eval_with_yield(
+        &mut self,
+        source: &str,
+        every: u64,
+        mut on_yield: impl FnMut() -> ControlFlow<()>,
+    ) -> Result<Value, Vec<String>> {
+        let chunk = self.compile(source)?;
+        return self
+            .vm
+            .interpret_yielding(&chunk, every, &mut on_yield)
+            .map_err(|error| vec![error]);
+    }
+
+    /// Compiles `source` with this interpreter's defines and optimization
+    /// level, for [`Interpreter::run`] to run any number of times.
+    pub fn compile(&self, source: &str) -> Result<Chunk, Vec<String>> {
+        let tokens = Scanner::new(source).scan_tokens()?;
+        return self.compile_tokens(&tokens);
+    }
+
+    fn compile_tokens(&self, tokens: &[Token]) -> Result<Chunk, Vec<String>> {
+        let tokens = pragma::preprocess(tokens, &self.defines)?;
+        let mut program = Parser::new(&tokens)
+            .parse()
+            .map_err(|error| vec![error.into()])?;
+        if self.opt_level > 0 {
+            program = optimizer::fold(program);
+        }
+        return compiler::compile(&program).map_err(|error| vec![error.into()]);
+    }
+
+    /// Runs a chunk from [`Interpreter::compile`], which may be another
+    /// interpreter's.
+    pub fn run(&mut self, chunk: &Chunk) -> Result<Value, Vec<String>> {
+        return self.vm.interpret(chunk).map_err(|error| vec![error]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval() -> Result<(), Vec<String>> {
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(interpreter.eval("1 + 2 * 3")?, Value::Int(7));
+        assert_eq!(
+            interpreter.eval("\"a\" #if B + \"b\" #end")?,
+            Value::from("a")
+        );
+
+        interpreter.set_defines(HashSet::from([String::from("B")]));
+        interpreter.set_opt_level(1);
+        assert_eq!(
+            interpreter.eval("\"a\" #if B + \"b\" #end")?,
+            Value::from("ab")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_threads() -> Result<(), Vec<String>> {
+        fn shareable<T: Send + Sync>() {}
+        shareable::<Interpreter>();
+        shareable::<Chunk>();
+        shareable::<Value>();
+
+        let chunk = Interpreter::new().compile("(\"a\" + \"b\", 1 + 2)")?;
+        let results = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| Interpreter::new().run(&chunk)))
+                .collect();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .collect::<Result<Vec<_>, _>>()
+        })?;
+        let expected = Value::from(vec![Value::from("ab"), Value::Int(3)]);
+        assert_eq!(results, vec![expected; 4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_with_yield() -> Result<(), Vec<String>> {
+        let mut interpreter = Interpreter::new();
+        // 100 constants, 99 additions and the return.
+        let source = vec!["1"; 100].join(" + ");
+
+        let mut yields = 0;
+        let value = interpreter.eval_with_yield(&source, 10, || {
+            yields += 1;
+            ControlFlow::Continue(())
+        })?;
+        assert_eq!(value, Value::Int(100));
+        assert_eq!(yields, 19);
+
+        let mut yields = 0;
+        let result = interpreter.eval_with_yield(&source, 50, || {
+            yields += 1;
+            match yields {
+                2 => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(()),
+            }
+        });
+        assert_eq!(
+            result.unwrap_err(),
+            vec![String::from("[line 1] Error: Execution cancelled.")]
+        );
+        assert_eq!(yields, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_errors() -> Result<(), Vec<String>> {
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(interpreter.eval("1 + € + €").unwrap_err().len(), 2);
+        assert_eq!(
+            interpreter.eval("1 +").unwrap_err(),
+            vec![String::from("[line 1] Error at end: Expected expression.")]
+        );
+        assert_eq!(
+            interpreter.eval("-nil").unwrap_err(),
+            vec![String::from("[line 1] Error: Operand must be a number.")]
+        );
+
+        Ok(())
+    }
+}
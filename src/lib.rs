@@ -0,0 +1,43 @@
+#![allow(clippy::needless_return, clippy::upper_case_acronyms)]
+
+pub mod analysis;
+pub mod ast;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod chunk;
+pub mod compiler;
+pub mod completion;
+pub mod debugger;
+pub mod diff;
+pub mod disassembler;
+pub mod doc;
+pub mod error;
+pub mod explorer;
+pub mod formatter;
+pub mod golden;
+pub mod highlight;
+pub mod incremental;
+pub mod interner;
+pub mod interpreter;
+pub mod lint;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+pub mod methods;
+pub mod natives;
+pub mod optimizer;
+pub mod parser;
+pub mod pragma;
+pub mod profiler;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod scanner;
+pub mod serialize;
+pub mod template;
+pub mod token;
+pub mod tracer;
+pub mod types;
+pub mod unparser;
+pub mod value;
+pub mod vm;
+#[cfg(feature = "wasm")]
+pub mod wasm;
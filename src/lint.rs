@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+use crate::error::{error, warning, Diagnostic};
+
+/// A kind of warning that can be switched on or off by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnreachableCode,
+}
+
+impl Lint {
+    pub const ALL: &'static [Lint] = &[Lint::UnreachableCode];
+
+    /// The name used on the command line, e.g. `--allow unreachable_code`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnreachableCode => "unreachable_code",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Lint::UnreachableCode => "code that can never run",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        return Lint::ALL.iter().copied().find(|lint| lint.name() == name);
+    }
+
+    fn default_level(self) -> Level {
+        match self {
+            Lint::UnreachableCode => Level::Warn,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Allow,
+    Warn,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub lint: Lint,
+    pub line: usize,
+    pub message: String,
+    /// The bytes of the source the warning is about, if it is given.
+    pub span: Option<Range<usize>>,
+}
+
+impl Warning {
+    pub fn new(lint: Lint, line: usize, message: &str) -> Warning {
+        return Warning {
+            lint,
+            line,
+            message: message.to_string(),
+            span: None,
+        };
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", warning(self.line, &self.message))
+    }
+}
+
+/// Which lints are reported, and whether warnings stop the program.
+#[derive(Debug, Clone, Default)]
+pub struct Lints {
+    levels: HashMap<Lint, Level>,
+    warnings_are_errors: bool,
+}
+
+impl Lints {
+    pub fn new() -> Lints {
+        return Lints::default();
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        return *self.levels.get(&lint).unwrap_or(&lint.default_level());
+    }
+
+    pub fn set_level(&mut self, lint: Lint, level: Level) {
+        self.levels.insert(lint, level);
+    }
+
+    /// Turns on every lint, including those that are off by default.
+    pub fn warn_all(&mut self) {
+        for lint in Lint::ALL {
+            self.set_level(*lint, Level::Warn);
+        }
+    }
+
+    pub fn set_warnings_are_errors(&mut self, warnings_are_errors: bool) {
+        self.warnings_are_errors = warnings_are_errors;
+    }
+
+    /// Drops the allowed warnings and formats the rest, as errors if
+    /// warnings are treated as errors.
+    pub fn apply(&self, warnings: Vec<Warning>) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+        let warnings = warnings
+            .into_iter()
+            .filter(|warning| self.level(warning.lint) == Level::Warn);
+        if self.warnings_are_errors {
+            let errors: Vec<Diagnostic> = warnings
+                .map(|warning| Diagnostic {
+                    message: error(warning.line, &warning.message),
+                    span: warning.span,
+                })
+                .collect();
+            if !errors.is_empty() {
+                return Err(errors);
+            }
+            return Ok(Vec::new());
+        }
+        return Ok(warnings
+            .map(|warning| Diagnostic {
+                message: warning.to_string(),
+                span: warning.span,
+            })
+            .collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_names() -> Result<(), String> {
+        for lint in Lint::ALL {
+            assert_eq!(Lint::from_name(lint.name()), Some(*lint));
+        }
+        assert_eq!(Lint::from_name("unused_everything"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_levels() -> Result<(), String> {
+        let warnings = vec![Warning::new(Lint::UnreachableCode, 3, "Unreachable code.")];
+        let mut lints = Lints::new();
+
+        assert_eq!(
+            lints.apply(warnings.clone()),
+            Ok(vec![Diagnostic::from(String::from(
+                "[line 3] Warning: Unreachable code."
+            ))])
+        );
+
+        lints.set_warnings_are_errors(true);
+        assert_eq!(
+            lints.apply(warnings.clone()),
+            Err(vec![Diagnostic::from(String::from(
+                "[line 3] Error: Unreachable code."
+            ))])
+        );
+
+        lints.set_level(Lint::UnreachableCode, Level::Allow);
+        assert_eq!(lints.apply(warnings.clone()), Ok(vec![]));
+
+        lints.warn_all();
+        assert!(lints.apply(warnings).is_err());
+
+        Ok(())
+    }
+}
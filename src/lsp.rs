@@ -0,0 +1,406 @@
+//! A language server speaking LSP over stdin and stdout, started with
+//! `udyr lsp`. Clients send the edits made to a document, which are
+//! applied to an [`incremental::Document`] so only what changed is scanned
+//! again.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value as Json};
+
+use crate::{
+    analysis, incremental::Document, parser::Parser, pragma, scanner::Scanner, token::TokenType,
+    types,
+};
+
+const ERROR: u8 = 1;
+const WARNING: u8 = 2;
+
+/// Handles requests from `input` until the client sends `exit` or closes
+/// the stream.
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut documents: HashMap<String, Document> = HashMap::new();
+
+    while let Some(message) = read_message(&mut input)? {
+        let Ok(message) = serde_json::from_slice::<Json>(&message) else {
+            let error = json!({ "code": -32700, "message": "Parse error" });
+            send(
+                &mut output,
+                json!({ "jsonrpc": "2.0", "id": null, "error": error }),
+            )?;
+            continue;
+        };
+        let id = message.get("id").cloned();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        match message["method"].as_str().unwrap_or_default() {
+            "initialize" => {
+                let capabilities = json!({
+                    "textDocumentSync": 2,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                });
+                let result =
+                    json!({ "capabilities": capabilities, "serverInfo": { "name": "udyr" } });
+                respond(&mut output, id, result)?;
+            }
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                let document = Document::new(text);
+                publish_diagnostics(&mut output, uri, &document)?;
+                documents.insert(uri.to_string(), document);
+            }
+            "textDocument/didChange" => {
+                let document = documents
+                    .entry(uri.to_string())
+                    .or_insert_with(|| Document::new(""));
+                let changes = params["contentChanges"].as_array();
+                for change in changes.into_iter().flatten() {
+                    let text = change["text"].as_str().unwrap_or_default();
+                    if change["range"].is_null() {
+                        *document = Document::new(text);
+                        continue;
+                    }
+                    let source = document.source();
+                    let start = offset_at_position(source, &change["range"]["start"]);
+                    let end = offset_at_position(source, &change["range"]["end"]);
+                    // Offsets from `offset_at` are always inside the text.
+                    let _ = document.edit(start..end.max(start), text);
+                }
+                publish_diagnostics(&mut output, uri, document)?;
+            }
+            "textDocument/didClose" => {
+                documents.remove(uri);
+                publish_diagnostics(&mut output, uri, &Document::new(""))?;
+            }
+            "textDocument/hover" => {
+                let text = documents.get(uri).map(Document::source).unwrap_or_default();
+                respond(&mut output, id, hover(text, &params["position"]))?;
+            }
+            // There are no declarations in the language yet.
+            "textDocument/documentSymbol" => respond(&mut output, id, json!([]))?,
+            "shutdown" => respond(&mut output, id, Json::Null)?,
+            "exit" => return Ok(()),
+            _ => {
+                if let Some(id) = id {
+                    let error = json!({ "code": -32601, "message": "Method not found" });
+                    send(
+                        &mut output,
+                        json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+                    )?;
+                }
+            }
+        }
+    }
+    return Ok(());
+}
+
+/// Reads the body of the next message, or `None` at the end of `input`.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Missing Content-Length header.",
+        ));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    return Ok(Some(body));
+}
+
+fn send(output: &mut impl Write, message: Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    return output.flush();
+}
+
+fn respond(output: &mut impl Write, id: Option<Json>, result: Json) -> io::Result<()> {
+    return send(
+        output,
+        json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+    );
+}
+
+fn publish_diagnostics(output: &mut impl Write, uri: &str, document: &Document) -> io::Result<()> {
+    let params = json!({ "uri": uri, "diagnostics": diagnostics(document) });
+    let notification = json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": params,
+    });
+    return send(output, notification);
+}
+
+/// Runs the rest of the front end over the tokens of `document` and turns
+/// its errors and warnings into LSP diagnostics covering the line they were
+/// reported on.
+fn diagnostics(document: &Document) -> Vec<Json> {
+    let errors = |messages: Vec<String>| {
+        return messages
+            .iter()
+            .map(|message| diagnostic(message, ERROR))
+            .collect();
+    };
+
+    let tokens = match document.tokens() {
+        Ok(tokens) => tokens,
+        Err(messages) => return errors(messages.to_vec()),
+    };
+    let tokens = match pragma::preprocess(tokens, &HashSet::new()) {
+        Ok(tokens) => tokens,
+        Err(messages) => return errors(messages),
+    };
+    let program = match Parser::new(&tokens).parse() {
+        Ok(program) => program,
+        Err(message) => return errors(vec![message.into()]),
+    };
+
+    let type_errors = types::check(&program).into_iter().map(String::from);
+    let mut diagnostics: Vec<Json> = errors(type_errors.collect());
+    for warning in analysis::unreachable_code(&program) {
+        diagnostics.push(diagnostic(&warning.to_string(), WARNING));
+    }
+    return diagnostics;
+}
+
+/// Builds a diagnostic from a message formatted like `[line 3] Error: ...`.
+fn diagnostic(message: &str, severity: u8) -> Json {
+    let (line, message) = message
+        .strip_prefix("[line ")
+        .and_then(|rest| rest.split_once("] "))
+        .and_then(|(line, message)| Some((line.parse::<usize>().ok()?, message)))
+        .unwrap_or((1, message));
+    let line = line.saturating_sub(1);
+    return json!({
+        "range": {
+            "start": { "line": line, "character": 0 },
+            "end": { "line": line + 1, "character": 0 },
+        },
+        "severity": severity,
+        "source": "udyr",
+        "message": message,
+    });
+}
+
+/// Describes the literal under the cursor, if there is one.
+fn hover(text: &str, position: &Json) -> Json {
+    let offset = offset_at_position(text, position);
+
+    let token = Scanner::new(text)
+        .flatten()
+        .find(|token| token.offset <= offset && offset < token.offset + token.lexeme.len());
+    let Some(token) = token else {
+        return Json::Null;
+    };
+    let contents = match token.token_type {
+        TokenType::NUMBER => format!("number `{}`", token.literal),
+        TokenType::STRING => format!("string `{:?}`", token.literal),
+        TokenType::TRUE | TokenType::FALSE => format!("bool `{}`", token.lexeme),
+        TokenType::NIL => String::from("nil"),
+        _ => return Json::Null,
+    };
+
+    let start = position_at(text, token.offset);
+    let end = position_at(text, token.offset + token.lexeme.len());
+    return json!({
+        "contents": { "kind": "markdown", "value": contents },
+        "range": { "start": start, "end": end },
+    });
+}
+
+fn offset_at_position(text: &str, position: &Json) -> usize {
+    let line = position["line"].as_u64().unwrap_or_default() as usize;
+    let character = position["character"].as_u64().unwrap_or_default() as usize;
+    return offset_at(text, line, character);
+}
+
+/// Converts an LSP position, counted in UTF-16 code units, to a byte offset.
+fn offset_at(text: &str, line: usize, character: usize) -> usize {
+    let mut offset = 0;
+    for (i, text_line) in text.split_inclusive('\n').enumerate() {
+        if i == line {
+            let mut units = 0;
+            for (index, c) in text_line.char_indices() {
+                if units >= character {
+                    return offset + index;
+                }
+                units += c.len_utf16();
+            }
+            return offset + text_line.len();
+        }
+        offset += text_line.len();
+    }
+    return text.len();
+}
+
+fn position_at(text: &str, offset: usize) -> Json {
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    return json!({ "line": line, "character": character });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(message: Json) -> String {
+        let body = message.to_string();
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    fn session(messages: Vec<Json>) -> Vec<Json> {
+        let input: String = messages.into_iter().map(frame).collect();
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output).unwrap();
+
+        let mut output = output.as_slice();
+        let mut replies = Vec::new();
+        while let Some(body) = read_message(&mut output).unwrap() {
+            replies.push(serde_json::from_slice(&body).unwrap());
+        }
+        replies
+    }
+
+    fn open(text: &str) -> Json {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": "file:///a.udyr", "text": text } },
+        })
+    }
+
+    #[test]
+    fn test_initialize_and_shutdown() -> Result<(), String> {
+        let replies = session(vec![
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "workspace/symbol", "params": {} }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+            json!({ "jsonrpc": "2.0", "id": 4, "method": "shutdown" }),
+        ]);
+
+        assert_eq!(replies.len(), 3);
+        assert_eq!(replies[0]["result"]["capabilities"]["hoverProvider"], true);
+        assert_eq!(replies[1]["error"]["code"], -32601);
+        assert_eq!(replies[2]["id"], 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagnostics() -> Result<(), String> {
+        let replies = session(vec![open("1 +\n(\"a\" - 2)"), open("true ? 1 :\n2")]);
+
+        let diagnostics = &replies[0]["params"]["diagnostics"];
+        assert_eq!(
+            diagnostics[0]["message"],
+            "Error at '-': Operands must be numbers."
+        );
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+        assert_eq!(diagnostics[0]["severity"], ERROR);
+
+        let diagnostics = &replies[1]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["message"], "Warning: Unreachable code.");
+        assert_eq!(diagnostics[0]["severity"], WARNING);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_incremental_changes() -> Result<(), String> {
+        let change = |changes: Json| {
+            json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didChange",
+                "params": {
+                    "textDocument": { "uri": "file:///a.udyr" },
+                    "contentChanges": changes,
+                },
+            })
+        };
+        let range = |start: (u32, u32), end: (u32, u32)| {
+            json!({
+                "start": { "line": start.0, "character": start.1 },
+                "end": { "line": end.0, "character": end.1 },
+            })
+        };
+        let replies = session(vec![
+            open("1 -\n2"),
+            change(json!([
+                { "range": range((1, 0), (1, 1)), "text": "\"a\"" },
+                { "range": range((0, 0), (0, 0)), "text": "\n" },
+            ])),
+            change(json!([{ "text": "1" }])),
+        ]);
+
+        assert_eq!(replies[0]["params"]["diagnostics"], json!([]));
+        let diagnostics = &replies[1]["params"]["diagnostics"];
+        assert_eq!(
+            diagnostics[0]["message"],
+            "Error at '-': Operands must be numbers."
+        );
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+        assert_eq!(replies[2]["params"]["diagnostics"], json!([]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hover() -> Result<(), String> {
+        let hover_at = |line: usize, character: usize| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "textDocument/hover",
+                "params": {
+                    "textDocument": { "uri": "file:///a.udyr" },
+                    "position": { "line": line, "character": character },
+                },
+            })
+        };
+        let replies = session(vec![
+            open("\"😀\" +\n  0x1F"),
+            hover_at(1, 3),
+            hover_at(0, 4),
+        ]);
+
+        assert_eq!(replies[1]["result"]["contents"]["value"], "number `31`");
+        assert_eq!(
+            replies[1]["result"]["range"],
+            json!({ "start": { "line": 1, "character": 2 }, "end": { "line": 1, "character": 6 } })
+        );
+        assert_eq!(replies[2]["result"], Json::Null);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_positions() -> Result<(), String> {
+        let text = "a😀b\ncd";
+
+        assert_eq!(offset_at(text, 0, 3), 5);
+        assert_eq!(offset_at(text, 1, 1), 8);
+        assert_eq!(offset_at(text, 1, 9), text.len());
+        assert_eq!(position_at(text, 5), json!({ "line": 0, "character": 3 }));
+
+        Ok(())
+    }
+}
@@ -1,56 +1,46 @@
 use std::env;
-use std::io;
-use std::io::Write;
-
-mod error;
-mod node;
-mod parser;
-mod scanner;
-mod token;
-
-#[derive(Debug, Clone)]
-struct RunError {
-    messages: Vec<String>,
-}
-
-fn run(source: &String) -> Result<(), RunError> {
-    let mut scanner = scanner::Scanner::new(source);
-    let tokens = scanner.scan_tokens().unwrap();
-    let mut parser = parser::Parser::new(&tokens);
-
-    let program = parser.parse();
-    program.print();
-
-    Ok(())
-}
-
-fn run_file(path: &String) {
-    let source = std::fs::read_to_string(path).unwrap();
-    run(&source);
-}
-
-fn run_prompt() {
-    let mut line = String::new();
-    loop {
-        print!("> ");
-        io::stdout().flush().unwrap();
-        let bytes_read = std::io::stdin().read_line(&mut line).unwrap();
-        if bytes_read == 1 && line == "\n" {
-            break;
-        }
-        run(&line);
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+mod app;
+
+use app::application::{Application, FrontEnd};
+use app::config;
+use app::options::Options;
+
+/// Where to look for a `udyr.toml`: the directory of the script the front
+/// end works on, or the current directory.
+fn config_directory(front_end: &FrontEnd) -> PathBuf {
+    let current = env::current_dir().unwrap_or_default();
+    let Some(path) = front_end
+        .path()
+        .and_then(|path| Path::new(path).canonicalize().ok())
+    else {
+        return current;
+    };
+    if path.is_dir() {
+        return path;
     }
+    path.parent().map(Path::to_path_buf).unwrap_or(current)
 }
 
-fn main() {
+fn main() -> ExitCode {
     env::set_var("RUST_BACKTRACE", "1");
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() > 2 {
-        println!("Usage: udyr [script]")
-    } else if args.len() == 2 {
-        run_file(&args[1]);
-    } else {
-        run_prompt();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (options, rest) = Options::from_args(args.clone());
+    let front_end = FrontEnd::from_args(&rest, &options);
+
+    // Flags override the configuration, so they are read again on top of it.
+    let mut options = Options::default();
+    if let Some(path) = config::find(&config_directory(&front_end)) {
+        if let Err(message) = config::load(&path, &mut options) {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
     }
+    let (options, _) = options.with_args(args);
+
+    let mut application = Application::new(options, front_end);
+    application.run();
+    application.destroy()
 }
@@ -0,0 +1,247 @@
+//! The properties and methods of built-in values, like `"abc".length` and
+//! `" a ".trim()`. Strings count and index by character, not by byte.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::value::Value;
+
+/// The properties and methods some built-in value has, for completion.
+pub const PROPERTIES: &[&str] = &["length"];
+pub const METHODS: &[&str] = &["format", "indexOf", "replace", "slice", "trim"];
+
+/// Reads the property `name` of `receiver`.
+pub fn property(receiver: &Value, name: &str) -> Result<Value, String> {
+    match (receiver, name) {
+        (Value::String(string), "length") => return Ok(Value::Int(string.chars().count() as i64)),
+        (Value::Tuple(elements), "length") => return Ok(Value::Int(elements.len() as i64)),
+        _ => return Err(format!("Undefined property '{}'.", name)),
+    }
+}
+
+/// Calls the method `name` of `receiver` with `arguments`.
+pub fn invoke(receiver: &Value, name: &str, arguments: &[Value]) -> Result<Value, String> {
+    let Value::String(string) = receiver else {
+        return Err(format!("Undefined method '{}'.", name));
+    };
+    match name {
+        "slice" => {
+            let [start, end] = arity(arguments)?;
+            let (start, end) = (int(start)?, int(end)?);
+            let length = string.chars().count() as i64;
+            if start < 0 || start > end || end > length {
+                return Err(String::from("Slice out of range."));
+            }
+            let slice = string.chars().skip(start as usize);
+            return Ok(Value::from(
+                slice.take((end - start) as usize).collect::<String>(),
+            ));
+        }
+        "indexOf" => {
+            let [needle] = arity(arguments)?;
+            let index = string
+                .find(text(needle)?)
+                .map_or(-1, |offset| string[..offset].chars().count() as i64);
+            return Ok(Value::Int(index));
+        }
+        "replace" => {
+            let [from, to] = arity(arguments)?;
+            return Ok(Value::from(string.replace(text(from)?, text(to)?)));
+        }
+        "trim" => {
+            let [] = arity(arguments)?;
+            return Ok(Value::from(string.trim()));
+        }
+        "format" => return Ok(Value::from(format(string, arguments)?)),
+        _ => return Err(format!("Undefined method '{}'.", name)),
+    }
+}
+
+/// Fills the placeholders in `template` with `arguments`, written the way
+/// they print. `{}` takes the next argument and `{1}` a given one, and
+/// `{:.2}` writes a number with two decimals. `{{` and `}}` are braces.
+pub fn format(template: &str, arguments: &[Value]) -> Result<String, String> {
+    let mut output = String::new();
+    let mut next = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.next_if_eq(&'{').is_some() => output.push('{'),
+            '}' if chars.next_if_eq(&'}').is_some() => output.push('}'),
+            '{' => {
+                let placeholder = placeholder(&mut chars)?;
+                let (index, precision) = placeholder.split_once(':').unwrap_or((&placeholder, ""));
+                let index = match index {
+                    "" => {
+                        next += 1;
+                        next - 1
+                    }
+                    _ => index.parse().map_err(|_| invalid(&placeholder))?,
+                };
+                let Some(value) = arguments.get(index) else {
+                    return Err(format!("No argument {} for the format string.", index));
+                };
+                if precision.is_empty() {
+                    output.push_str(&value.to_string());
+                    continue;
+                }
+                let precision: usize = precision
+                    .strip_prefix('.')
+                    .and_then(|precision| precision.parse().ok())
+                    .ok_or_else(|| invalid(&placeholder))?;
+                let Some(number) = value.as_float() else {
+                    return Err(String::from("Only numbers can have a precision."));
+                };
+                output.push_str(&format!("{:.*}", precision, number));
+            }
+            '}' => return Err(String::from("Unmatched '}' in the format string.")),
+            c => output.push(c),
+        }
+    }
+    return Ok(output);
+}
+
+/// Reads what is between `{` and `}`.
+fn placeholder(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut placeholder = String::new();
+    for c in chars.by_ref() {
+        if c == '}' {
+            return Ok(placeholder);
+        }
+        placeholder.push(c);
+    }
+    return Err(String::from("Unclosed '{' in the format string."));
+}
+
+fn invalid(placeholder: &str) -> String {
+    return format!("Invalid placeholder '{{{}}}'.", placeholder);
+}
+
+pub(crate) fn arity<const N: usize>(arguments: &[Value]) -> Result<&[Value; N], String> {
+    return arguments
+        .try_into()
+        .map_err(|_| format!("Expected {} arguments but got {}.", N, arguments.len()));
+}
+
+pub(crate) fn int(value: &Value) -> Result<i64, String> {
+    match value {
+        Value::Int(value) => return Ok(*value),
+        _ => return Err(String::from("Argument must be an int.")),
+    }
+}
+
+pub(crate) fn text(value: &Value) -> Result<&str, String> {
+    match value {
+        Value::String(value) => return Ok(value),
+        _ => return Err(String::from("Argument must be a string.")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> Value {
+        Value::from(value)
+    }
+
+    #[test]
+    fn test_properties() -> Result<(), String> {
+        assert_eq!(property(&string("høst"), "length")?, Value::Int(4));
+        assert_eq!(
+            property(&Value::Tuple(vec![Value::Nil].into()), "length")?,
+            Value::Int(1)
+        );
+        assert_eq!(
+            property(&Value::Int(1), "length").unwrap_err(),
+            "Undefined property 'length'."
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_methods() -> Result<(), String> {
+        let hello = string(" høst ");
+
+        assert_eq!(invoke(&hello, "trim", &[])?, string("høst"));
+        assert_eq!(
+            invoke(&hello, "slice", &[Value::Int(2), Value::Int(4)])?,
+            string("øs")
+        );
+        assert_eq!(invoke(&hello, "indexOf", &[string("s")])?, Value::Int(3));
+        assert_eq!(invoke(&hello, "indexOf", &[string("x")])?, Value::Int(-1));
+        assert_eq!(
+            invoke(&hello, "replace", &[string(" "), string("_")])?,
+            string("_høst_")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format() -> Result<(), String> {
+        let arguments = [Value::Int(1), Value::Number(2.0 / 3.0), string("a")];
+
+        assert_eq!(
+            format("x = {}, y = {:.2}, {}!", &arguments)?,
+            "x = 1, y = 0.67, a!"
+        );
+        assert_eq!(format("{2}{0}{0:.1} {{}}", &arguments)?, "a11.0 {}");
+        assert_eq!(
+            invoke(
+                &string("{} + {}"),
+                "format",
+                &[Value::Nil, Value::Bool(true)]
+            )?,
+            string("nil + true")
+        );
+
+        for (template, message) in [
+            ("{3}", "No argument 3 for the format string."),
+            ("{}{}{}{}", "No argument 3 for the format string."),
+            ("{:2}", "Invalid placeholder '{:2}'."),
+            ("{2:.1}", "Only numbers can have a precision."),
+            ("{x}", "Invalid placeholder '{x}'."),
+            ("{0:2}", "Invalid placeholder '{0:2}'."),
+            ("{", "Unclosed '{' in the format string."),
+            ("}", "Unmatched '}' in the format string."),
+        ] {
+            assert_eq!(format(template, &arguments).unwrap_err(), message);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_method_errors() -> Result<(), String> {
+        let hello = string("hello");
+
+        assert_eq!(
+            invoke(&hello, "slice", &[Value::Int(3), Value::Int(2)]).unwrap_err(),
+            "Slice out of range."
+        );
+        assert_eq!(
+            invoke(&hello, "slice", &[Value::Int(0)]).unwrap_err(),
+            "Expected 2 arguments but got 1."
+        );
+        assert_eq!(
+            invoke(&hello, "indexOf", &[Value::Int(0)]).unwrap_err(),
+            "Argument must be a string."
+        );
+        assert_eq!(
+            invoke(&hello, "slice", &[string("0"), Value::Int(1)]).unwrap_err(),
+            "Argument must be an int."
+        );
+        assert_eq!(
+            invoke(&hello, "upper", &[]).unwrap_err(),
+            "Undefined method 'upper'."
+        );
+        assert_eq!(
+            invoke(&Value::Nil, "trim", &[]).unwrap_err(),
+            "Undefined method 'trim'."
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,574 @@
+//! The functions built into the language, like `clock()`. They can only be
+//! called, not passed around, until the language has function values.
+
+use std::env;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    methods::{arity, int, text},
+    value::Value,
+    vm::Limits,
+};
+
+/// Every native, with namespaced ones like `log.info` written out.
+pub const NAMES: &[&str] = &[
+    "args",
+    "assert",
+    "assertEquals",
+    "clock",
+    "env",
+    "exec",
+    "exit",
+    "fetch",
+    "formatTime",
+    "log.debug",
+    "log.error",
+    "log.info",
+    "log.warn",
+    "now",
+    "sleep",
+];
+
+/// What natives that reach outside the program may do. Everything is
+/// denied unless the program is run with it allowed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capabilities {
+    /// Run other programs with `exec`.
+    pub exec: bool,
+    /// Make HTTP requests with `fetch`, if built with the `net` feature.
+    pub net: bool,
+}
+
+/// How important a message from `log.info(...)` and friends is. Messages
+/// below the level the program runs with are not written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LogLevel {
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub const ALL: &'static [LogLevel] = &[
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Warn,
+        LogLevel::Error,
+    ];
+
+    /// The name used on the command line, e.g. `--log-level warn`, and
+    /// after `log.` in scripts.
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        return LogLevel::ALL
+            .iter()
+            .copied()
+            .find(|level| level.name() == name);
+    }
+}
+
+/// What natives may know about the program calling them.
+pub struct Context<'a> {
+    pub limits: &'a Limits,
+    pub capabilities: &'a Capabilities,
+    /// When the program started running, if it runs with a timeout or
+    /// calls `clock()`.
+    pub started: Option<Instant>,
+    /// The arguments given to the script after its name.
+    pub arguments: &'a [String],
+    /// Set by `exit(code)` to stop the program.
+    pub exit_code: Option<u8>,
+    /// The line of the call.
+    pub line: usize,
+    pub log_level: LogLevel,
+}
+
+impl Context<'_> {
+    /// How long the program has been running, or zero if its start was
+    /// not timed.
+    fn elapsed(&self) -> Duration {
+        return self
+            .started
+            .map_or(Duration::ZERO, |started| started.elapsed());
+    }
+}
+
+pub fn is_native(name: &str) -> bool {
+    return NAMES.contains(&name);
+}
+
+/// Whether `name` is a namespace of natives, like `log`.
+pub fn is_namespace(name: &str) -> bool {
+    return NAMES.iter().any(|native| {
+        native
+            .split_once('.')
+            .is_some_and(|(namespace, _)| namespace == name)
+    });
+}
+
+/// Calls the native `name` with `arguments`.
+pub fn call(name: &str, arguments: &[Value], context: &mut Context) -> Result<Value, String> {
+    match name {
+        "args" => {
+            let [] = arity(arguments)?;
+            let arguments = context.arguments.iter().cloned().map(Value::from);
+            return Ok(Value::from(arguments.collect::<Vec<_>>()));
+        }
+        "assert" => {
+            let [condition, message] = arity(arguments)?;
+            if !condition.is_truthy() {
+                return Err(format!("Assertion failed: {}", message));
+            }
+            return Ok(Value::Nil);
+        }
+        "assertEquals" => {
+            let [actual, expected] = arity(arguments)?;
+            if !actual.equals(expected) {
+                return Err(format!(
+                    "Assertion failed: expected {} but got {}.",
+                    quoted(expected),
+                    quoted(actual)
+                ));
+            }
+            return Ok(Value::Nil);
+        }
+        "env" => {
+            let [name] = arity(arguments)?;
+            return Ok(env::var(text(name)?).map_or(Value::Nil, Value::from));
+        }
+        "exec" => {
+            let [command, command_arguments] = arity(arguments)?;
+            if !context.capabilities.exec {
+                return Err(String::from("Running commands is not allowed."));
+            }
+            return exec(text(command)?, command_arguments);
+        }
+        "fetch" => {
+            let [url] = arity(arguments)?;
+            if !context.capabilities.net {
+                return Err(String::from("Network access is not allowed."));
+            }
+            return fetch(text(url)?, context);
+        }
+        "exit" => {
+            let [code] = arity(arguments)?;
+            let code = u8::try_from(int(code)?)
+                .map_err(|_| String::from("Exit code must be between 0 and 255."))?;
+            context.exit_code = Some(code);
+            return Ok(Value::Nil);
+        }
+        "clock" => {
+            let [] = arity(arguments)?;
+            return Ok(Value::Number(context.elapsed().as_secs_f64()));
+        }
+        "now" => {
+            let [] = arity(arguments)?;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            return Ok(Value::Int(now.as_millis() as i64));
+        }
+        "sleep" => {
+            let [milliseconds] = arity(arguments)?;
+            let milliseconds = u64::try_from(int(milliseconds)?)
+                .map_err(|_| String::from("Cannot sleep for a negative time."))?;
+            return sleep(Duration::from_millis(milliseconds), context);
+        }
+        "formatTime" => {
+            let [timestamp, format] = arity(arguments)?;
+            return Ok(Value::from(format_time(int(timestamp)?, text(format)?)?));
+        }
+        _ => {}
+    }
+    if let Some(level) = name.strip_prefix("log.").and_then(LogLevel::from_name) {
+        let [message] = arity(arguments)?;
+        if level >= context.log_level {
+            eprintln!("{}", log_message(level, context.line, message));
+        }
+        return Ok(Value::Nil);
+    }
+    return Err(format!("Undefined function '{}'.", name));
+}
+
+/// A line of the log: when, how important, where and what.
+fn log_message(level: LogLevel, line: usize, message: &Value) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let time = format_time(now.as_millis() as i64, "%Y-%m-%d %H:%M:%S").unwrap();
+    let level = level.name().to_uppercase();
+    return format!("{} {:<5} [line {}] {}", time, level, line, message);
+}
+
+/// Writes `value` the way it prints inside a tuple, with strings quoted.
+fn quoted(value: &Value) -> String {
+    match value {
+        Value::String(string) => return format!("{:?}", string),
+        value => return value.to_string(),
+    }
+}
+
+/// Runs `command` with `arguments`, a tuple of strings, and returns its
+/// exit status, stdout and stderr. The status is nil if a signal killed it.
+fn exec(command: &str, arguments: &Value) -> Result<Value, String> {
+    let Value::Tuple(arguments) = arguments else {
+        return Err(String::from("Arguments must be a tuple of strings."));
+    };
+    let arguments = arguments
+        .iter()
+        .map(text)
+        .collect::<Result<Vec<&str>, String>>()?;
+    let output = Command::new(command)
+        .args(arguments)
+        .output()
+        .map_err(|err| format!("Could not run '{}': {}.", command, err))?;
+    let status = output
+        .status
+        .code()
+        .map_or(Value::Nil, |code| Value::Int(code as i64));
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    return Ok(Value::Tuple(
+        vec![status, Value::from(stdout), Value::from(stderr)].into(),
+    ));
+}
+
+/// Gets `url` and returns the status, the headers as a tuple of name and
+/// value pairs, and the body. Responses with error statuses are returned
+/// like any other; only failing to get one is an error.
+#[cfg(feature = "net")]
+fn fetch(url: &str, context: &Context) -> Result<Value, String> {
+    let mut request = ureq::get(url);
+    if let Some(timeout) = context.limits.timeout {
+        request = request.timeout(timeout.saturating_sub(context.elapsed()));
+    }
+    let response = match request.call() {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+        Err(err) => return Err(format!("Could not fetch {}.", err)),
+    };
+    let status = Value::Int(response.status() as i64);
+    let headers = response
+        .headers_names()
+        .into_iter()
+        .filter_map(|name| {
+            let value = response.header(&name)?.to_string();
+            return Some(Value::from(vec![Value::from(name), Value::from(value)]));
+        })
+        .collect::<Vec<_>>();
+    let body = response
+        .into_string()
+        .map_err(|err| format!("Could not read the body of '{}': {}.", url, err))?;
+    return Ok(Value::Tuple(
+        vec![status, Value::from(headers), Value::from(body)].into(),
+    ));
+}
+
+#[cfg(not(feature = "net"))]
+fn fetch(_url: &str, _context: &Context) -> Result<Value, String> {
+    return Err(String::from("udyr was built without the net feature."));
+}
+
+/// Sleeps, but only until the program runs out of time.
+fn sleep(duration: Duration, context: &Context) -> Result<Value, String> {
+    let Some(timeout) = context.limits.timeout else {
+        thread::sleep(duration);
+        return Ok(Value::Nil);
+    };
+    let remaining = timeout.saturating_sub(context.elapsed());
+    if duration < remaining {
+        thread::sleep(duration);
+        return Ok(Value::Nil);
+    }
+    thread::sleep(remaining);
+    return Err(format!(
+        "Execution limit exceeded: ran for more than {:?}.",
+        timeout
+    ));
+}
+
+/// Writes a timestamp in milliseconds since the Unix epoch as UTC, with
+/// `%Y`, `%m`, `%d`, `%H`, `%M`, `%S` and `%%` in `format` replaced like
+/// `strftime` does.
+pub fn format_time(timestamp: i64, format: &str) -> Result<String, String> {
+    let seconds = timestamp.div_euclid(1000);
+    let (year, month, day) = civil_date(seconds.div_euclid(86400));
+    let time = seconds.rem_euclid(86400);
+
+    let mut output = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => output.push_str(&format!("{:04}", year)),
+            Some('m') => output.push_str(&format!("{:02}", month)),
+            Some('d') => output.push_str(&format!("{:02}", day)),
+            Some('H') => output.push_str(&format!("{:02}", time / 3600)),
+            Some('M') => output.push_str(&format!("{:02}", time / 60 % 60)),
+            Some('S') => output.push_str(&format!("{:02}", time % 60)),
+            Some('%') => output.push('%'),
+            Some(c) => return Err(format!("Unknown time format '%{}'.", c)),
+            None => return Err(String::from("Unknown time format '%'.")),
+        }
+    }
+    return Ok(output);
+}
+
+/// The year, month and day `days` after 1970-01-01, from Howard Hinnant's
+/// `civil_from_days`.
+fn civil_date(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    return (year, month, day);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DENIED: Capabilities = Capabilities {
+        exec: false,
+        net: false,
+    };
+
+    fn context<'a>(limits: &'a Limits, arguments: &'a [String]) -> Context<'a> {
+        Context {
+            limits,
+            capabilities: &DENIED,
+            started: Some(Instant::now()),
+            arguments,
+            exit_code: None,
+            line: 1,
+            log_level: LogLevel::default(),
+        }
+    }
+
+    #[test]
+    fn test_format_time() -> Result<(), String> {
+        let format = "%Y-%m-%d %H:%M:%S";
+
+        assert_eq!(format_time(0, format)?, "1970-01-01 00:00:00");
+        assert_eq!(format_time(951_868_799_999, format)?, "2000-02-29 23:59:59");
+        assert_eq!(format_time(-1, "%Y %%")?, "1969 %");
+        assert_eq!(
+            format_time(0, "%j").unwrap_err(),
+            "Unknown time format '%j'."
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_natives() -> Result<(), String> {
+        let limits = Limits::default();
+        let mut context = context(&limits, &[]);
+
+        assert!(matches!(call("now", &[], &mut context)?, Value::Int(now) if now > 0));
+        assert!(matches!(
+            call("clock", &[], &mut context)?,
+            Value::Number(_)
+        ));
+        assert_eq!(call("sleep", &[Value::Int(1)], &mut context)?, Value::Nil);
+        assert_eq!(
+            call("sleep", &[Value::Int(-1)], &mut context).unwrap_err(),
+            "Cannot sleep for a negative time."
+        );
+        assert_eq!(
+            call("now", &[Value::Nil], &mut context).unwrap_err(),
+            "Expected 0 arguments but got 1."
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_assertions() -> Result<(), String> {
+        let limits = Limits::default();
+        let mut context = context(&limits, &[]);
+        let message = Value::from("x is positive");
+
+        assert_eq!(
+            call("assert", &[Value::Int(1), message.clone()], &mut context)?,
+            Value::Nil
+        );
+        assert_eq!(
+            call("assert", &[Value::Nil, message], &mut context).unwrap_err(),
+            "Assertion failed: x is positive"
+        );
+        assert_eq!(
+            call(
+                "assertEquals",
+                &[Value::Int(1), Value::Number(1.0)],
+                &mut context
+            )?,
+            Value::Nil
+        );
+        assert_eq!(
+            call(
+                "assertEquals",
+                &[Value::from("1"), Value::Int(1)],
+                &mut context
+            )
+            .unwrap_err(),
+            "Assertion failed: expected 1 but got \"1\"."
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_log() -> Result<(), String> {
+        let limits = Limits::default();
+        let mut context = context(&limits, &[]);
+
+        let message = log_message(LogLevel::Warn, 3, &Value::Int(1));
+        assert!(message.ends_with(" WARN  [line 3] 1"), "{}", message);
+        assert_eq!(message.len(), "1970-01-01 00:00:00 WARN  [line 3] 1".len());
+
+        assert_eq!(call("log.info", &[Value::Nil], &mut context)?, Value::Nil);
+        assert_eq!(
+            call("log.trace", &[Value::Nil], &mut context).unwrap_err(),
+            "Undefined function 'log.trace'."
+        );
+        assert!(LogLevel::Debug < LogLevel::from_name("error").unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_natives() -> Result<(), String> {
+        let limits = Limits::default();
+        let arguments = [String::from("-v"), String::from("a.txt")];
+        let mut context = context(&limits, &arguments);
+
+        assert_eq!(
+            call("args", &[], &mut context)?.to_string(),
+            r#"("-v", "a.txt")"#
+        );
+        assert_eq!(
+            call("env", &[Value::from("UDYR_UNSET")], &mut context)?,
+            Value::Nil
+        );
+        assert_eq!(call("exit", &[Value::Int(3)], &mut context)?, Value::Nil);
+        assert_eq!(context.exit_code, Some(3));
+        assert_eq!(
+            call("exit", &[Value::Int(256)], &mut context).unwrap_err(),
+            "Exit code must be between 0 and 255."
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exec() -> Result<(), String> {
+        let limits = Limits::default();
+        let mut context = context(&limits, &[]);
+        let arguments = [
+            Value::from("sh"),
+            Value::Tuple(
+                vec![
+                    Value::from("-c"),
+                    Value::from("echo hi; echo no >&2; exit 3"),
+                ]
+                .into(),
+            ),
+        ];
+
+        assert_eq!(
+            call("exec", &arguments, &mut context).unwrap_err(),
+            "Running commands is not allowed."
+        );
+
+        context.capabilities = &Capabilities {
+            exec: true,
+            ..DENIED
+        };
+        assert_eq!(
+            call("exec", &arguments, &mut context)?.to_string(),
+            r#"(3, "hi\n", "no\n")"#
+        );
+        assert_eq!(
+            call("exec", &[arguments[0].clone(), Value::Nil], &mut context).unwrap_err(),
+            "Arguments must be a tuple of strings."
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn test_fetch() -> Result<(), String> {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").map_err(|err| err.to_string())?;
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 1024]).unwrap();
+            let response = "HTTP/1.1 404 Not Found\r\nX-A: b\r\nContent-Length: 2\r\n\r\nno";
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        let limits = Limits::default();
+        let mut context = context(&limits, &[]);
+        let url = [Value::from(url)];
+
+        assert_eq!(
+            call("fetch", &url, &mut context).unwrap_err(),
+            "Network access is not allowed."
+        );
+        context.capabilities = &Capabilities {
+            net: true,
+            ..DENIED
+        };
+        let Value::Tuple(response) = call("fetch", &url, &mut context)? else {
+            panic!("fetch should return a tuple");
+        };
+        server.join().unwrap();
+
+        assert_eq!(response[0], Value::Int(404));
+        assert!(response[1].to_string().contains(r#"("x-a", "b")"#));
+        assert_eq!(response[2].to_string(), "no");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sleep_respects_timeout() -> Result<(), String> {
+        let limits = Limits {
+            timeout: Some(Duration::from_millis(10)),
+            ..Limits::default()
+        };
+        let started = Instant::now();
+
+        assert_eq!(
+            call("sleep", &[Value::Int(60_000)], &mut context(&limits, &[])).unwrap_err(),
+            "Execution limit exceeded: ran for more than 10ms."
+        );
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        Ok(())
+    }
+}
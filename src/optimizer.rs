@@ -0,0 +1,245 @@
+use crate::{
+    ast::{Ast, Expr, ExprId, Program, Stmt},
+    token::{Token, TokenType},
+    value::{Arithmetic, Value},
+};
+
+/// Folds constant subexpressions like `2 * 60 * 60` or `!true` into
+/// literals. Anything that could fail or behave differently at runtime,
+/// like `"a" - 1` or `1 / 0`, is left for the runtime.
+pub fn fold(mut program: Program) -> Program {
+    for stmt in &program.statements {
+        match stmt {
+            Stmt::Expression(expr) => fold_expr(&mut program.ast, *expr),
+        }
+    }
+    return program;
+}
+
+/// Folds the expression `id` and everything in it, in place. A folded
+/// expression keeps its id, so what refers to it sees the folded one.
+pub fn fold_expr(ast: &mut Ast, id: ExprId) {
+    for child in ast[id].children() {
+        fold_expr(ast, child);
+    }
+    if let Some(expr) = folded(ast, id) {
+        ast[id] = expr;
+    }
+}
+
+/// What the expression `id`, with everything in it already folded, folds
+/// into, if it changes.
+fn folded(ast: &mut Ast, id: ExprId) -> Option<Expr> {
+    match &ast[id] {
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        } => {
+            if let (Some(a), Some(b)) = (constant(&ast[*left]), constant(&ast[*right])) {
+                if let Some(value) = binary(operator, a, b) {
+                    return Some(literal(value, operator.line));
+                }
+            }
+            // `(x + "a") + "b"` is `x + "ab"`: the same string if `x` is a
+            // string, and otherwise the same error at the first `+`.
+            let (
+                TokenType::Plus,
+                Expr::Binary {
+                    left: inner,
+                    operator: inner_operator,
+                    right: inner_right,
+                },
+                Some(Value::String(b)),
+            ) = (&operator.token_type, &ast[*left], constant(&ast[*right]))
+            else {
+                return None;
+            };
+            let (TokenType::Plus, Some(Value::String(a))) =
+                (&inner_operator.token_type, constant(&ast[*inner_right]))
+            else {
+                return None;
+            };
+            let (left, operator) = (*inner, inner_operator.clone());
+            let right = ast.add(literal(Value::from(format!("{}{}", a, b)), operator.line));
+            return Some(Expr::Binary {
+                left,
+                operator,
+                right,
+            });
+        }
+        Expr::Conditional {
+            condition,
+            then_branch,
+            else_branch,
+        } => match constant(&ast[*condition]) {
+            Some(value) if value.is_truthy() => return Some(ast[*then_branch].clone()),
+            Some(_) => return Some(ast[*else_branch].clone()),
+            None => return None,
+        },
+        Expr::Grouping(expr) => match &ast[*expr] {
+            Expr::Literal(_) => return Some(ast[*expr].clone()),
+            _ => return None,
+        },
+        Expr::Unary { operator, right } => {
+            let value = match (&operator.token_type, constant(&ast[*right])) {
+                (TokenType::BANG, Some(value)) => Some(Value::Bool(!value.is_truthy())),
+                (TokenType::Minus, Some(value)) => value.negate().ok(),
+                _ => None,
+            };
+            return value.map(|value| literal(value, operator.line));
+        }
+        Expr::Call { .. }
+        | Expr::Get { .. }
+        | Expr::Index { .. }
+        | Expr::Literal(_)
+        | Expr::Match { .. }
+        | Expr::Tuple { .. }
+        | Expr::Variable(_) => return None,
+    }
+}
+
+fn binary(operator: &Token, left: Value, right: Value) -> Option<Value> {
+    let arithmetic = |op| Value::arithmetic(op, &left, &right).ok();
+    let order = || Value::compare(&left, &right).ok();
+    let value = match (&operator.token_type, &left, &right) {
+        (TokenType::QuestionQuestion, Value::Nil, _) => right,
+        (TokenType::QuestionQuestion, _, _) => left,
+        (TokenType::EqualEqual, a, b) => Value::Bool(a.equals(b)),
+        (TokenType::BangEqual, a, b) => Value::Bool(!a.equals(b)),
+        (TokenType::Plus, Value::String(a), Value::String(b)) => Value::from(format!("{}{}", a, b)),
+        (TokenType::Plus, _, _) => arithmetic(Arithmetic::Add)?,
+        (TokenType::Minus, _, _) => arithmetic(Arithmetic::Subtract)?,
+        (TokenType::STAR, _, _) => arithmetic(Arithmetic::Multiply)?,
+        (TokenType::SLASH, _, _) => arithmetic(Arithmetic::Divide)?,
+        (TokenType::GREATER, _, _) => Value::Bool(order()?.is_some_and(|o| o.is_gt())),
+        (TokenType::GreaterEqual, _, _) => Value::Bool(order()?.is_some_and(|o| o.is_ge())),
+        (TokenType::LESS, _, _) => Value::Bool(order()?.is_some_and(|o| o.is_lt())),
+        (TokenType::LessEqual, _, _) => Value::Bool(order()?.is_some_and(|o| o.is_le())),
+        _ => return None,
+    };
+    // Infinities and NaN have no literal, so `1 / 0` stays a division.
+    if matches!(value, Value::Number(number) if !number.is_finite()) {
+        return None;
+    }
+    return Some(value);
+}
+
+/// The value of a literal expression.
+pub(crate) fn constant(expr: &Expr) -> Option<Value> {
+    let Expr::Literal(token) = expr else {
+        return None;
+    };
+    match token.token_type {
+        TokenType::NUMBER => Some(Value::number(&token.literal)),
+        TokenType::STRING => Some(Value::from(token.literal.as_str())),
+        TokenType::TRUE => Some(Value::Bool(true)),
+        TokenType::FALSE => Some(Value::Bool(false)),
+        TokenType::NIL => Some(Value::Nil),
+        _ => None,
+    }
+}
+
+fn literal(value: Value, line: usize) -> Expr {
+    let token = match value {
+        Value::Nil => Token::new(TokenType::NIL, "nil", "", line),
+        Value::Bool(true) => Token::new(TokenType::TRUE, "true", "", line),
+        Value::Bool(false) => Token::new(TokenType::FALSE, "false", "", line),
+        Value::String(value) => {
+            let lexeme = format!("{:?}", value);
+            Token::new(TokenType::STRING, &lexeme, &value, line)
+        }
+        _ => {
+            let text = value.to_string();
+            Token::new(TokenType::NUMBER, &text, &text, line)
+        }
+    };
+    return Expr::Literal(token);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compiler, parser::Parser, scanner::Scanner, vm::Vm};
+
+    use super::*;
+
+    fn parse(source: &str) -> Program {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        Parser::new(&tokens).parse().unwrap()
+    }
+
+    fn folded(source: &str) -> String {
+        let program = fold(parse(source));
+        let Stmt::Expression(expr) = program.statements[0];
+        program.ast.display(expr).to_string()
+    }
+
+    fn interpret(program: &Program) -> Result<Value, String> {
+        let chunk = compiler::compile(program)?;
+        Vm::new().interpret(&chunk)
+    }
+
+    #[test]
+    fn test_fold_constants() -> Result<(), String> {
+        assert_eq!(folded("2 * 60 * 60"), "7200");
+        assert_eq!(folded("!true"), "false");
+        assert_eq!(folded("7 / 2"), "3");
+        assert_eq!(folded("3 / 2.0 + 0.5"), "2.0");
+        assert_eq!(folded("-(1.5 + 1)"), "-2.5");
+        assert_eq!(folded("\"foo\" + \"bar\" == \"foobar\""), "true");
+        assert_eq!(folded("1 < 2 ? \"yes\" : x"), "\"yes\"");
+        assert_eq!(folded("nil ? x : y"), "y");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fold_around_variables() -> Result<(), String> {
+        assert_eq!(folded("x + 2 * 3"), "(+ x 6)");
+        assert_eq!(folded("(1 + 2) * x"), "(* 3 x)");
+        assert_eq!(folded("(x)"), "(group x)");
+        assert_eq!(folded("x ? 1 + 1 : -x"), "(? x 2 (- x))");
+        assert_eq!(folded("nil ?? 1 + 1"), "2");
+        assert_eq!(folded("\"a\" ?? nil"), "\"a\"");
+        assert_eq!(folded("x ?? 1"), "(?? x 1)");
+        assert_eq!(folded("x + \"a\" + \"b\" + \"c\""), "(+ x \"abc\")");
+        assert_eq!(folded("\"a\" + x + \"b\""), "(+ (+ \"a\" x) \"b\")");
+        assert_eq!(folded("x + 1 + \"b\""), "(+ (+ x 1) \"b\")");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_runtime_semantics_preserved() -> Result<(), String> {
+        assert_eq!(folded("1 / 0"), "(/ 1 0)");
+        assert_eq!(folded("1.0 / 0"), "(/ 1.0 0)");
+        #[cfg(not(feature = "bigint"))]
+        assert_eq!(
+            folded("9223372036854775807 + 1"),
+            "(+ 9223372036854775807 1)"
+        );
+        assert_eq!(folded("0 / 0 == 0 / 0"), "(== (/ 0 0) (/ 0 0))");
+        assert_eq!(folded("\"a\" - 1"), "(- \"a\" 1)");
+        assert_eq!(folded("-nil"), "(- nil)");
+
+        for source in [
+            "1 / 0",
+            "1.0 / 0",
+            "-(1.0 / 0) < 0",
+            "7 / 2 == 3.5",
+            "3 / 2.0 + 1",
+            "0 / 0 == 0 / 0",
+            "\"a\" + \"\\n\" + \"b\"",
+            "(1 + 2) * 3 >= 9 ? !nil : 1 - 2",
+            "\"a\" - 1",
+            "-0 == 0",
+            "clock() + \"a\" + \"b\"",
+            "\"\".trim() + \"a\" +\n\"b\"",
+        ] {
+            let program = parse(source);
+            assert_eq!(interpret(&fold(program.clone())), interpret(&program));
+        }
+
+        Ok(())
+    }
+}
@@ -1,410 +1,838 @@
 use crate::{
-    node::{Node, NodeType},
+    ast::{Arm, Ast, Expr, ExprId, Pattern, Program, Stmt},
+    error::{report_at, Diagnostic},
     token::{self, TokenType},
 };
-use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+/// The binary operators with their precedence (higher binds tighter) and
+/// associativity. Adding an operator is a matter of adding a row here.
+pub const BINARY_OPERATORS: &[(TokenType, u8, Associativity)] = &[
+    (TokenType::QuestionQuestion, 0, Associativity::Right),
+    (TokenType::BangEqual, 1, Associativity::Left),
+    (TokenType::EqualEqual, 1, Associativity::Left),
+    (TokenType::GREATER, 2, Associativity::Left),
+    (TokenType::GreaterEqual, 2, Associativity::Left),
+    (TokenType::LESS, 2, Associativity::Left),
+    (TokenType::LessEqual, 2, Associativity::Left),
+    (TokenType::Minus, 3, Associativity::Left),
+    (TokenType::Plus, 3, Associativity::Left),
+    (TokenType::SLASH, 4, Associativity::Left),
+    (TokenType::STAR, 4, Associativity::Left),
+];
+
+fn binary_operator(token_type: &TokenType) -> Option<&'static (TokenType, u8, Associativity)> {
+    return BINARY_OPERATORS
+        .iter()
+        .find(|(operator, _, _)| operator == token_type);
+}
+
+/// How deeply expressions may nest before the parser gives up, unless
+/// changed with [`Parser::set_max_depth`].
+pub const DEFAULT_MAX_DEPTH: usize = 128;
 
 #[derive(Debug)]
-pub struct Parser {
-    pub(crate) tokens: Vec<token::Token>,
+pub struct Parser<'a> {
+    pub(crate) tokens: &'a [token::Token],
     pub(crate) current: usize,
+    /// The expressions parsed so far.
+    pub(crate) ast: Ast,
+    depth: usize,
+    max_depth: usize,
 }
 
-impl Parser {
-    pub fn new(tokens: &Vec<token::Token>) -> Parser {
+impl<'a> Parser<'a> {
+    pub fn new(tokens: &'a [token::Token]) -> Parser<'a> {
         return Parser {
-            tokens: tokens.clone(),
+            tokens,
             current: 0,
+            ast: Ast::new(),
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
         };
     }
 
-    pub fn parse(&mut self) -> Rc<Node> {
-        let expr = self.parse_exspression(5);
-        let root = Rc::new(Node::new(NodeType::Program, &[expr.unwrap()]));
-        return root;
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn parse(&mut self) -> Result<Program, Diagnostic> {
+        let expr = self.parse_expression()?;
+        self.expect(TokenType::EOF, "Expected end of expression.")?;
+        return Ok(Program {
+            ast: std::mem::take(&mut self.ast),
+            statements: vec![Stmt::Expression(expr)],
+        });
     }
 
-    pub fn parse_exspression(&mut self, recurse: usize) -> Result<Rc<Node>, String> {
-        if recurse == 0 {
-            return Err(String::from("Recursion error"));
+    /// Moves to the next token, but never past EOF.
+    pub fn advance(&mut self) {
+        if self.current_token().token_type != TokenType::EOF {
+            self.current += 1;
         }
+    }
 
-        let start = self.current;
+    pub fn current_token(&self) -> &'a token::Token {
+        return &self.tokens[self.current];
+    }
 
-        let binary = self.parse_binary(recurse - 1);
-        match binary {
-            Ok(node) => return Ok(Rc::new(Node::new(NodeType::Expression, &[node]))),
-            Err(err) => {
-                self.current = start;
-            }
+    fn matches(&mut self, token_types: &[TokenType]) -> Option<token::Token> {
+        let token = self.current_token();
+        if token_types.contains(&token.token_type) {
+            self.advance();
+            return Some(token.clone());
         }
+        return None;
+    }
 
-        let group = self.parse_grouping(recurse - 1);
-        match group {
-            Ok(node) => return Ok(Rc::new(Node::new(NodeType::Expression, &[node]))),
-            Err(err) => {
-                self.current = start;
-            }
+    /// Consumes the current token if it has the given type, and reports
+    /// `message` at the offending token otherwise.
+    fn expect(&mut self, token_type: TokenType, message: &str) -> Result<token::Token, Diagnostic> {
+        match self.matches(&[token_type]) {
+            Some(token) => Ok(token),
+            None => Err(self.error(self.current_token(), message)),
         }
+    }
 
-        let unary = self.parse_unary(recurse - 1);
-        match unary {
-            Ok(node) => return Ok(Rc::new(Node::new(NodeType::Expression, &[node]))),
-            Err(err) => {
-                self.current = start;
-            }
+    fn error(&self, token: &token::Token, message: &str) -> Diagnostic {
+        if token.token_type == TokenType::EOF {
+            return report_at(token, " at end", message);
         }
+        return report_at(token, &format!(" at '{}'", token.lexeme), message);
+    }
 
-        let literal = self.parse_literal(recurse - 1);
-        match literal {
-            Ok(node) => return Ok(Rc::new(Node::new(NodeType::Expression, &[node]))),
-            Err(err) => {
-                self.current = start;
-                return Err(err);
-            }
+    /// Guards every recursive rule so deeply nested input reports an error
+    /// instead of overflowing the stack.
+    fn nested<T>(
+        &mut self,
+        rule: impl FnOnce(&mut Self) -> Result<T, Diagnostic>,
+    ) -> Result<T, Diagnostic> {
+        if self.depth >= self.max_depth {
+            return Err(self.error(self.current_token(), "Expression too deeply nested."));
         }
+        self.depth += 1;
+        let result = rule(self);
+        self.depth -= 1;
+        return result;
     }
 
-    pub fn advance(&mut self) {
-        self.current += 1;
+    /// expression → conditional
+    pub fn parse_expression(&mut self) -> Result<ExprId, Diagnostic> {
+        return self.nested(|parser| parser.parse_conditional());
     }
 
-    pub fn current_token(&self) -> token::Token {
-        return self.tokens[self.current].clone();
+    /// conditional → binary ( "?" expression ":" conditional )?
+    pub fn parse_conditional(&mut self) -> Result<ExprId, Diagnostic> {
+        let condition = self.parse_binary(0)?;
+        if self.matches(&[TokenType::Question]).is_none() {
+            return Ok(condition);
+        }
+
+        let then_branch = self.parse_expression()?;
+        self.expect(
+            TokenType::Colon,
+            "Expected ':' after then branch of conditional expression.",
+        )?;
+        let else_branch = self.nested(|parser| parser.parse_conditional())?;
+
+        return Ok(self.ast.add(Expr::Conditional {
+            condition,
+            then_branch,
+            else_branch,
+        }));
     }
 
-    pub fn parse_grouping(&mut self, recurse: usize) -> Result<Rc<Node>, String> {
-        if recurse == 0 {
-            return Err(String::from("Recursion error"));
+    /// binary → unary ( operator unary )*, grouped by [`BINARY_OPERATORS`].
+    ///
+    /// Operators binding at least as tightly as `min_precedence` are folded
+    /// into the result; looser ones are left for an enclosing call.
+    pub fn parse_binary(&mut self, min_precedence: u8) -> Result<ExprId, Diagnostic> {
+        let mut expr = self.parse_unary()?;
+        while let Some(&(_, precedence, associativity)) =
+            binary_operator(&self.current_token().token_type)
+        {
+            if precedence < min_precedence {
+                break;
+            }
+            let operator = self.current_token().clone();
+            self.advance();
+
+            let next_precedence = match associativity {
+                Associativity::Left => precedence + 1,
+                Associativity::Right => precedence,
+            };
+            let right = self.nested(|parser| parser.parse_binary(next_precedence))?;
+            expr = self.ast.add(Expr::Binary {
+                left: expr,
+                operator,
+                right,
+            });
         }
+        return Ok(expr);
+    }
 
-        if self.current_token().token_type != TokenType::LeftParen {
-            return Err(String::from(""));
+    /// unary → ( "!" | "-" ) unary | call
+    pub fn parse_unary(&mut self) -> Result<ExprId, Diagnostic> {
+        if let Some(operator) = self.matches(&[TokenType::BANG, TokenType::Minus]) {
+            let right = self.nested(|parser| parser.parse_unary())?;
+            return Ok(self.ast.add(Expr::Unary { operator, right }));
         }
+        return self.parse_call();
+    }
 
-        self.advance(); // "("
-
-        let expr = self.parse_exspression(recurse - 1);
-
-        self.advance(); // ")"
-
-        match expr {
-            Ok(node) => Ok(Rc::new(Node::new(NodeType::Grouping, &[node]))),
-            Err(err) => Err(err),
+    /// call → primary ( "[" expression "]" | "." IDENTIFIER | "(" arguments? ")" )*
+    pub fn parse_call(&mut self) -> Result<ExprId, Diagnostic> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            if let Some(bracket) = self.matches(&[TokenType::LeftBracket]) {
+                let index = self.parse_expression()?;
+                self.expect(TokenType::RightBracket, "Expected ']' after index.")?;
+                expr = self.ast.add(Expr::Index {
+                    object: expr,
+                    bracket,
+                    index,
+                });
+            } else if self.matches(&[TokenType::Dot]).is_some() {
+                let name =
+                    self.expect(TokenType::IDENTIFIER, "Expected property name after '.'.")?;
+                expr = self.ast.add(Expr::Get { object: expr, name });
+            } else if let Some(paren) = self.matches(&[TokenType::LeftParen]) {
+                let arguments = self.parse_arguments()?;
+                expr = self.ast.add(Expr::Call {
+                    callee: expr,
+                    paren,
+                    arguments,
+                });
+            } else {
+                return Ok(expr);
+            }
         }
     }
 
-    pub fn parse_binary(&mut self, recurse: usize) -> Result<Rc<Node>, String> {
-        if recurse == 0 {
-            return Err(String::from("Recursion error"));
-        }
-        let start = self.current;
-        let expr1 = self.parse_exspression(recurse - 1);
-        match expr1 {
-            Ok(node) => {
-                let op = self.parse_operator(recurse - 1);
-                match op {
-                    Ok(operator) => {
-                        let expr2 = self.parse_exspression(recurse - 1);
-                        match expr2 {
-                            Ok(node2) => {
-                                let expr = Node::new(NodeType::Binary, &[node, operator, node2]);
-                                return Ok(Rc::new(expr));
-                            }
-                            Err(err) => {
-                                self.current = start;
-                                return Err(err);
-                            }
-                        }
-                    }
-                    Err(err) => {
-                        self.current = start;
-                        return Err(err);
-                    }
+    /// arguments → expression ( "," expression )*, up to the closing ")".
+    fn parse_arguments(&mut self) -> Result<Vec<ExprId>, Diagnostic> {
+        let mut arguments = Vec::new();
+        if self.current_token().token_type != TokenType::RightParen {
+            loop {
+                arguments.push(self.parse_expression()?);
+                if self.matches(&[TokenType::Comma]).is_none() {
+                    break;
                 }
             }
-            Err(err) => {
-                self.current = start;
-                return Err(err);
-            }
         }
+        self.expect(TokenType::RightParen, "Expected ')' after arguments.")?;
+        return Ok(arguments);
     }
 
-    pub fn parse_unary(&mut self, recurse: usize) -> Result<Rc<Node>, String> {
-        if recurse == 0 {
-            return Err(String::from("Recursion error"));
+    /// primary → NUMBER | STRING | "true" | "false" | "nil" | IDENTIFIER
+    ///         | "(" expression ")" | tuple | match
+    pub fn parse_primary(&mut self) -> Result<ExprId, Diagnostic> {
+        if let Some(name) = self.matches(&[TokenType::IDENTIFIER]) {
+            return Ok(self.ast.add(Expr::Variable(name)));
         }
 
-        let start = self.current;
-
-        if !(self.current_token().token_type == TokenType::BANG
-            || self.current_token().token_type == TokenType::Minus)
-        {
-            return Err(String::from(""));
+        if let Some(keyword) = self.matches(&[TokenType::MATCH]) {
+            return self.parse_match(keyword);
         }
 
-        let sign = self.current_token();
-
-        self.advance(); // jump over ! or -
-
-        let expr = self.parse_exspression(recurse - 1);
-        match expr {
-            Ok(node) => {
-                let mut unary = Node::new(NodeType::Unary, &[node]);
-                unary.token = sign;
-                return Ok(Rc::new(unary));
+        if let Some(paren) = self.matches(&[TokenType::LeftParen]) {
+            if self.matches(&[TokenType::RightParen]).is_some() {
+                return Ok(self.ast.add(Expr::Tuple {
+                    paren,
+                    elements: Vec::new(),
+                }));
             }
-            Err(err) => {
-                self.current = start;
-                return Err(err);
+            let expr = self.parse_expression()?;
+            if self.current_token().token_type == TokenType::Comma {
+                return self.parse_tuple(paren, expr);
             }
+            self.expect(TokenType::RightParen, "Expected ')' after expression.")?;
+            return Ok(self.ast.add(Expr::Grouping(expr)));
         }
-    }
 
-    pub fn parse_operator(&mut self, recurse: usize) -> Result<Rc<Node>, String> {
-        if recurse == 0 {
-            return Err(String::from("Recursion error"));
-        }
         match self.current_token().token_type {
-            TokenType::EqualEqual
-            | TokenType::BangEqual
-            | TokenType::LESS
-            | TokenType::LessEqual
-            | TokenType::GreaterEqual
-            | TokenType::GREATER
-            | TokenType::Plus
-            | TokenType::Minus
-            | TokenType::STAR
-            | TokenType::SLASH => {
-                let mut node = Node::new(NodeType::Operator, &[]);
-                node.token = self.current_token();
+            TokenType::NUMBER
+            | TokenType::STRING
+            | TokenType::TRUE
+            | TokenType::FALSE
+            | TokenType::NIL => {
+                let literal = Expr::Literal(self.current_token().clone());
                 self.advance();
-                return Ok(Rc::new(node));
+                return Ok(self.ast.add(literal));
             }
-            _ => Err(String::from("")),
+            _ => Err(self.error(self.current_token(), "Expected expression.")),
         }
     }
 
-    pub fn parse_literal(&mut self, recurse: usize) -> Result<Rc<Node>, String> {
-        if recurse == 0 {
-            return Err(String::from("Recursion error"));
+    /// tuple → "(" ")" | "(" expression ( "," expression )* ","? ")"
+    ///
+    /// Called after the first element, with a `,` as the current token.
+    fn parse_tuple(&mut self, paren: token::Token, first: ExprId) -> Result<ExprId, Diagnostic> {
+        let mut elements = vec![first];
+        while self.matches(&[TokenType::Comma]).is_some() {
+            if self.current_token().token_type == TokenType::RightParen {
+                break;
+            }
+            elements.push(self.parse_expression()?);
         }
-        match self.current_token().token_type {
-            TokenType::NUMBER | TokenType::STRING => {
-                let mut node = Node::new(NodeType::Literal, &[]);
-                node.token = self.current_token();
-                self.advance();
-                return Ok(Rc::new(node));
+        self.expect(TokenType::RightParen, "Expected ')' after tuple elements.")?;
+        return Ok(self.ast.add(Expr::Tuple { paren, elements }));
+    }
+
+    /// match → "match" expression "{" arm ( "," arm )* ","? "}"
+    /// arm → pattern ( "if" expression )? "=>" expression
+    ///
+    /// Called after the `match` keyword.
+    fn parse_match(&mut self, keyword: token::Token) -> Result<ExprId, Diagnostic> {
+        let subject = self.parse_expression()?;
+        self.expect(TokenType::LeftBrace, "Expected '{' after match value.")?;
+        let mut arms = Vec::new();
+        loop {
+            let pattern = self.nested(|parser| parser.parse_pattern())?;
+            let guard = match self.matches(&[TokenType::IF]) {
+                Some(_) => Some(self.parse_expression()?),
+                None => None,
+            };
+            self.expect(TokenType::FatArrow, "Expected '=>' after match pattern.")?;
+            let body = self.parse_expression()?;
+            arms.push(Arm {
+                pattern,
+                guard,
+                body,
+            });
+            if self.matches(&[TokenType::Comma]).is_none()
+                || self.current_token().token_type == TokenType::RightBrace
+            {
+                break;
             }
-            _ => { /* Do nothing */ }
         }
-        match self.current_token().lexeme.as_str() {
-            "true" | "false" | "nil" => {
-                let mut node = Node::new(NodeType::Literal, &[]);
-                node.token = self.current_token();
-                self.advance();
-                return Ok(Rc::new(node));
+        self.expect(TokenType::RightBrace, "Expected '}' after match arms.")?;
+        return Ok(self.ast.add(Expr::Match {
+            keyword,
+            subject,
+            arms,
+        }));
+    }
+
+    /// pattern → "_" | IDENTIFIER | NUMBER | STRING | "true" | "false" | "nil"
+    ///         | "(" pattern ")" | "(" ( pattern ( "," pattern )* ","? )? ")"
+    fn parse_pattern(&mut self) -> Result<Pattern, Diagnostic> {
+        if let Some(name) = self.matches(&[TokenType::IDENTIFIER]) {
+            if name.lexeme == "_" {
+                return Ok(Pattern::Wildcard(name));
+            }
+            return Ok(Pattern::Binding(name));
+        }
+        if let Some(literal) = self.matches(&[
+            TokenType::NUMBER,
+            TokenType::STRING,
+            TokenType::TRUE,
+            TokenType::FALSE,
+            TokenType::NIL,
+        ]) {
+            return Ok(Pattern::Literal(literal));
+        }
+        let Some(paren) = self.matches(&[TokenType::LeftParen]) else {
+            return Err(self.error(self.current_token(), "Expected pattern."));
+        };
+
+        // As in expressions, `(a)` is `a` and `(a,)` a tuple of one.
+        let mut elements = Vec::new();
+        let mut grouping = true;
+        while self.current_token().token_type != TokenType::RightParen {
+            elements.push(self.nested(|parser| parser.parse_pattern())?);
+            if self.matches(&[TokenType::Comma]).is_none() {
+                break;
             }
-            _ => Err(String::from("")),
+            grouping = false;
+        }
+        self.expect(TokenType::RightParen, "Expected ')' after tuple pattern.")?;
+        if grouping && elements.len() == 1 {
+            return Ok(elements.remove(0));
         }
+        return Ok(Pattern::Tuple { paren, elements });
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::scanner;
+    use crate::token::Token;
 
     use super::*;
 
-    #[test]
-    fn test_literal_true() -> Result<(), String> {
-        let mut scanner = scanner::Scanner::new(&String::from("true"));
+    fn parse_expression(source: &str) -> Result<(Ast, ExprId), String> {
+        let mut scanner = scanner::Scanner::new(source);
         let tokens = scanner.scan_tokens().unwrap();
         let mut parser = Parser::new(&tokens);
+        let expr = parser.parse_expression()?;
+        Ok((parser.ast, expr))
+    }
+
+    fn literal_token(ast: &Ast, expr: ExprId) -> &Token {
+        match &ast[expr] {
+            Expr::Literal(token) => token,
+            expr => panic!("expected a literal, got {:?}", expr),
+        }
+    }
 
-        let node = parser.parse_literal(10).unwrap();
-        assert_eq!(node.node_type, NodeType::Literal);
-        assert_eq!(node.children.len(), 0);
-        assert_eq!(node.token.token_type, TokenType::TRUE);
+    #[test]
+    fn test_literal_true() -> Result<(), String> {
+        let (ast, expr) = parse_expression("true")?;
+        assert_eq!(literal_token(&ast, expr).token_type, TokenType::TRUE);
 
         Ok(())
     }
 
     #[test]
     fn test_literal_nil() -> Result<(), String> {
-        let mut scanner = scanner::Scanner::new(&String::from("nil"));
-        let tokens = scanner.scan_tokens().unwrap();
-        let mut parser = Parser::new(&tokens);
-
-        let node = parser.parse_literal(10).unwrap();
-        assert_eq!(node.node_type, NodeType::Literal);
-        assert_eq!(node.children.len(), 0);
-        assert_eq!(node.token.token_type, TokenType::NIL);
+        let (ast, expr) = parse_expression("nil")?;
+        assert_eq!(literal_token(&ast, expr).token_type, TokenType::NIL);
 
         Ok(())
     }
 
     #[test]
     fn test_literal_num() -> Result<(), String> {
-        let mut scanner = scanner::Scanner::new(&String::from("123"));
-        let tokens = scanner.scan_tokens().unwrap();
-        let mut parser = Parser::new(&tokens);
-
-        let node = parser.parse_literal(10).unwrap();
-        assert_eq!(node.node_type, NodeType::Literal);
-        assert_eq!(node.children.len(), 0);
-        assert_eq!(node.token.token_type, TokenType::NUMBER);
+        let (ast, expr) = parse_expression("123")?;
+        assert_eq!(literal_token(&ast, expr).token_type, TokenType::NUMBER);
 
         Ok(())
     }
 
     #[test]
     fn test_literal_string() -> Result<(), String> {
-        let mut scanner = scanner::Scanner::new(&String::from("\"123\""));
-        let tokens = scanner.scan_tokens().unwrap();
-        let mut parser = Parser::new(&tokens);
-
-        let node = parser.parse_literal(10).unwrap();
-        assert_eq!(node.node_type, NodeType::Literal);
-        assert_eq!(node.children.len(), 0);
-        assert_eq!(node.token.token_type, TokenType::STRING);
+        let (ast, expr) = parse_expression("\"123\"")?;
+        assert_eq!(literal_token(&ast, expr).token_type, TokenType::STRING);
 
         Ok(())
     }
 
     #[test]
     fn test_operators() -> Result<(), String> {
-        let mut scanner = scanner::Scanner::new(&String::from("== != < <= >= > + - * /"));
-        let tokens = scanner.scan_tokens().unwrap();
-        let mut parser = Parser::new(&tokens);
-
-        let node_equal_equal = parser.parse_operator(10).unwrap();
-        assert_eq!(node_equal_equal.node_type, NodeType::Operator);
-        assert_eq!(node_equal_equal.children.len(), 0);
-        assert_eq!(node_equal_equal.token.token_type, TokenType::EqualEqual);
-
-        let node_bang_equal = parser.parse_operator(10).unwrap();
-        assert_eq!(node_bang_equal.node_type, NodeType::Operator);
-        assert_eq!(node_bang_equal.children.len(), 0);
-        assert_eq!(node_bang_equal.token.token_type, TokenType::BangEqual);
-
-        let node_less = parser.parse_operator(10).unwrap();
-        assert_eq!(node_less.node_type, NodeType::Operator);
-        assert_eq!(node_less.children.len(), 0);
-        assert_eq!(node_less.token.token_type, TokenType::LESS);
-
-        let node_less_equal = parser.parse_operator(10).unwrap();
-        assert_eq!(node_less_equal.node_type, NodeType::Operator);
-        assert_eq!(node_less_equal.children.len(), 0);
-        assert_eq!(node_less_equal.token.token_type, TokenType::LessEqual);
-
-        let node_greater_equal = parser.parse_operator(10).unwrap();
-        assert_eq!(node_greater_equal.node_type, NodeType::Operator);
-        assert_eq!(node_greater_equal.children.len(), 0);
-        assert_eq!(node_greater_equal.token.token_type, TokenType::GreaterEqual);
-
-        let node_greater = parser.parse_operator(10).unwrap();
-        assert_eq!(node_greater.node_type, NodeType::Operator);
-        assert_eq!(node_greater.children.len(), 0);
-        assert_eq!(node_greater.token.token_type, TokenType::GREATER);
-
-        let node_plus = parser.parse_operator(10).unwrap();
-        assert_eq!(node_plus.node_type, NodeType::Operator);
-        assert_eq!(node_plus.children.len(), 0);
-        assert_eq!(node_plus.token.token_type, TokenType::Plus);
-
-        let node_minus = parser.parse_operator(10).unwrap();
-        assert_eq!(node_minus.node_type, NodeType::Operator);
-        assert_eq!(node_minus.children.len(), 0);
-        assert_eq!(node_minus.token.token_type, TokenType::Minus);
-
-        let node_star = parser.parse_operator(10).unwrap();
-        assert_eq!(node_star.node_type, NodeType::Operator);
-        assert_eq!(node_star.children.len(), 0);
-        assert_eq!(node_star.token.token_type, TokenType::STAR);
-
-        let node_slash = parser.parse_operator(10).unwrap();
-        assert_eq!(node_slash.node_type, NodeType::Operator);
-        assert_eq!(node_slash.children.len(), 0);
-        assert_eq!(node_slash.token.token_type, TokenType::SLASH);
+        for (source, token_type) in [
+            ("1 == 2", TokenType::EqualEqual),
+            ("1 != 2", TokenType::BangEqual),
+            ("1 < 2", TokenType::LESS),
+            ("1 <= 2", TokenType::LessEqual),
+            ("1 >= 2", TokenType::GreaterEqual),
+            ("1 > 2", TokenType::GREATER),
+            ("1 + 2", TokenType::Plus),
+            ("1 - 2", TokenType::Minus),
+            ("1 * 2", TokenType::STAR),
+            ("1 / 2", TokenType::SLASH),
+        ] {
+            let (ast, expr) = parse_expression(source)?;
+            match &ast[expr] {
+                Expr::Binary { operator, .. } => assert_eq!(operator.token_type, token_type),
+                expr => panic!("expected a binary expression, got {:?}", expr),
+            }
+        }
 
         Ok(())
     }
 
     #[test]
     fn test_unary_minus() -> Result<(), String> {
-        let mut scanner = scanner::Scanner::new(&String::from("-5"));
-        let tokens = scanner.scan_tokens().unwrap();
+        let (ast, expr) = parse_expression("-5")?;
+        match &ast[expr] {
+            Expr::Unary { operator, right } => {
+                assert_eq!(operator.token_type, TokenType::Minus);
+                assert_eq!(literal_token(&ast, *right).token_type, TokenType::NUMBER);
+                assert_eq!(literal_token(&ast, *right).lexeme.as_str(), "5");
+            }
+            expr => panic!("expected a unary expression, got {:?}", expr),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unary_bang() -> Result<(), String> {
+        let (ast, expr) = parse_expression("!true")?;
+        match &ast[expr] {
+            Expr::Unary { operator, right } => {
+                assert_eq!(operator.token_type, TokenType::BANG);
+                assert_eq!(literal_token(&ast, *right).token_type, TokenType::TRUE);
+            }
+            expr => panic!("expected a unary expression, got {:?}", expr),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_plus() -> Result<(), String> {
+        let (ast, expr) = parse_expression("5+4")?;
+        match &ast[expr] {
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                assert_eq!(literal_token(&ast, *left).lexeme.as_str(), "5");
+                assert_eq!(operator.token_type, TokenType::Plus);
+                assert_eq!(literal_token(&ast, *right).lexeme.as_str(), "4");
+            }
+            expr => panic!("expected a binary expression, got {:?}", expr),
+        }
 
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_precedence() -> Result<(), String> {
+        let (ast, expr) = parse_expression("1 + 2 * 3")?;
+        match &ast[expr] {
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                assert_eq!(literal_token(&ast, *left).lexeme.as_str(), "1");
+                assert_eq!(operator.token_type, TokenType::Plus);
+                assert!(matches!(ast[*right], Expr::Binary { .. }));
+            }
+            expr => panic!("expected a binary expression, got {:?}", expr),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_precedence_and_associativity() -> Result<(), String> {
+        for (source, expected) in [
+            ("1 - 2 - 3", "(- (- 1 2) 3)"),
+            ("1 / 2 / 3", "(/ (/ 1 2) 3)"),
+            ("2 * 3 + 4", "(+ (* 2 3) 4)"),
+            ("2 + 3 * 4", "(+ 2 (* 3 4))"),
+            ("-a * b", "(* (- a) b)"),
+            ("!!a", "(! (! a))"),
+            ("a == b == c", "(== (== a b) c)"),
+            ("a < b == c >= d", "(== (< a b) (>= c d))"),
+            ("1 + 2 < 3 * 4", "(< (+ 1 2) (* 3 4))"),
+            ("(1 + 2) * 3", "(* (group (+ 1 2)) 3)"),
+            ("a ? b : c ? d : e", "(? a b (? c d e))"),
+            ("a == b ? 1 + 2 : 3", "(? (== a b) (+ 1 2) 3)"),
+            ("a ? b ? c : d : e", "(? a (? b c d) e)"),
+            ("a ?? b ?? c", "(?? a (?? b c))"),
+            ("a ?? b == c ? d : e", "(? (?? a (== b c)) d e)"),
+        ] {
+            let (ast, expr) = parse_expression(source)?;
+            assert_eq!(ast.display(expr).to_string(), expected, "{}", source);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_variable() -> Result<(), String> {
+        let (ast, expr) = parse_expression("answer")?;
+        match &ast[expr] {
+            Expr::Variable(name) => assert_eq!(name.lexeme, "answer"),
+            expr => panic!("expected a variable, got {:?}", expr),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_keyword_literals() -> Result<(), String> {
+        for (source, token_type) in [
+            ("true", TokenType::TRUE),
+            ("false", TokenType::FALSE),
+            ("nil", TokenType::NIL),
+        ] {
+            let (ast, expr) = parse_expression(source)?;
+            assert_eq!(literal_token(&ast, expr).token_type, token_type);
+            assert_eq!(literal_token(&ast, expr).lexeme, source);
+        }
+
+        // Only the token type matters, not the lexeme.
+        let tokens = [
+            Token::new(TokenType::NIL, "", "", 1),
+            Token::new(TokenType::EOF, "", "", 1),
+        ];
+        let mut parser = Parser::new(&tokens);
+        let expr = parser.parse_expression()?;
+        assert_eq!(literal_token(&parser.ast, expr).token_type, TokenType::NIL);
+
+        let tokens = [
+            Token::new(TokenType::IDENTIFIER, "true", "", 1),
+            Token::new(TokenType::EOF, "", "", 1),
+        ];
         let mut parser = Parser::new(&tokens);
+        let expr = parser.parse_expression()?;
+        assert!(matches!(parser.ast[expr], Expr::Variable(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_grouping() -> Result<(), String> {
+        let (ast, expr) = parse_expression("(1)")?;
+        match &ast[expr] {
+            Expr::Grouping(expr) => assert_eq!(literal_token(&ast, *expr).lexeme.as_str(), "1"),
+            expr => panic!("expected a grouping, got {:?}", expr),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tuples_and_index() -> Result<(), String> {
+        for (source, expected) in [
+            ("()", "(tuple)"),
+            ("(1,)", "(tuple 1)"),
+            ("(1, \"a\", (2, 3))", "(tuple 1 \"a\" (tuple 2 3))"),
+            ("(a ? b : c, d,)", "(tuple (? a b c) d)"),
+            ("t[0][1 + 1]", "(index (index t 0) (+ 1 1))"),
+            ("-t[0]", "(- (index t 0))"),
+            ("(1, 2)[0] * 2", "(* (index (tuple 1 2) 0) 2)"),
+            ("-s.length", "(- (. s length))"),
+            (
+                "s.trim().slice(0, n)[1]",
+                "(index (call (. (call (. s trim)) slice) 0 n) 1)",
+            ),
+        ] {
+            let (ast, expr) = parse_expression(source)?;
+            assert_eq!(ast.display(expr).to_string(), expected);
+        }
 
-        let node_minus = parser.parse_unary(10).unwrap();
-        assert_eq!(node_minus.node_type, NodeType::Unary);
-        assert_eq!(node_minus.token.token_type, TokenType::Minus);
-        assert_eq!(node_minus.children.len(), 1);
-        assert_eq!(node_minus.children[0].node_type, NodeType::Expression);
-        assert_eq!(node_minus.children[0].children.len(), 1);
         assert_eq!(
-            node_minus.children[0].children[0].node_type,
-            NodeType::Literal
+            parse_expression("(1, 2"),
+            Err(String::from(
+                "[line 1] Error at end: Expected ')' after tuple elements."
+            ))
         );
         assert_eq!(
-            node_minus.children[0].children[0].token.token_type,
-            TokenType::NUMBER
+            parse_expression("(,)"),
+            Err(String::from("[line 1] Error at ',': Expected expression."))
+        );
+        assert_eq!(
+            parse_expression("t[0"),
+            Err(String::from(
+                "[line 1] Error at end: Expected ']' after index."
+            ))
+        );
+        assert_eq!(
+            parse_expression("s.1"),
+            Err(String::from(
+                "[line 1] Error at '1': Expected property name after '.'."
+            ))
         );
         assert_eq!(
-            node_minus.children[0].children[0].token.lexeme.as_str(),
-            "5"
+            parse_expression("s.f(1,)"),
+            Err(String::from("[line 1] Error at ')': Expected expression."))
         );
 
         Ok(())
     }
+
     #[test]
-    fn test_unary_bang() -> Result<(), String> {
-        let mut scanner = scanner::Scanner::new(&String::from("!true"));
-        let tokens = scanner.scan_tokens().unwrap();
+    fn test_match() -> Result<(), String> {
+        for (source, expected) in [
+            ("match x { _ => 1 }", "(match x (_ => 1))"),
+            (
+                "match t { (a, _) if a > 1 => a, (0, (b,)) => b, }",
+                "(match t ((tuple a _) if (> a 1) => a) ((tuple 0 (tuple b)) => b))",
+            ),
+            (
+                "match s { \"a\" => true, nil => false, ((x)) => x, () => 0 }",
+                "(match s (\"a\" => true) (nil => false) (x => x) ((tuple) => 0))",
+            ),
+            (
+                "1 + match x ? y : z { n => n }.length",
+                "(+ 1 (. (match (? x y z) (n => n)) length))",
+            ),
+        ] {
+            let (ast, expr) = parse_expression(source)?;
+            assert_eq!(ast.display(expr).to_string(), expected);
+        }
 
-        let mut parser = Parser::new(&tokens);
+        for (source, message) in [
+            (
+                "match x 1",
+                "[line 1] Error at '1': Expected '{' after match value.",
+            ),
+            ("match x {}", "[line 1] Error at '}': Expected pattern."),
+            (
+                "match x { -1 => 2 }",
+                "[line 1] Error at '-': Expected pattern.",
+            ),
+            (
+                "match x { a 2 }",
+                "[line 1] Error at '2': Expected '=>' after match pattern.",
+            ),
+            (
+                "match x { a => 1 b => 2 }",
+                "[line 1] Error at 'b': Expected '}' after match arms.",
+            ),
+            (
+                "match x { (a => 1 }",
+                "[line 1] Error at '=>': Expected ')' after tuple pattern.",
+            ),
+        ] {
+            assert_eq!(parse_expression(source), Err(String::from(message)));
+        }
 
-        let node_minus = parser.parse_unary(10).unwrap();
-        assert_eq!(node_minus.node_type, NodeType::Unary);
-        assert_eq!(node_minus.token.token_type, TokenType::BANG);
-        assert_eq!(node_minus.children.len(), 1);
-        assert_eq!(node_minus.children[0].node_type, NodeType::Expression);
-        assert_eq!(node_minus.children[0].children.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_conditional() -> Result<(), String> {
+        let (ast, expr) = parse_expression("true ? 1 : 2")?;
+        match &ast[expr] {
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                assert_eq!(literal_token(&ast, *condition).token_type, TokenType::TRUE);
+                assert_eq!(literal_token(&ast, *then_branch).lexeme.as_str(), "1");
+                assert_eq!(literal_token(&ast, *else_branch).lexeme.as_str(), "2");
+            }
+            expr => panic!("expected a conditional, got {:?}", expr),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conditional_right_associative() -> Result<(), String> {
+        let (ast, expr) = parse_expression("1 ? 2 : 3 ? 4 : 5")?;
+        match &ast[expr] {
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                assert_eq!(literal_token(&ast, *condition).lexeme.as_str(), "1");
+                assert_eq!(literal_token(&ast, *then_branch).lexeme.as_str(), "2");
+                match &ast[*else_branch] {
+                    Expr::Conditional {
+                        condition,
+                        else_branch,
+                        ..
+                    } => {
+                        assert_eq!(literal_token(&ast, *condition).lexeme.as_str(), "3");
+                        assert_eq!(literal_token(&ast, *else_branch).lexeme.as_str(), "5");
+                    }
+                    expr => panic!("expected a conditional, got {:?}", expr),
+                }
+            }
+            expr => panic!("expected a conditional, got {:?}", expr),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conditional_missing_colon() -> Result<(), String> {
         assert_eq!(
-            node_minus.children[0].children[0].node_type,
-            NodeType::Literal
+            parse_expression("1 ? 2"),
+            Err(String::from(
+                "[line 1] Error at end: Expected ':' after then branch of conditional expression."
+            ))
         );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_groupings() -> Result<(), String> {
+        let source = format!("{}1{}", "(".repeat(127), ")".repeat(127));
+        let (ast, mut expr) = parse_expression(&source)?;
+        for _ in 0..127 {
+            expr = match &ast[expr] {
+                Expr::Grouping(inner) => *inner,
+                expr => panic!("expected a grouping, got {:?}", expr),
+            };
+        }
+        assert_eq!(literal_token(&ast, expr).lexeme.as_str(), "1");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_too_deeply_nested() -> Result<(), String> {
+        let source = format!("{}1", "-".repeat(10));
+        let mut scanner = scanner::Scanner::new(&source);
+        let tokens = scanner.scan_tokens().unwrap();
+
+        let mut parser = Parser::new(&tokens);
+        parser.set_max_depth(5);
         assert_eq!(
-            node_minus.children[0].children[0].token.token_type,
-            TokenType::TRUE
+            parser.parse_expression().map_err(String::from),
+            Err(String::from(
+                "[line 1] Error at '-': Expression too deeply nested."
+            ))
         );
 
+        let source = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert!(parse_expression(&source).is_err());
+
         Ok(())
     }
 
     #[test]
-    fn test_binary_plus() -> Result<(), String> {
-        let mut scanner = scanner::Scanner::new(&String::from("5+4"));
+    fn test_parse_program() -> Result<(), String> {
+        let mut scanner = scanner::Scanner::new("-1");
         let tokens = scanner.scan_tokens().unwrap();
 
         let mut parser = Parser::new(&tokens);
 
-        let node_plus = parser.parse_binary(10).unwrap();
-        assert_eq!(node_plus.node_type, NodeType::Unary);
-        assert_eq!(node_plus.token.token_type, TokenType::BANG);
-        assert_eq!(node_plus.children.len(), 1);
-        assert_eq!(node_plus.children[0].node_type, NodeType::Expression);
-        assert_eq!(node_plus.children[0].children.len(), 1);
-        assert_eq!(
-            node_plus.children[0].children[0].node_type,
-            NodeType::Literal
-        );
-        assert_eq!(
-            node_plus.children[0].children[0].token.token_type,
-            TokenType::TRUE
-        );
+        let program = parser.parse()?;
+        assert_eq!(program.statements.len(), 1);
+        let Stmt::Expression(expr) = program.statements[0];
+        assert!(matches!(program.ast[expr], Expr::Unary { .. }));
+        assert_eq!(program.ast.ids().count(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() -> Result<(), String> {
+        for (source, message) in [
+            ("1 +", "[line 1] Error at end: Expected expression."),
+            ("1 2", "[line 1] Error at '2': Expected end of expression."),
+            ("* 2", "[line 1] Error at '*': Expected expression."),
+            (
+                "(1 + 2",
+                "[line 1] Error at end: Expected ')' after expression.",
+            ),
+            (
+                "(1 + 2;",
+                "[line 1] Error at ';': Expected ')' after expression.",
+            ),
+            (
+                "(1 +\n2\n3)",
+                "[line 3] Error at '3': Expected ')' after expression.",
+            ),
+            (
+                "((1)",
+                "[line 1] Error at end: Expected ')' after expression.",
+            ),
+            ("1)", "[line 1] Error at ')': Expected end of expression."),
+        ] {
+            let mut scanner = scanner::Scanner::new(source);
+            let tokens = scanner.scan_tokens().unwrap();
+
+            let mut parser = Parser::new(&tokens);
+            assert_eq!(
+                parser.parse().map_err(String::from),
+                Err(String::from(message))
+            );
+        }
+
+        // Errors keep the span of the token they are at.
+        let tokens = scanner::Scanner::new("1 + (2 *)").scan_tokens().unwrap();
+        assert_eq!(Parser::new(&tokens).parse().unwrap_err().span, Some(8..9));
 
         Ok(())
     }
@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+
+use crate::error::{error, report};
+use crate::token::{Token, TokenType};
+
+/// Removes the tokens excluded by `#if NAME ... #else ... #end` pragmas.
+///
+/// A section is kept when `NAME` is in `defines`. Pragmas nest, and the
+/// pragma tokens themselves never reach the parser.
+pub fn preprocess(tokens: &[Token], defines: &HashSet<String>) -> Result<Vec<Token>, Vec<String>> {
+    let mut output = Vec::new();
+    let mut errors = Vec::new();
+    // One entry per open `#if`: whether the enclosing section is active,
+    // and whether the current branch of this `#if` is active.
+    let mut sections: Vec<(bool, bool)> = Vec::new();
+    let mut current = 0;
+
+    while current < tokens.len() {
+        let token = &tokens[current];
+        let active = sections.last().is_none_or(|section| section.1);
+
+        if token.token_type != TokenType::Hash {
+            if active || token.token_type == TokenType::EOF {
+                output.push(token.clone());
+            }
+            current += 1;
+            continue;
+        }
+
+        let directive = &tokens[(current + 1).min(tokens.len() - 1)];
+        match directive.token_type {
+            TokenType::IF => {
+                let name = &tokens[(current + 2).min(tokens.len() - 1)];
+                if name.token_type != TokenType::IDENTIFIER {
+                    errors.push(report(
+                        name.line,
+                        " at '#if'",
+                        "Expected a name after '#if'.",
+                    ));
+                    sections.push((active, false));
+                    current += 2;
+                    continue;
+                }
+                sections.push((active, active && defines.contains(name.lexeme.as_str())));
+                current += 3;
+            }
+            TokenType::ELSE => {
+                match sections.last_mut() {
+                    Some(section) => section.1 = section.0 && !section.1,
+                    None => errors.push(error(directive.line, "'#else' without '#if'.")),
+                }
+                current += 2;
+            }
+            TokenType::IDENTIFIER if directive.lexeme == "end" => {
+                if sections.pop().is_none() {
+                    errors.push(error(directive.line, "'#end' without '#if'."));
+                }
+                current += 2;
+            }
+            _ => {
+                errors.push(report(token.line, " at '#'", "Unknown pragma."));
+                current += 1;
+            }
+        }
+    }
+
+    if !sections.is_empty() {
+        let line = tokens.last().map_or(0, |token| token.line);
+        errors.push(error(line, "Missing '#end' for '#if'."));
+    }
+
+    if errors.is_empty() {
+        Ok(output)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scanner;
+
+    use super::*;
+
+    fn preprocess_source(source: &str, defines: &[&str]) -> Result<Vec<Token>, Vec<String>> {
+        let mut scanner = scanner::Scanner::new(source);
+        let tokens = scanner.scan_tokens().unwrap();
+        let defines = defines.iter().map(|name| name.to_string()).collect();
+        preprocess(&tokens, &defines)
+    }
+
+    #[test]
+    fn test_if_defined() -> Result<(), String> {
+        let tokens = preprocess_source("1 #if DEBUG + 2 #end", &["DEBUG"]).unwrap();
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[1].token_type, TokenType::Plus);
+        assert_eq!(tokens[3].token_type, TokenType::EOF);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_if_not_defined() -> Result<(), String> {
+        let tokens = preprocess_source("1 #if DEBUG + 2 #end", &[]).unwrap();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].lexeme, "1");
+        assert_eq!(tokens[1].token_type, TokenType::EOF);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_else_and_nesting() -> Result<(), String> {
+        let source = "#if A #if B 1 #else 2 #end #else 3 #end";
+
+        let tokens = preprocess_source(source, &["A"]).unwrap();
+        assert_eq!(tokens[0].lexeme, "2");
+
+        let tokens = preprocess_source(source, &["A", "B"]).unwrap();
+        assert_eq!(tokens[0].lexeme, "1");
+
+        let tokens = preprocess_source(source, &["B"]).unwrap();
+        assert_eq!(tokens[0].lexeme, "3");
+        assert_eq!(tokens.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unbalanced_pragmas() -> Result<(), String> {
+        assert_eq!(
+            preprocess_source("#if A 1", &[]).unwrap_err(),
+            vec![String::from("[line 1] Error: Missing '#end' for '#if'.")]
+        );
+        assert_eq!(
+            preprocess_source("1\n#end", &[]).unwrap_err(),
+            vec![String::from("[line 2] Error: '#end' without '#if'.")]
+        );
+        assert_eq!(preprocess_source("#if 1 #end", &[]).unwrap_err().len(), 1);
+        assert_eq!(preprocess_source("#debug", &[]).unwrap_err().len(), 1);
+
+        Ok(())
+    }
+}
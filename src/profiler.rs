@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::{chunk::Chunk, value::Value, vm::Observer};
+
+/// What the VM spent on one source line.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LineProfile {
+    pub instructions: u64,
+    pub time: Duration,
+}
+
+/// Counts the instructions run and the wall time spent on every source
+/// line. An instruction's time lasts until the next one starts, so call
+/// [`Profiler::stop`] once the VM returns to account for the last one.
+///
+/// There are no user functions yet, so everything is attributed to the
+/// top-level script.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    lines: BTreeMap<usize, LineProfile>,
+    current: Option<(usize, Instant)>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        return Profiler::default();
+    }
+
+    pub fn stop(&mut self) {
+        if let Some((line, started)) = self.current.take() {
+            self.line(line).time += started.elapsed();
+        }
+    }
+
+    pub fn lines(&self) -> &BTreeMap<usize, LineProfile> {
+        return &self.lines;
+    }
+
+    /// A table of the lines, the slowest first.
+    pub fn report(&self) -> String {
+        let mut lines: Vec<_> = self.lines.iter().collect();
+        lines.sort_by(|(a_line, a), (b_line, b)| b.time.cmp(&a.time).then(a_line.cmp(b_line)));
+
+        let mut report = String::from("    Line  Instructions       Time\n");
+        for (line, profile) in lines {
+            report.push_str(&format!(
+                "{:>8}  {:>12}  {:>7.3}ms\n",
+                line,
+                profile.instructions,
+                profile.time.as_secs_f64() * 1000.0
+            ));
+        }
+        return report;
+    }
+
+    /// Instruction counts in the folded stack format read by flamegraph
+    /// tools, one `script;line N count` per line.
+    pub fn folded(&self) -> String {
+        let mut folded = String::new();
+        for (line, profile) in &self.lines {
+            folded.push_str(&format!("script;line {} {}\n", line, profile.instructions));
+        }
+        return folded;
+    }
+
+    fn line(&mut self, line: usize) -> &mut LineProfile {
+        return self.lines.entry(line).or_default();
+    }
+}
+
+impl Observer for Profiler {
+    fn before_instruction(&mut self, chunk: &Chunk, offset: usize, _stack: &[Value]) {
+        self.stop();
+        let line = chunk.lines[offset];
+        self.line(line).instructions += 1;
+        self.current = Some((line, Instant::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compiler, parser::Parser, scanner::Scanner, vm::Vm};
+
+    use super::*;
+
+    fn profile(source: &str) -> Profiler {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let chunk = compiler::compile(&Parser::new(&tokens).parse().unwrap()).unwrap();
+        let mut profiler = Profiler::new();
+        Vm::new().interpret_with(&chunk, &mut profiler).unwrap();
+        profiler.stop();
+        profiler
+    }
+
+    #[test]
+    fn test_counts_per_line() -> Result<(), String> {
+        let profiler = profile("1 +\n2 *\n3");
+
+        let counts: Vec<_> = profiler
+            .lines()
+            .iter()
+            .map(|(line, profile)| (*line, profile.instructions))
+            .collect();
+        assert_eq!(counts, vec![(1, 3), (2, 2), (3, 1)]);
+        assert_eq!(
+            profiler.folded(),
+            "script;line 1 3\nscript;line 2 2\nscript;line 3 1\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report() -> Result<(), String> {
+        let report = profile("false ? 1 : 2").report();
+
+        let mut lines = report.lines();
+        assert_eq!(lines.next(), Some("    Line  Instructions       Time"));
+        assert!(lines.next().unwrap().starts_with("       1             5"));
+        assert_eq!(lines.next(), None);
+
+        Ok(())
+    }
+}
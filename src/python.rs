@@ -0,0 +1,79 @@
+//! The `pyudyr` Python module, built with `--features python`.
+
+use pyo3::{
+    create_exception, exceptions::PyException, prelude::*, types::PyTuple, IntoPyObjectExt,
+};
+
+use crate::{interpreter::Interpreter, natives::Capabilities, value::Value};
+
+create_exception!(pyudyr, UdyrError, PyException, "A udyr program failed.");
+
+/// Converts a udyr value to the matching Python object: `nil` becomes
+/// `None`, ints become ints, floats become floats and tuples become tuples.
+pub fn to_python(py: Python<'_>, value: Value) -> PyResult<Py<PyAny>> {
+    match value {
+        Value::Nil => Ok(py.None()),
+        Value::Bool(value) => value.into_py_any(py),
+        Value::Int(value) => value.into_py_any(py),
+        #[cfg(feature = "bigint")]
+        Value::BigInt(value) => (*value).into_py_any(py),
+        Value::Number(value) => value.into_py_any(py),
+        Value::String(value) => value.as_str().into_py_any(py),
+        Value::Tuple(elements) => {
+            let elements = elements
+                .iter()
+                .map(|element| to_python(py, element.clone()))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyTuple::new(py, elements)?.into_py_any(py)
+        }
+    }
+}
+
+/// Runs `source` and returns its value, raising `UdyrError` with every
+/// error message, one per line, if it fails. `exec` only works with
+/// `allow_exec=True`.
+#[pyfunction]
+#[pyo3(signature = (source, allow_exec = false))]
+fn eval(py: Python<'_>, source: &str, allow_exec: bool) -> PyResult<Py<PyAny>> {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_capabilities(Capabilities {
+        exec: allow_exec,
+        ..Capabilities::default()
+    });
+    match interpreter.eval(source) {
+        Ok(value) => to_python(py, value),
+        Err(errors) => Err(UdyrError::new_err(errors.join("\n"))),
+    }
+}
+
+#[pymodule]
+fn pyudyr(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(eval, module)?)?;
+    module.add("UdyrError", module.py().get_type::<UdyrError>())?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval() -> Result<(), String> {
+        Python::initialize();
+        Python::attach(|py| {
+            let value = eval(py, "\"a\" + \"b\"", false).unwrap();
+            assert_eq!(value.extract::<String>(py).unwrap(), "ab");
+
+            let value = eval(py, "1 < 2 ? 3 : nil", false).unwrap();
+            assert_eq!(value.extract::<f64>(py).unwrap(), 3.0);
+            assert!(eval(py, "nil", false).unwrap().is_none(py));
+
+            let error = eval(py, "-nil", false).unwrap_err();
+            assert!(error.is_instance_of::<UdyrError>(py));
+            assert!(eval(py, "exec(\"true\", ())", false).is_err());
+            assert!(eval(py, "exec(\"true\", ())", true).is_ok());
+        });
+
+        Ok(())
+    }
+}
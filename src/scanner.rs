@@ -1,92 +1,221 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, OnceLock};
 
-use crate::error::error;
-use crate::token::{Token, TokenType};
+use crate::error::{error, report};
+use crate::interner::Interner;
+use crate::token::{Substr, Token, TokenType, Trivia};
+use crate::value::Value;
 
 pub struct Scanner {
-    source: String,
-    tokens: Vec<Token>,
+    /// Shared with the lexemes of the tokens.
+    source: Arc<str>,
+    /// Tokens scanned but not yet handed out by the iterator.
+    tokens: VecDeque<Token>,
     errors: Vec<String>,
-    keywords: HashMap<String, TokenType>,
+    /// Where the `\` of each invalid escape is, which doubling fixes.
+    pub(crate) fixes: Vec<usize>,
+    /// How many of `errors` the iterator has handed out.
+    reported: usize,
+    finished: bool,
+    interner: Interner,
     start: usize,
     current: usize,
     line: usize,
+    strict: bool,
+    comments: bool,
+    trivia: bool,
+    template: bool,
+    /// Whether a template is inside `{{ }}` or `{% %}`.
+    in_code: bool,
+    /// Where the trivia before the next token starts.
+    trivia_start: usize,
+    /// The first line break since `trivia_start` that is not in a comment,
+    /// which ends the trailing trivia of the token before it.
+    line_break: Option<usize>,
+}
+
+pub(crate) fn keywords() -> &'static HashMap<&'static str, TokenType> {
+    static KEYWORDS: OnceLock<HashMap<&'static str, TokenType>> = OnceLock::new();
+    return KEYWORDS.get_or_init(|| {
+        HashMap::from([
+            ("and", TokenType::AND),
+            ("class", TokenType::CLASS),
+            ("else", TokenType::ELSE),
+            ("false", TokenType::FALSE),
+            ("for", TokenType::FOR),
+            ("fun", TokenType::FUN),
+            ("if", TokenType::IF),
+            ("match", TokenType::MATCH),
+            ("nil", TokenType::NIL),
+            ("or", TokenType::OR),
+            ("print", TokenType::PRINT),
+            ("return", TokenType::RETURN),
+            ("super", TokenType::SUPER),
+            ("this", TokenType::THIS),
+            ("true", TokenType::TRUE),
+            ("var", TokenType::VAR),
+            ("while", TokenType::WHILE),
+        ])
+    });
 }
 
 fn is_digit(c: char) -> bool {
-    return c >= '0' && c <= '9';
+    return c.is_ascii_digit();
 }
 
+/// Identifiers may start with any Unicode letter or `_`.
 fn is_alpha(c: char) -> bool {
-    return (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_';
+    return c.is_alphabetic() || c == '_';
 }
 
+/// Identifiers may continue with any Unicode letter, digit or `_`.
 fn is_alpha_numeric(c: char) -> bool {
-    return is_digit(c) || is_alpha(c);
+    return c.is_alphanumeric() || c == '_';
+}
+
+/// The digits and radix of an integer literal like `1_000` or `0xFF`, or
+/// `None` if it has a fraction or exponent.
+fn integer_digits(literal: &str) -> Option<(String, u32)> {
+    let literal = literal.replace('_', "");
+    let (digits, radix) = match literal.get(..2) {
+        Some("0x" | "0X") => (&literal[2..], 16),
+        Some("0b" | "0B") => (&literal[2..], 2),
+        _ if literal.bytes().all(|b| b.is_ascii_digit()) => (literal.as_str(), 10),
+        _ => return None,
+    };
+    return Some((digits.to_string(), radix));
+}
+
+/// The value of an integer literal like `1_000` or `0xFF`, or `None` if it
+/// has a fraction or exponent or, without the `bigint` feature, does not
+/// fit an `i64`.
+fn integer_value(literal: &str) -> Option<Value> {
+    let (digits, radix) = integer_digits(literal)?;
+    let digits = digits.as_str();
+    #[cfg(feature = "bigint")]
+    return num_bigint::BigInt::parse_bytes(digits.as_bytes(), radix).map(Value::from);
+    #[cfg(not(feature = "bigint"))]
+    return i64::from_str_radix(digits, radix).ok().map(Value::Int);
+}
+
+/// Writes a decimal literal like `12.50e-1` without exponent and insignificant zeros, the way
+/// its `f64` value prints if the literal can be represented exactly.
+fn normalize_number(literal: &str) -> Option<String> {
+    let (mantissa, exponent) = match literal.split_once('e') {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().ok()?),
+        None => (literal, 0),
+    };
+    if exponent.abs() > 1000 {
+        return None;
+    }
+
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", integer, fraction);
+    let point = integer.len() as i64 + exponent;
+    let shifted = if point <= 0 {
+        format!("0.{}{}", "0".repeat(-point as usize), digits)
+    } else if point as usize >= digits.len() {
+        format!("{}{}", digits, "0".repeat(point as usize - digits.len()))
+    } else {
+        let (integer, fraction) = digits.split_at(point as usize);
+        format!("{}.{}", integer, fraction)
+    };
+
+    let (integer, fraction) = match shifted.split_once('.') {
+        Some((integer, fraction)) => (integer, fraction.trim_end_matches('0')),
+        None => (shifted.as_str(), ""),
+    };
+    let mut integer = integer.trim_start_matches('0');
+    if integer.is_empty() {
+        integer = "0";
+    }
+    if fraction.is_empty() {
+        return Some(String::from(integer));
+    }
+    return Some(format!("{}.{}", integer, fraction));
 }
 
 impl Scanner {
-    pub fn new(source: &String) -> Scanner {
-        let mut scanner = Scanner {
-            source: source.clone(),
-            tokens: Vec::new(),
+    pub fn new(source: &str) -> Scanner {
+        return Scanner::shared(Arc::from(source));
+    }
+
+    /// Scans a source without copying it, for hosts that already keep it
+    /// in an `Arc`.
+    pub fn shared(source: Arc<str>) -> Scanner {
+        return Scanner::resume(source, 0, 1, Interner::new());
+    }
+
+    /// Scans `source` from `offset`, which must be where a token starts or
+    /// the space before one, as if on `line`. Identifiers are added to
+    /// `interner`, so symbols match those of an earlier scan.
+    pub(crate) fn resume(
+        source: Arc<str>,
+        offset: usize,
+        line: usize,
+        interner: Interner,
+    ) -> Scanner {
+        return Scanner {
+            source,
+            tokens: VecDeque::new(),
             errors: Vec::new(),
-            keywords: HashMap::new(),
-            start: 0,
-            current: 0,
-            line: 1,
+            fixes: Vec::new(),
+            reported: 0,
+            finished: false,
+            interner,
+            start: offset,
+            current: offset,
+            line,
+            strict: false,
+            comments: false,
+            trivia: false,
+            template: false,
+            in_code: false,
+            trivia_start: offset,
+            line_break: None,
         };
+    }
 
-        scanner.keywords.insert(String::from("and"), TokenType::AND);
-        scanner
-            .keywords
-            .insert(String::from("class"), TokenType::CLASS);
-        scanner
-            .keywords
-            .insert(String::from("else"), TokenType::ELSE);
-        scanner
-            .keywords
-            .insert(String::from("false"), TokenType::FALSE);
-        scanner.keywords.insert(String::from("for"), TokenType::FOR);
-        scanner.keywords.insert(String::from("fun"), TokenType::FUN);
-        scanner.keywords.insert(String::from("if"), TokenType::IF);
-        scanner.keywords.insert(String::from("nil"), TokenType::NIL);
-        scanner.keywords.insert(String::from("or"), TokenType::OR);
-        scanner
-            .keywords
-            .insert(String::from("print"), TokenType::PRINT);
-        scanner
-            .keywords
-            .insert(String::from("return"), TokenType::RETURN);
-        scanner
-            .keywords
-            .insert(String::from("super"), TokenType::SUPER);
-        scanner
-            .keywords
-            .insert(String::from("this"), TokenType::THIS);
-        scanner
-            .keywords
-            .insert(String::from("true"), TokenType::TRUE);
-        scanner.keywords.insert(String::from("var"), TokenType::VAR);
-        scanner
-            .keywords
-            .insert(String::from("while"), TokenType::WHILE);
-
-        return scanner;
-    }
-
-    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, ()> {
-        while !self.is_at_end() {
-            self.start = self.current;
-            self.scan_token();
+    pub(crate) fn into_interner(self) -> Interner {
+        return self.interner;
+    }
+
+    /// The names of all identifiers scanned so far, by symbol.
+    pub fn interner(&self) -> &Interner {
+        return &self.interner;
+    }
+
+    /// In strict mode, number literals that lose precision as an `f64` are reported.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Keeps comments as COMMENT tokens, for tools that reproduce the source.
+    pub fn set_comments(&mut self, comments: bool) {
+        self.comments = comments;
+    }
+
+    /// Keeps the whitespace and comments around each token as its leading
+    /// and trailing trivia, so the tokens can be written back as the exact
+    /// source with [`token::source`](crate::token::source).
+    pub fn set_trivia(&mut self, trivia: bool) {
+        self.trivia = trivia;
+    }
+
+    /// Scans the source as a template: text, scanned as TemplateText
+    /// tokens, with code between `{{` and `}}` or `{%` and `%}`.
+    pub fn set_template(&mut self, template: bool) {
+        self.template = template;
+    }
+
+    /// Scans the rest of the source. If anything could not be scanned, all
+    /// the errors are returned instead of the tokens.
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, Vec<String>> {
+        let tokens = self.by_ref().flatten().collect();
+        if !self.errors.is_empty() {
+            return Err(self.errors.clone());
         }
-        self.tokens.push(Token::new(
-            TokenType::EOF,
-            &String::from("\0"),
-            &String::from("\0"),
-            self.line,
-        ));
-        Ok(self.tokens.clone())
+        return Ok(tokens);
     }
 
     fn is_at_end(&self) -> bool {
@@ -94,49 +223,86 @@ impl Scanner {
     }
 
     fn scan_token(&mut self) {
+        if self.template && !self.in_code {
+            self.template_text();
+            return;
+        }
         let c = self.advance();
         match c {
             // Single lexemes
             '(' => self.add_empty_token(TokenType::LeftParen),
             ')' => self.add_empty_token(TokenType::RightParen),
             '{' => self.add_empty_token(TokenType::LeftBrace),
+            '}' if self.in_code && self.match_next('}') => {
+                self.current += 1;
+                self.in_code = false;
+                self.add_empty_token(TokenType::ExpressionClose);
+            }
+            '%' if self.in_code && self.match_next('}') => {
+                self.current += 1;
+                self.in_code = false;
+                self.add_empty_token(TokenType::StatementClose);
+            }
             '}' => self.add_empty_token(TokenType::RightBrace),
+            '[' => self.add_empty_token(TokenType::LeftBracket),
+            ']' => self.add_empty_token(TokenType::RightBracket),
             ',' => self.add_empty_token(TokenType::Comma),
             '.' => self.add_empty_token(TokenType::Dot),
             '-' => self.add_empty_token(TokenType::Minus),
             '+' => self.add_empty_token(TokenType::Plus),
             ';' => self.add_empty_token(TokenType::SEMICOLON),
             '*' => self.add_empty_token(TokenType::STAR),
+            '?' => {
+                if self.match_next('?') {
+                    self.current += 1;
+                    self.add_empty_token(TokenType::QuestionQuestion);
+                } else {
+                    self.add_empty_token(TokenType::Question);
+                }
+            }
+            ':' => self.add_empty_token(TokenType::Colon),
+            // A first line like `#!/usr/bin/env udyr` lets the script run
+            // as a program, and is otherwise a comment.
+            '#' if self.start == 0 && self.match_next('!') => {
+                while self.peek() != '\n' && !self.is_at_end() {
+                    self.advance();
+                }
+                self.add_comment();
+            }
+            '#' => self.add_empty_token(TokenType::Hash),
 
             // operators
             '!' => {
                 if self.match_next('=') {
-                    self.add_empty_token(TokenType::BangEqual);
                     self.current += 1;
+                    self.add_empty_token(TokenType::BangEqual);
                 } else {
                     self.add_empty_token(TokenType::BANG);
                 }
             }
             '=' => {
                 if self.match_next('=') {
+                    self.current += 1;
                     self.add_empty_token(TokenType::EqualEqual);
+                } else if self.match_next('>') {
                     self.current += 1;
+                    self.add_empty_token(TokenType::FatArrow);
                 } else {
                     self.add_empty_token(TokenType::EQUAL);
                 }
             }
             '<' => {
                 if self.match_next('=') {
-                    self.add_empty_token(TokenType::LessEqual);
                     self.current += 1;
+                    self.add_empty_token(TokenType::LessEqual);
                 } else {
                     self.add_empty_token(TokenType::LESS);
                 }
             }
             '>' => {
                 if self.match_next('=') {
-                    self.add_empty_token(TokenType::GreaterEqual);
                     self.current += 1;
+                    self.add_empty_token(TokenType::GreaterEqual);
                 } else {
                     self.add_empty_token(TokenType::GREATER);
                 }
@@ -150,6 +316,11 @@ impl Scanner {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    self.add_comment();
+                } else if self.match_next('*') {
+                    self.current += 1;
+                    self.block_comment();
+                    self.add_comment();
                 } else {
                     self.add_empty_token(TokenType::SLASH)
                 }
@@ -157,14 +328,17 @@ impl Scanner {
 
             // Whitespace
             ' ' | '\r' | '\t' => {}
-            '\n' => self.line += 1,
+            '\n' => {
+                self.line += 1;
+                self.line_break.get_or_insert(self.start);
+            }
 
             // Strings
             '"' => self.string(),
 
             _ => {
                 if is_digit(c) {
-                    self.number();
+                    self.number(c);
                 } else if is_alpha(c) {
                     self.identifier();
                 } else {
@@ -174,79 +348,385 @@ impl Scanner {
         }
     }
 
+    /// Consumes the next character. `start` and `current` are byte offsets
+    /// into the source, always on character boundaries.
     fn advance(&mut self) -> char {
-        let c = self.source.chars().nth(self.current).unwrap();
-        self.current += 1;
+        if self.is_at_end() {
+            return '\0';
+        }
+        let c = self.peek();
+        self.current += c.len_utf8();
         return c;
     }
 
+    /// Scans template text up to the next `{{` or `{%`, then that.
+    fn template_text(&mut self) {
+        let opens_code =
+            |scanner: &Scanner| scanner.peek() == '{' && matches!(scanner.peek_next(), '{' | '%');
+        while !self.is_at_end() && !opens_code(self) {
+            if self.peek() == '\n' {
+                self.line += 1;
+            }
+            self.advance();
+        }
+        if self.current > self.start {
+            let text = self.slice(self.start, self.current);
+            self.add_token(TokenType::TemplateText, text);
+            self.start = self.current;
+        }
+        if !self.is_at_end() {
+            self.advance();
+            let token_type = match self.advance() {
+                '{' => TokenType::ExpressionOpen,
+                _ => TokenType::StatementOpen,
+            };
+            self.in_code = true;
+            self.add_empty_token(token_type);
+        }
+    }
+
     fn add_empty_token(&mut self, token_type: TokenType) {
-        self.add_token(token_type, &String::new());
+        let literal = self.slice(self.start, self.start);
+        self.add_token(token_type, literal);
     }
 
-    fn add_token(&mut self, token_type: TokenType, literal: &String) {
-        let text = String::from(&self.source[self.start..self.current]);
-        self.tokens
-            .push(Token::new(token_type, &text, &literal, self.line))
+    fn add_token(&mut self, token_type: TokenType, literal: Substr) {
+        let token = Token {
+            token_type,
+            lexeme: self.slice(self.start, self.current),
+            literal,
+            line: self.line,
+            symbol: None,
+            offset: self.start,
+            trivia: None,
+        };
+        self.push_token(token);
+    }
+
+    /// Bytes `start..end` of the source, without copying them.
+    fn slice(&self, start: usize, end: usize) -> Substr {
+        return Substr::new(&self.source, start, end);
+    }
+
+    fn push_token(&mut self, mut token: Token) {
+        if self.trivia {
+            let end = token.offset.min(self.source.len());
+            let split = self.line_break.take().unwrap_or(end);
+            let (trailing, leading) = (
+                &self.source[self.trivia_start..split],
+                &self.source[split..end],
+            );
+            // The first token has nothing before it to trail.
+            let leading = match self.tokens.back_mut() {
+                Some(previous) => {
+                    previous.trivia.get_or_insert_default().trailing = trailing.to_string();
+                    leading.to_string()
+                }
+                None => format!("{}{}", trailing, leading),
+            };
+            token.trivia = Some(Box::new(Trivia {
+                leading,
+                trailing: String::new(),
+            }));
+            self.trivia_start = end + token.lexeme.len();
+        }
+        self.tokens.push_back(token);
+    }
+
+    fn add_comment(&mut self) {
+        if self.comments {
+            self.add_empty_token(TokenType::COMMENT);
+        }
     }
 
     fn match_next(&self, expected: char) -> bool {
         if self.is_at_end() {
             return false;
         }
-        if self.source.chars().nth(self.current).unwrap() != expected {
-            return false;
-        }
-        return true;
+        return self.peek() == expected;
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-        return self.source.chars().nth(self.current).unwrap();
+        return self.source[self.current..].chars().next().unwrap_or('\0');
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
+        return self.source[self.current..].chars().nth(1).unwrap_or('\0');
+    }
+
+    /// Skips a `/* ... */` comment, which may nest and span several lines.
+    fn block_comment(&mut self) {
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_at_end() {
+                self.errors
+                    .push(error(self.line, "Unterminated block comment."));
+                return;
+            }
+
+            if self.peek() == '/' && self.peek_next() == '*' {
+                self.current += 2;
+                depth += 1;
+            } else if self.peek() == '*' && self.peek_next() == '/' {
+                self.current += 2;
+                depth -= 1;
+            } else {
+                if self.peek() == '\n' {
+                    self.line += 1;
+                }
+                self.advance();
+            }
         }
-        return self.source.chars().nth(self.current + 1).unwrap();
     }
 
     fn string(&mut self) {
+        // Only a string with escapes needs its value copied.
+        let mut escaped: Option<String> = None;
         while self.peek() != '"' && !self.is_at_end() {
             if self.peek() == '\n' {
                 self.line += 1;
             }
-            self.advance();
+            if self.peek() == '\\' {
+                let value = escaped
+                    .get_or_insert_with(|| self.source[self.start + 1..self.current].to_string());
+                if let Some(c) = self.escape() {
+                    value.push(c);
+                }
+                continue;
+            }
+            let c = self.advance();
+            if let Some(value) = &mut escaped {
+                value.push(c);
+            }
         }
         if self.is_at_end() {
-            self.errors.push(error(self.line, "Unterminated string!"));
+            self.errors.push(error(self.line, "Unterminated string."));
+            return;
         }
         self.advance(); // the closing "
 
-        let value = String::from(&self.source[self.start + 1..self.current - 1]);
-        self.add_token(TokenType::STRING, &value);
+        let value = match escaped {
+            Some(value) => Substr::from(value),
+            None => self.slice(self.start + 1, self.current - 1),
+        };
+        self.add_token(TokenType::STRING, value);
     }
 
-    fn number(&mut self) {
-        while is_digit(self.peek()) {
-            self.advance();
+    fn escape(&mut self) -> Option<char> {
+        let backslash = self.current;
+        self.advance(); // the \
+        if self.is_at_end() {
+            return None;
+        }
+
+        let c = self.advance();
+        match c {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            'u' => self.unicode_escape(),
+            _ => {
+                if c == '\n' {
+                    self.line += 1;
+                }
+                let location = format!(" at '\\{}'", c.escape_default());
+                let message = format!(
+                    "Invalid escape sequence. Did you mean \"\\\\{}\"?",
+                    c.escape_default()
+                );
+                self.errors.push(report(self.line, &location, &message));
+                self.fixes.push(backslash);
+                None
+            }
+        }
+    }
+
+    /// Scans the `{XXXX}` part of a `\u{XXXX}` escape, with one to six hex digits.
+    fn unicode_escape(&mut self) -> Option<char> {
+        if self.peek() != '{' {
+            self.errors.push(report(
+                self.line,
+                " at '\\u'",
+                "Expected '{' after '\\u' in unicode escape sequence.",
+            ));
+            return None;
+        }
+        self.advance(); // the {
+
+        let mut digits = String::new();
+        while self.peek().is_ascii_hexdigit() {
+            digits.push(self.advance());
+        }
+
+        let location = format!(" at '\\u{{{}'", digits);
+        if self.peek() != '}' {
+            self.errors.push(report(
+                self.line,
+                &location,
+                "Expected '}' after unicode escape sequence.",
+            ));
+            return None;
         }
+        self.advance(); // the }
+
+        let c = u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32);
+        match c {
+            Some(c) if digits.len() <= 6 => Some(c),
+            _ => {
+                let location = format!(" at '\\u{{{}}}'", digits);
+                self.errors.push(report(
+                    self.line,
+                    &location,
+                    "Invalid unicode escape sequence.",
+                ));
+                None
+            }
+        }
+    }
+
+    fn number(&mut self, first: char) {
+        let result = if first == '0' && matches!(self.peek(), 'x' | 'X' | 'b' | 'B') {
+            let radix = if matches!(self.advance(), 'x' | 'X') {
+                16
+            } else {
+                2
+            };
+            self.radix_number(radix)
+        } else {
+            self.decimal_number(first)
+        };
+
+        if result.is_err() {
+            // Swallow the rest of a malformed literal like `0b102` or `1_x`
+            // instead of reporting it again as more tokens.
+            while is_alpha_numeric(self.peek()) {
+                self.advance();
+            }
+        }
+
+        let text = &self.source[self.start..self.current];
+        let location = format!(" at '{}'", text);
+        let (number, exact) = match result {
+            Ok(value) => value,
+            Err(message) => {
+                self.errors.push(report(self.line, &location, &message));
+                return;
+            }
+        };
+
+        // Literals without a fraction or exponent are ints, which are
+        // always exact, and an error rather than a rounded float when they
+        // do not fit.
+        let integer = integer_value(text);
+        if integer.is_none() {
+            if integer_digits(text).is_some() {
+                self.errors.push(report(
+                    self.line,
+                    &location,
+                    "Integer literal is too large to be represented.",
+                ));
+            } else if number.is_infinite() {
+                self.errors.push(report(
+                    self.line,
+                    &location,
+                    "Number literal is too large to be represented.",
+                ));
+            } else if self.strict && !exact {
+                self.errors.push(report(
+                    self.line,
+                    &location,
+                    "Number literal cannot be represented exactly.",
+                ));
+            }
+        }
+
+        // Most literals are already written the way their value prints.
+        let value = integer.unwrap_or(Value::Number(number)).to_string();
+        let literal = if value == text {
+            self.slice(self.start, self.current)
+        } else {
+            Substr::from(value)
+        };
+        self.add_token(TokenType::NUMBER, literal)
+    }
+
+    /// Scans digits in `radix`, allowing `_` between them, and returns the digits without separators.
+    fn digits(&mut self, radix: u32) -> Result<String, String> {
+        let mut digits = String::new();
+        while self.peek().is_digit(radix) || self.peek() == '_' {
+            let c = self.advance();
+            if c != '_' {
+                digits.push(c);
+            } else if !self.peek().is_digit(radix) {
+                return Err(String::from(
+                    "Expected a digit after '_' in number literal.",
+                ));
+            }
+        }
+        return Ok(digits);
+    }
+
+    fn decimal_number(&mut self, first: char) -> Result<(f64, bool), String> {
+        let mut text = String::from(first);
+        text += &self.digits(10)?;
 
         if self.peek() == '.' && is_digit(self.peek_next()) {
             self.advance(); // Consume the .
+            text.push('.');
+            text += &self.digits(10)?;
+        }
 
-            while is_digit(self.peek()) {
-                self.advance();
+        if matches!(self.peek(), 'e' | 'E') {
+            self.advance();
+            text.push('e');
+            if matches!(self.peek(), '+' | '-') {
+                text.push(self.advance());
+            }
+            if !is_digit(self.peek()) {
+                return Err(String::from("Expected digits in exponent."));
             }
+            text += &self.digits(10)?;
         }
 
-        self.add_token(
-            TokenType::NUMBER,
-            &String::from(&self.source[self.start..self.current]),
-        )
+        let number: f64 = text.parse().unwrap();
+        let exact = match normalize_number(&text) {
+            Some(normalized) => number.to_string() == normalized,
+            None => false,
+        };
+        return Ok((number, exact));
+    }
+
+    fn radix_number(&mut self, radix: u32) -> Result<(f64, bool), String> {
+        let (prefix, name) = if radix == 16 {
+            ("0x", "hex")
+        } else {
+            ("0b", "binary")
+        };
+
+        let digits = self.digits(radix)?;
+        if digits.is_empty() {
+            return Err(format!("Expected {} digits after '{}'.", name, prefix));
+        }
+        if is_alpha_numeric(self.peek()) {
+            return Err(format!(
+                "Invalid digit '{}' in {} number literal.",
+                self.peek(),
+                name
+            ));
+        }
+
+        match u128::from_str_radix(&digits, radix) {
+            Ok(value) => {
+                let number = value as f64;
+                return Ok((number, number as u128 == value));
+            }
+            Err(_) => return Ok((f64::INFINITY, false)),
+        }
     }
 
     fn identifier(&mut self) {
@@ -254,13 +734,50 @@ impl Scanner {
             self.advance();
         }
 
-        let text = String::from(&self.source[self.start..self.current]);
-        let mut token_type = TokenType::IDENTIFIER;
-        if self.keywords.contains_key(&text) {
-            token_type = self.keywords.get(&text).unwrap().clone();
+        let text = &self.source[self.start..self.current];
+        match keywords().get(text) {
+            Some(token_type) => self.add_empty_token(token_type.clone()),
+            None => {
+                let symbol = self.interner.intern(text);
+                self.add_empty_token(TokenType::IDENTIFIER);
+                self.tokens.back_mut().unwrap().symbol = Some(symbol);
+            }
         }
+    }
+}
+
+/// Scans lazily, one token (or error) at a time, ending with the EOF token.
+impl Iterator for Scanner {
+    type Item = Result<Token, String>;
 
-        self.add_empty_token(token_type);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.reported < self.errors.len() {
+                self.reported += 1;
+                return Some(Err(self.errors[self.reported - 1].clone()));
+            }
+            // With trivia, the last token waits for the next one, which
+            // finds where its trailing trivia ends.
+            let waiting = self.trivia && !self.finished && self.tokens.len() == 1;
+            if !waiting {
+                if let Some(token) = self.tokens.pop_front() {
+                    return Some(Ok(token));
+                }
+            }
+            if self.finished {
+                return None;
+            }
+
+            if self.is_at_end() {
+                self.finished = true;
+                let mut token = Token::new(TokenType::EOF, "\0", "\0", self.line);
+                token.offset = self.source.len();
+                self.push_token(token);
+            } else {
+                self.start = self.current;
+                self.scan_token();
+            }
+        }
     }
 }
 
@@ -271,46 +788,126 @@ mod tests {
     #[test]
     fn test_scanner_create() -> Result<(), String> {
         let scanner = Scanner::new(&String::from("Hei"));
-        assert_eq!(scanner.source, "Hei");
+        assert_eq!(&*scanner.source, "Hei");
         assert_eq!(scanner.start, 0);
         assert_eq!(scanner.current, 0);
         assert_eq!(scanner.line, 1);
-        assert_eq!(scanner.tokens.is_empty(), true);
-        assert_eq!(scanner.errors.is_empty(), true);
+        assert!(scanner.tokens.is_empty());
+        assert!(scanner.errors.is_empty());
         Ok(())
     }
 
     #[test]
     fn test_scan_tokens() -> Result<(), String> {
-        let mut scanner = Scanner::new(&String::from("*+}(.,-;"));
+        let mut scanner = Scanner::new(&String::from("*+}(.,-;?:??"));
         let tokens = scanner.scan_tokens().unwrap();
 
-        assert_eq!(tokens.len(), 8);
-        assert_eq!((&tokens[0]).token_type, TokenType::STAR);
-        assert_eq!((&tokens[1]).token_type, TokenType::Plus);
-        assert_eq!((&tokens[2]).token_type, TokenType::RightBrace);
-        assert_eq!((&tokens[3]).token_type, TokenType::LeftParen);
-        assert_eq!((&tokens[4]).token_type, TokenType::Dot);
-        assert_eq!((&tokens[5]).token_type, TokenType::Comma);
-        assert_eq!((&tokens[6]).token_type, TokenType::Minus);
-        assert_eq!((&tokens[7]).token_type, TokenType::SEMICOLON);
+        assert_eq!(tokens.len(), 12);
+        assert_eq!(tokens[0].token_type, TokenType::STAR);
+        assert_eq!(tokens[1].token_type, TokenType::Plus);
+        assert_eq!(tokens[2].token_type, TokenType::RightBrace);
+        assert_eq!(tokens[3].token_type, TokenType::LeftParen);
+        assert_eq!(tokens[4].token_type, TokenType::Dot);
+        assert_eq!(tokens[5].token_type, TokenType::Comma);
+        assert_eq!(tokens[6].token_type, TokenType::Minus);
+        assert_eq!(tokens[7].token_type, TokenType::SEMICOLON);
+        assert_eq!(tokens[8].token_type, TokenType::Question);
+        assert_eq!(tokens[9].token_type, TokenType::Colon);
+        assert_eq!(tokens[10].token_type, TokenType::QuestionQuestion);
+        assert_eq!(tokens[11].token_type, TokenType::EOF);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_iterator() -> Result<(), String> {
+        let mut scanner = Scanner::new("1 € 2");
+
+        assert_eq!(scanner.next().unwrap().unwrap().lexeme, "1");
+        assert!(scanner.tokens.is_empty());
+        assert_eq!(
+            scanner.next().unwrap(),
+            Err(String::from("[line 1] Error: Unexpected character."))
+        );
+        assert_eq!(scanner.next().unwrap().unwrap().lexeme, "2");
+        assert_eq!(scanner.next().unwrap().unwrap().token_type, TokenType::EOF);
+        assert!(scanner.next().is_none());
+        assert!(scanner.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_tokens_errors() -> Result<(), String> {
+        let mut scanner = Scanner::new("1 € 2\n\"\\q\" €");
+
+        assert_eq!(
+            scanner.scan_tokens(),
+            Err(vec![
+                String::from("[line 1] Error: Unexpected character."),
+                String::from(
+                    "[line 2] Error at '\\q': Invalid escape sequence. Did you mean \"\\\\q\"?"
+                ),
+                String::from("[line 2] Error: Unexpected character."),
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unterminated_string() -> Result<(), String> {
+        let mut scanner = Scanner::new("1 \"abc\ndef");
+        let tokens: Vec<Token> = scanner.by_ref().flatten().collect();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type, TokenType::NUMBER);
+        assert_eq!(tokens[1].token_type, TokenType::EOF);
+        assert_eq!(
+            scanner.errors,
+            vec![String::from("[line 2] Error: Unterminated string.")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated_inputs() -> Result<(), String> {
+        let source =
+            "var s = \"a\\n\\u{1F600}\\\"ø\" /* c /* n */ */ 0x1F + 1_000.5e-3 // é\n!= \"\"";
+
+        for (end, _) in source.char_indices().chain([(source.len(), ' ')]) {
+            let mut scanner = Scanner::new(&source[..end]);
+            let tokens: Vec<Token> = scanner.by_ref().flatten().collect();
+
+            let last = tokens.last().unwrap();
+            assert_eq!(last.token_type, TokenType::EOF, "{:?}", &source[..end]);
+            assert!(tokens[..tokens.len() - 1]
+                .iter()
+                .all(|token| token.token_type != TokenType::EOF));
+            assert_eq!(scanner.next(), None);
+        }
 
         Ok(())
     }
 
     #[test]
     fn test_double_operator() -> Result<(), String> {
-        let mut scanner = Scanner::new(&String::from("<+<=+!+=="));
+        let mut scanner = Scanner::new(&String::from("<+<=+!+==+=>"));
         let tokens = scanner.scan_tokens().unwrap();
 
-        assert_eq!(tokens.len(), 7);
-        assert_eq!((&tokens[0]).token_type, TokenType::LESS);
-        assert_eq!((&tokens[1]).token_type, TokenType::Plus);
-        assert_eq!((&tokens[2]).token_type, TokenType::LessEqual);
-        assert_eq!((&tokens[3]).token_type, TokenType::Plus);
-        assert_eq!((&tokens[4]).token_type, TokenType::BANG);
-        assert_eq!((&tokens[5]).token_type, TokenType::Plus);
-        assert_eq!((&tokens[6]).token_type, TokenType::EqualEqual);
+        assert_eq!(tokens.len(), 10);
+        assert_eq!(tokens[0].token_type, TokenType::LESS);
+        assert_eq!(tokens[1].token_type, TokenType::Plus);
+        assert_eq!(tokens[2].token_type, TokenType::LessEqual);
+        assert_eq!(tokens[3].token_type, TokenType::Plus);
+        assert_eq!(tokens[4].token_type, TokenType::BANG);
+        assert_eq!(tokens[5].token_type, TokenType::Plus);
+        assert_eq!(tokens[6].token_type, TokenType::EqualEqual);
+        assert_eq!(tokens[8].token_type, TokenType::FatArrow);
+        assert_eq!(tokens[2].lexeme, "<=");
+        assert_eq!(tokens[6].lexeme, "==");
+        assert_eq!(tokens[8].lexeme, "=>");
 
         Ok(())
     }
@@ -320,7 +917,7 @@ mod tests {
         let mut scanner = Scanner::new(&String::from("+//hello\n+"));
         let tokens = scanner.scan_tokens().unwrap();
 
-        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens.len(), 3);
 
         Ok(())
     }
@@ -330,7 +927,140 @@ mod tests {
         let mut scanner = Scanner::new(&String::from("//hello\n"));
         let tokens = scanner.scan_tokens().unwrap();
 
-        assert_eq!(tokens.len(), 0);
+        assert_eq!(tokens.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_comments() -> Result<(), String> {
+        let mut scanner = Scanner::new("+/* a\n/* nested\n*/ still comment */-\n*");
+        let tokens = scanner.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0].token_type, TokenType::Plus);
+        assert_eq!(tokens[1].token_type, TokenType::Minus);
+        assert_eq!(tokens[1].line, 3);
+        assert_eq!(tokens[2].token_type, TokenType::STAR);
+        assert_eq!(tokens[2].line, 4);
+        assert!(scanner.errors.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_comment_tokens() -> Result<(), String> {
+        let mut scanner = Scanner::new("1 // one\n/* a\nb */ 2");
+        scanner.set_comments(true);
+        let tokens = scanner.scan_tokens().unwrap();
+
+        let comments: Vec<_> = tokens
+            .iter()
+            .filter(|token| token.token_type == TokenType::COMMENT)
+            .map(|token| (token.lexeme.as_str(), token.line))
+            .collect();
+        assert_eq!(comments, vec![("// one", 1), ("/* a\nb */", 3)]);
+        assert_eq!(tokens.len(), 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shebang() -> Result<(), String> {
+        let tokens = Scanner::new("#!/usr/bin/env udyr\n1")
+            .scan_tokens()
+            .unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!((tokens[0].lexeme.as_str(), tokens[0].line), ("1", 2));
+
+        let mut scanner = Scanner::new("#!udyr");
+        scanner.set_comments(true);
+        let tokens = scanner.scan_tokens().unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::COMMENT);
+        assert_eq!(tokens[0].lexeme, "#!udyr");
+
+        let tokens = Scanner::new("1 #!").scan_tokens().unwrap();
+        assert_eq!(tokens[1].token_type, TokenType::Hash);
+        assert_eq!(tokens[2].token_type, TokenType::BANG);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_template() -> Result<(), String> {
+        let mut scanner = Scanner::new("a {\n{{ 1 }}{%\"%}\"%}");
+        scanner.set_template(true);
+        let tokens: Vec<_> = scanner
+            .scan_tokens()
+            .unwrap()
+            .into_iter()
+            .map(|token| (token.token_type, token.lexeme.to_string(), token.line))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenType::TemplateText, String::from("a {\n"), 2),
+                (TokenType::ExpressionOpen, String::from("{{"), 2),
+                (TokenType::NUMBER, String::from("1"), 2),
+                (TokenType::ExpressionClose, String::from("}}"), 2),
+                (TokenType::StatementOpen, String::from("{%"), 2),
+                (TokenType::STRING, String::from("\"%}\""), 2),
+                (TokenType::StatementClose, String::from("%}"), 2),
+                (TokenType::EOF, String::from("\0"), 2),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trivia() -> Result<(), String> {
+        let source = "// sum\n\n1 + /* two */ 2 // done\n  + \"a\nb\" /* c\n */\n";
+        let mut scanner = Scanner::new(source);
+        scanner.set_trivia(true);
+        let tokens = scanner.scan_tokens().unwrap();
+
+        let trivia: Vec<_> = tokens
+            .iter()
+            .map(|token| {
+                let trivia = token.trivia.as_deref().unwrap();
+                (trivia.leading.as_str(), trivia.trailing.as_str())
+            })
+            .collect();
+        assert_eq!(
+            trivia,
+            vec![
+                ("// sum\n\n", " "),
+                ("", " /* two */ "),
+                ("", " // done"),
+                ("\n  ", " "),
+                ("", " /* c\n */"),
+                ("\n", ""),
+            ]
+        );
+        assert_eq!(crate::token::source(&tokens), source);
+
+        for source in ["", "  ", "1", "\n1\n", "1 /* a */\n\n// b\n2 @ 3"] {
+            let mut scanner = Scanner::new(source);
+            scanner.set_trivia(true);
+            let tokens: Vec<Token> = scanner.by_ref().flatten().collect();
+            assert_eq!(crate::token::source(&tokens), source);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() -> Result<(), String> {
+        let mut scanner = Scanner::new("+/* a /* b */\n");
+        let tokens: Vec<Token> = scanner.by_ref().flatten().collect();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(
+            scanner.errors,
+            vec![String::from("[line 2] Error: Unterminated block comment.")]
+        );
 
         Ok(())
     }
@@ -340,10 +1070,10 @@ mod tests {
         let mut scanner = Scanner::new(&String::from("+\n-\n//hello\n/"));
         let tokens = scanner.scan_tokens().unwrap();
 
-        assert_eq!(tokens.len(), 3);
-        assert_eq!((&tokens[0]).line, 1);
-        assert_eq!((&tokens[1]).line, 2);
-        assert_eq!((&tokens[2]).line, 4);
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0].line, 1);
+        assert_eq!(tokens[1].line, 2);
+        assert_eq!(tokens[2].line, 4);
 
         Ok(())
     }
@@ -353,10 +1083,69 @@ mod tests {
         let mut scanner = Scanner::new(&String::from("+\"Hello\"-\"Hello2\""));
         let tokens = scanner.scan_tokens().unwrap();
 
-        assert_eq!(tokens.len(), 4);
-        assert_eq!((&tokens[1]).token_type, TokenType::STRING);
-        assert_eq!((&tokens[1]).literal, "Hello");
-        assert_eq!((&tokens[3]).literal, "Hello2");
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(tokens[1].token_type, TokenType::STRING);
+        assert_eq!(tokens[1].literal, "Hello");
+        assert_eq!(tokens[3].literal, "Hello2");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_valid_escapes() -> Result<(), String> {
+        let mut scanner = Scanner::new("\"a\\n\\t\\r\\0\\\\\\\"b\"");
+        let tokens = scanner.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type, TokenType::STRING);
+        assert_eq!(tokens[0].literal, "a\n\t\r\0\\\"b");
+        assert!(scanner.errors.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unicode_escapes() -> Result<(), String> {
+        let mut scanner = Scanner::new("\"\\u{48}i \\u{1F600}\"");
+        let tokens = scanner.scan_tokens().unwrap();
+
+        assert_eq!(tokens[0].literal, "Hi \u{1F600}");
+        assert!(scanner.errors.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_unicode_escapes() -> Result<(), String> {
+        for source in [
+            "\"\\u41\"",
+            "\"\\u{41\"",
+            "\"\\u{}\"",
+            "\"\\u{110000}\"",
+            "\"\\u{D800}\"",
+            "\"\\u{0000041}\"",
+        ] {
+            let mut scanner = Scanner::new(source);
+            let tokens: Vec<Token> = scanner.by_ref().flatten().collect();
+
+            assert_eq!(tokens[0].token_type, TokenType::STRING);
+            assert_eq!(scanner.errors.len(), 1, "{}", source);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_escape() -> Result<(), String> {
+        let mut scanner = Scanner::new("\"a\\qb\"");
+        let tokens: Vec<Token> = scanner.by_ref().flatten().collect();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(scanner.errors.len(), 1);
+        assert_eq!(
+            scanner.errors[0],
+            "[line 1] Error at '\\q': Invalid escape sequence. Did you mean \"\\\\q\"?"
+        );
 
         Ok(())
     }
@@ -366,10 +1155,219 @@ mod tests {
         let mut scanner = Scanner::new(&String::from("123+123.123"));
         let tokens = scanner.scan_tokens().unwrap();
 
-        assert_eq!(tokens.len(), 3);
-        assert_eq!((&tokens[0]).literal, "123");
-        assert_eq!((&tokens[1]).token_type, TokenType::Plus);
-        assert_eq!((&tokens[2]).literal, "123.123");
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0].literal, "123");
+        assert_eq!(tokens[1].token_type, TokenType::Plus);
+        assert_eq!(tokens[2].literal, "123.123");
+
+        Ok(())
+    }
+
+    // Without `bigint`, large int literals are floats.
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn test_number_out_of_range() -> Result<(), String> {
+        let huge = format!("1{}", "0".repeat(400));
+        let mut scanner = Scanner::new(&format!("1 + {}", huge));
+        let tokens: Vec<Token> = scanner.by_ref().flatten().collect();
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(scanner.errors.len(), 1);
+        assert!(scanner.errors[0].starts_with(&format!("[line 1] Error at '{}'", huge)));
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn test_number_precision_strict() -> Result<(), String> {
+        let source = "18446744073709551617 + 9007199254740993 + 0.50 + 007 + 0.1000000000000000001";
+
+        let mut scanner = Scanner::new(source);
+        assert_eq!(
+            scanner.scan_tokens().unwrap_err(),
+            vec![String::from(
+                "[line 1] Error at '18446744073709551617': Integer literal is too large to be represented."
+            )]
+        );
+
+        let mut scanner = Scanner::new(source);
+        scanner.set_strict(true);
+        assert!(scanner.scan_tokens().is_err());
+        assert_eq!(scanner.errors.len(), 2);
+        assert!(scanner.errors[0].contains("'18446744073709551617'"));
+        assert!(scanner.errors[1].contains("'0.1000000000000000001'"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_number_formats() -> Result<(), String> {
+        let mut scanner = Scanner::new("1e9 2.5e-3 1_000_000 0xFF 0b1010 1E+2 0x_ff_ff 007");
+        let tokens = scanner.scan_tokens().unwrap();
+
+        let literals: Vec<&str> = tokens[..8].iter().map(|t| t.literal.as_str()).collect();
+        assert_eq!(
+            literals,
+            vec![
+                "1000000000.0",
+                "0.0025",
+                "1000000",
+                "255",
+                "10",
+                "100.0",
+                "65535",
+                "7"
+            ]
+        );
+        assert!(scanner.errors.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_numbers() -> Result<(), String> {
+        for (source, message) in [
+            ("1e", "[line 1] Error at '1e': Expected digits in exponent."),
+            (
+                "1e+",
+                "[line 1] Error at '1e+': Expected digits in exponent.",
+            ),
+            (
+                "0x",
+                "[line 1] Error at '0x': Expected hex digits after '0x'.",
+            ),
+            (
+                "0b",
+                "[line 1] Error at '0b': Expected binary digits after '0b'.",
+            ),
+            (
+                "0b102",
+                "[line 1] Error at '0b102': Invalid digit '2' in binary number literal.",
+            ),
+            (
+                "1__0",
+                "[line 1] Error at '1__0': Expected a digit after '_' in number literal.",
+            ),
+            (
+                "1_",
+                "[line 1] Error at '1_': Expected a digit after '_' in number literal.",
+            ),
+        ] {
+            let mut scanner = Scanner::new(source);
+            let tokens: Vec<Token> = scanner.by_ref().flatten().collect();
+
+            assert_eq!(tokens.len(), 1, "{}", source);
+            assert_eq!(scanner.errors, vec![String::from(message)]);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn test_number_precision_strict_formats() -> Result<(), String> {
+        let mut scanner = Scanner::new("1e300 1.5e-1 12.5e1 0x20000000000000001 1e-400");
+        scanner.set_strict(true);
+        assert!(scanner.scan_tokens().is_err());
+
+        assert_eq!(scanner.errors.len(), 2);
+        assert_eq!(
+            scanner.errors[0],
+            "[line 1] Error at '0x20000000000000001': Integer literal is too large to be represented."
+        );
+        assert!(scanner.errors[1].contains("'1e-400'"));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_big_int_literals() -> Result<(), String> {
+        let mut scanner =
+            Scanner::new("18446744073709551617 0x20000000000000001 0.1000000000000000001");
+        scanner.set_strict(true);
+        let tokens: Vec<Token> = scanner.by_ref().flatten().collect();
+
+        assert_eq!(tokens[0].literal, "18446744073709551617");
+        assert_eq!(tokens[1].literal, "36893488147419103233");
+        assert_eq!(scanner.errors.len(), 1);
+        assert!(scanner.errors[0].contains("'0.1000000000000000001'"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_identifier_symbols() -> Result<(), String> {
+        let mut scanner = Scanner::new("a + b + a + var");
+        let tokens = scanner.scan_tokens().unwrap();
+
+        let a = tokens[0].symbol.unwrap();
+        let b = tokens[2].symbol.unwrap();
+        assert_ne!(a, b);
+        assert_eq!(tokens[4].symbol, Some(a));
+        assert_eq!(tokens[6].symbol, None);
+        assert_eq!(scanner.interner().len(), 2);
+        assert_eq!(scanner.interner().resolve(b), "b");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_offsets() -> Result<(), String> {
+        let source = "ø + \"å\"\n  <= 1";
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+
+        let offsets: Vec<usize> = tokens.iter().map(|token| token.offset).collect();
+        assert_eq!(offsets, vec![0, 3, 5, 12, 15, source.len()]);
+        for token in &tokens[..tokens.len() - 1] {
+            assert_eq!(token.lexeme, &source[token.offset..][..token.lexeme.len()]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_source() -> Result<(), String> {
+        let source: Arc<str> = Arc::from("ø + \"å\" + 1_0 + 10 + \"\\n\"");
+        let tokens = Scanner::shared(Arc::clone(&source)).scan_tokens().unwrap();
+        let in_source = |text: &str| source.as_bytes().as_ptr_range().contains(&text.as_ptr());
+
+        for token in &tokens[..tokens.len() - 1] {
+            assert!(in_source(&token.lexeme), "{:?}", token.lexeme);
+        }
+        let literals: Vec<bool> = tokens
+            .iter()
+            .map(|token| in_source(&token.literal))
+            .collect();
+        // Only `1_0`, written as `10`, and the escaped string are copied.
+        assert_eq!(
+            literals,
+            vec![true, true, true, true, false, true, true, true, false, false]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unicode_source() -> Result<(), String> {
+        let mut scanner = Scanner::new("π + naïve_2 - \"smørbrød 😀\"\n€ *");
+        let tokens: Vec<Token> = scanner.by_ref().flatten().collect();
+
+        assert_eq!(tokens.len(), 7);
+        assert_eq!(tokens[0].token_type, TokenType::IDENTIFIER);
+        assert_eq!(tokens[0].lexeme, "π");
+        assert_eq!(tokens[2].token_type, TokenType::IDENTIFIER);
+        assert_eq!(tokens[2].lexeme, "naïve_2");
+        assert_eq!(tokens[4].token_type, TokenType::STRING);
+        assert_eq!(tokens[4].lexeme, "\"smørbrød 😀\"");
+        assert_eq!(tokens[4].literal, "smørbrød 😀");
+        assert_eq!(tokens[5].token_type, TokenType::STAR);
+        assert_eq!(tokens[5].line, 2);
+        assert_eq!(
+            scanner.errors,
+            vec![String::from("[line 2] Error: Unexpected character.")]
+        );
 
         Ok(())
     }
@@ -380,16 +1378,17 @@ mod tests {
             Scanner::new(&String::from("var + myClass - class + superFres // var \n"));
         let tokens = scanner.scan_tokens().unwrap();
 
-        assert_eq!(tokens.len(), 7);
-        assert_eq!((&tokens[0]).token_type, TokenType::VAR);
-        assert_eq!((&tokens[1]).token_type, TokenType::Plus);
-        assert_eq!((&tokens[2]).token_type, TokenType::IDENTIFIER);
-        assert_eq!((&tokens[2]).lexeme, "myClass");
-        assert_eq!((&tokens[3]).token_type, TokenType::Minus);
-        assert_eq!((&tokens[4]).token_type, TokenType::CLASS);
-        assert_eq!((&tokens[5]).token_type, TokenType::Plus);
-        assert_eq!((&tokens[6]).token_type, TokenType::IDENTIFIER);
-        assert_eq!((&tokens[6]).lexeme, "superFres");
+        assert_eq!(tokens.len(), 8);
+        assert_eq!(tokens[0].token_type, TokenType::VAR);
+        assert_eq!(tokens[0].lexeme, "var");
+        assert_eq!(tokens[1].token_type, TokenType::Plus);
+        assert_eq!(tokens[2].token_type, TokenType::IDENTIFIER);
+        assert_eq!(tokens[2].lexeme, "myClass");
+        assert_eq!(tokens[3].token_type, TokenType::Minus);
+        assert_eq!(tokens[4].token_type, TokenType::CLASS);
+        assert_eq!(tokens[5].token_type, TokenType::Plus);
+        assert_eq!(tokens[6].token_type, TokenType::IDENTIFIER);
+        assert_eq!(tokens[6].lexeme, "superFres");
 
         Ok(())
     }
@@ -0,0 +1,280 @@
+//! The `.udyrc` format for compiled chunks. All integers are little endian:
+//!
+//! ```text
+//! magic "UDYR", version: u8
+//! constant count: u32, then per constant a tag byte and its payload
+//! code length: u32, the code bytes, then the source line of every byte as u32
+//! ```
+
+use crate::{
+    chunk::{Chunk, OpCode},
+    value::Value,
+};
+
+pub const MAGIC: &[u8; 4] = b"UDYR";
+pub const VERSION: u8 = 2;
+
+const TAG_NIL: u8 = 0;
+const TAG_BOOL: u8 = 1;
+const TAG_NUMBER: u8 = 2;
+const TAG_STRING: u8 = 3;
+const TAG_INT: u8 = 4;
+/// Only written and read with the `bigint` feature.
+#[cfg(feature = "bigint")]
+const TAG_BIG_INT: u8 = 5;
+
+pub fn serialize(chunk: &Chunk) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+
+    write_u32(&mut bytes, chunk.constants.len());
+    for constant in &chunk.constants {
+        match constant {
+            Value::Nil => bytes.push(TAG_NIL),
+            Value::Bool(value) => bytes.extend([TAG_BOOL, *value as u8]),
+            Value::Int(value) => {
+                bytes.push(TAG_INT);
+                bytes.extend(value.to_le_bytes());
+            }
+            #[cfg(feature = "bigint")]
+            Value::BigInt(value) => {
+                let value = value.to_signed_bytes_le();
+                bytes.push(TAG_BIG_INT);
+                write_u32(&mut bytes, value.len());
+                bytes.extend(value);
+            }
+            Value::Number(value) => {
+                bytes.push(TAG_NUMBER);
+                bytes.extend(value.to_le_bytes());
+            }
+            Value::String(value) => {
+                bytes.push(TAG_STRING);
+                write_u32(&mut bytes, value.len());
+                bytes.extend(value.as_bytes());
+            }
+            Value::Tuple(_) => unreachable!("tuples are built at runtime, never constants"),
+        }
+    }
+
+    write_u32(&mut bytes, chunk.code.len());
+    bytes.extend(&chunk.code);
+    for line in &chunk.lines {
+        write_u32(&mut bytes, *line);
+    }
+    return bytes;
+}
+
+/// Reads a chunk written by [`serialize`], checking that the code is safe
+/// for the VM to run.
+pub fn deserialize(bytes: &[u8]) -> Result<Chunk, String> {
+    let mut reader = Reader { bytes, position: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(String::from("Not a udyr bytecode file."));
+    }
+    let version = reader.take(1)?[0];
+    if version != VERSION {
+        return Err(format!("Unsupported bytecode version {}.", version));
+    }
+
+    let mut chunk = Chunk::new();
+    for _ in 0..reader.u32()? {
+        let constant = match reader.take(1)?[0] {
+            TAG_NIL => Value::Nil,
+            TAG_BOOL => Value::Bool(reader.take(1)?[0] != 0),
+            TAG_INT => Value::Int(i64::from_le_bytes(reader.take(8)?.try_into().unwrap())),
+            #[cfg(feature = "bigint")]
+            TAG_BIG_INT => {
+                let length = reader.u32()?;
+                Value::BigInt(Box::new(num_bigint::BigInt::from_signed_bytes_le(
+                    reader.take(length)?,
+                )))
+            }
+            TAG_NUMBER => Value::Number(f64::from_le_bytes(reader.take(8)?.try_into().unwrap())),
+            TAG_STRING => {
+                let length = reader.u32()?;
+                let string = std::str::from_utf8(reader.take(length)?)
+                    .map_err(|_| String::from("Invalid UTF-8 in string constant."))?;
+                Value::from(string)
+            }
+            tag => return Err(format!("Invalid constant tag {}.", tag)),
+        };
+        chunk.constants.push(constant);
+    }
+
+    let length = reader.u32()?;
+    chunk.code = reader.take(length)?.to_vec();
+    for _ in 0..length {
+        chunk.lines.push(reader.u32()?);
+    }
+    if reader.position != bytes.len() {
+        return Err(String::from("Unexpected data after the bytecode."));
+    }
+
+    verify(&chunk)?;
+    return Ok(chunk);
+}
+
+/// Checks that every instruction is known and complete, that constants
+/// exist, that jumps land on the start of an instruction, and that the
+/// code ends with `Return`.
+fn verify(chunk: &Chunk) -> Result<(), String> {
+    let invalid = |offset: usize| format!("Invalid bytecode at offset {}.", offset);
+    let code = &chunk.code;
+
+    let mut offset = 0;
+    let mut last = None;
+    let mut starts = vec![false; code.len()];
+    // Each jump and where it lands, checked once all starts are known.
+    let mut jumps = Vec::new();
+    while offset < code.len() {
+        starts[offset] = true;
+        let op = OpCode::from_byte(code[offset]).ok_or_else(|| invalid(offset))?;
+        let size = match op {
+            OpCode::Constant
+            | OpCode::GetProperty
+            | OpCode::Tuple
+            | OpCode::GetLocal
+            | OpCode::Unbind
+            | OpCode::MatchTuple => 2,
+            OpCode::Jump
+            | OpCode::JumpIfFalse
+            | OpCode::JumpIfNotNil
+            | OpCode::Invoke
+            | OpCode::CallNative => 3,
+            _ => 1,
+        };
+        if offset + size > code.len() {
+            return Err(invalid(offset));
+        }
+        match op {
+            OpCode::Constant | OpCode::GetProperty | OpCode::Invoke | OpCode::CallNative
+                if code[offset + 1] as usize >= chunk.constants.len() =>
+            {
+                return Err(invalid(offset));
+            }
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::JumpIfNotNil => {
+                let jump = u16::from_be_bytes([code[offset + 1], code[offset + 2]]) as usize;
+                jumps.push((offset, offset + size + jump));
+            }
+            _ => {}
+        }
+        last = Some(op);
+        offset += size;
+    }
+    for (offset, target) in jumps {
+        if !starts.get(target).copied().unwrap_or(false) {
+            return Err(invalid(offset));
+        }
+    }
+
+    if last != Some(OpCode::Return) {
+        return Err(String::from("Bytecode does not end with a return."));
+    }
+    return Ok(());
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend((value as u32).to_le_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() - self.position < count {
+            return Err(String::from("Truncated bytecode file."));
+        }
+        let bytes = &self.bytes[self.position..self.position + count];
+        self.position += count;
+        return Ok(bytes);
+    }
+
+    fn u32(&mut self) -> Result<usize, String> {
+        let bytes = self.take(4)?.try_into().unwrap();
+        return Ok(u32::from_le_bytes(bytes) as usize);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compiler, parser::Parser, scanner::Scanner};
+
+    use super::*;
+
+    fn compile_source(source: &str) -> Chunk {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let program = Parser::new(&tokens).parse().unwrap();
+        compiler::compile(&program).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<(), String> {
+        let mut chunk = compile_source("\"ø\" == (1.5 > 2 ? nil : \"a\" + \"b\")");
+        chunk.add_constant(Value::Bool(true));
+        chunk.add_constant(Value::Nil);
+        #[cfg(feature = "bigint")]
+        chunk.add_constant(Value::number("-123456789012345678901234567890"));
+
+        let bytes = serialize(&chunk);
+        assert_eq!(&bytes[..5], b"UDYR\x02");
+        assert_eq!(deserialize(&bytes)?, chunk);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_files() -> Result<(), String> {
+        let bytes = serialize(&compile_source("1 + 2"));
+
+        assert_eq!(
+            deserialize(b"#!/usr/bin/env udyr").unwrap_err(),
+            "Not a udyr bytecode file."
+        );
+        assert_eq!(
+            deserialize(b"UDYR\x01").unwrap_err(),
+            "Unsupported bytecode version 1."
+        );
+        for length in 0..bytes.len() {
+            assert!(deserialize(&bytes[..length]).is_err());
+        }
+
+        let mut bad_constant = bytes.clone();
+        // Six bytes of code, then a u32 line for each of them.
+        let code_start = bytes.len() - 6 - 6 * 4;
+        bad_constant[code_start + 1] = 2;
+        assert_eq!(
+            deserialize(&bad_constant).unwrap_err(),
+            "Invalid bytecode at offset 0."
+        );
+
+        // The then branch of `nil ? 1 : 2` ends with a jump at offset 7
+        // over the else branch. One byte short, it would land on the
+        // operand of the else branch's `Constant`.
+        let chunk = compile_source("nil ? 1 : 2");
+        assert_eq!(chunk.code[7], OpCode::Jump as u8);
+        let mut bad_jump = serialize(&chunk);
+        let jump_start = bad_jump.len() - chunk.code.len() * 5 + 7;
+        bad_jump[jump_start + 2] -= 1;
+        assert_eq!(
+            deserialize(&bad_jump).unwrap_err(),
+            "Invalid bytecode at offset 7."
+        );
+        bad_jump[jump_start + 2] += 100;
+        assert_eq!(
+            deserialize(&bad_jump).unwrap_err(),
+            "Invalid bytecode at offset 7."
+        );
+
+        let mut no_return = bytes.clone();
+        no_return[code_start + 5] = OpCode::Add as u8;
+        assert_eq!(
+            deserialize(&no_return).unwrap_err(),
+            "Bytecode does not end with a return."
+        );
+
+        Ok(())
+    }
+}
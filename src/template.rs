@@ -0,0 +1,113 @@
+//! Templates for `udyr render`: text with udyr in it. The text is written
+//! as it is, `{{ expression }}` is replaced by the value of the
+//! expression, and `{% expression %}` is run for what it does, like
+//! logging, and leaves nothing. There are no statements in the language
+//! yet, so both hold an expression.
+
+use crate::{
+    error::report,
+    interpreter::Interpreter,
+    scanner::Scanner,
+    token::{Token, TokenType},
+};
+
+/// Renders `template`, running its code with `interpreter`. Errors in the
+/// code are reported at their line in the template.
+pub fn render(template: &str, interpreter: &mut Interpreter) -> Result<String, Vec<String>> {
+    let mut scanner = Scanner::new(template);
+    scanner.set_template(true);
+    let tokens = scanner.scan_tokens()?;
+
+    let mut output = String::new();
+    let mut index = 0;
+    loop {
+        let open = &tokens[index];
+        let close_type = match open.token_type {
+            TokenType::EOF => return Ok(output),
+            TokenType::TemplateText => {
+                output.push_str(&open.literal);
+                index += 1;
+                continue;
+            }
+            TokenType::ExpressionOpen => TokenType::ExpressionClose,
+            _ => TokenType::StatementClose,
+        };
+
+        let length = tokens[index + 1..]
+            .iter()
+            .position(|token| {
+                matches!(
+                    token.token_type,
+                    TokenType::ExpressionClose | TokenType::StatementClose | TokenType::EOF
+                )
+            })
+            .expect("the tokens end with EOF");
+        let close = &tokens[index + 1 + length];
+        if close.token_type != close_type {
+            let expected = match close_type {
+                TokenType::ExpressionClose => "}}",
+                _ => "%}",
+            };
+            let message = format!("Expected '{}' to close '{}'.", expected, open.lexeme);
+            return Err(vec![report(
+                open.line,
+                &format!(" at '{}'", open.lexeme),
+                &message,
+            )]);
+        }
+
+        let mut code = tokens[index + 1..index + 1 + length].to_vec();
+        let mut end = Token::new(TokenType::EOF, "\0", "\0", close.line);
+        end.offset = close.offset;
+        code.push(end);
+        let value = interpreter.eval_tokens(&code)?;
+        if close_type == TokenType::ExpressionClose {
+            output.push_str(&value.to_string());
+        }
+        index += length + 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered(template: &str) -> Result<String, Vec<String>> {
+        render(template, &mut Interpreter::new())
+    }
+
+    #[test]
+    fn test_render() -> Result<(), Vec<String>> {
+        assert_eq!(
+            rendered("Sum: {{ 1 + 2 }}, {{\"a\" + \"}}\"}}!\n")?,
+            "Sum: 3, a}}!\n"
+        );
+        assert_eq!(rendered("a{% 1 + 2 %}b")?, "ab");
+        assert_eq!(rendered("{ not code }")?, "{ not code }");
+        assert_eq!(rendered("")?, "");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> Result<(), Vec<String>> {
+        assert_eq!(
+            rendered("a\n{{ 1 +\n}}").unwrap_err(),
+            vec!["[line 3] Error at end: Expected expression."]
+        );
+        assert_eq!(
+            rendered("\n{{ 1 %}").unwrap_err(),
+            vec!["[line 2] Error at '{{': Expected '}}' to close '{{'."]
+        );
+        assert_eq!(
+            rendered("{% 1").unwrap_err(),
+            vec!["[line 1] Error at '{%': Expected '%}' to close '{%'."]
+        );
+        assert_eq!(
+            rendered("{{ -\"a\" }}").unwrap_err(),
+            vec!["[line 1] Error: Operand must be a number."]
+        );
+
+        Ok(())
+    }
+}
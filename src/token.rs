@@ -1,10 +1,20 @@
-#[derive(Debug, Clone, PartialEq)]
+use std::{
+    fmt,
+    ops::{Deref, Range},
+    sync::Arc,
+};
+
+use crate::interner::Symbol;
+
+#[derive(Debug, Clone, PartialEq, Default)]
 pub enum TokenType {
     // Single-character tokens.
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -12,6 +22,9 @@ pub enum TokenType {
     SEMICOLON,
     SLASH,
     STAR,
+    Question,
+    Colon,
+    Hash,
 
     // One or two character tokens.
     BANG,
@@ -22,6 +35,9 @@ pub enum TokenType {
     GreaterEqual,
     LESS,
     LessEqual,
+    QuestionQuestion,
+    /// `=>`
+    FatArrow,
 
     // Literals.
     IDENTIFIER,
@@ -36,6 +52,7 @@ pub enum TokenType {
     FUN,
     FOR,
     IF,
+    MATCH,
     NIL,
     OR,
     PRINT,
@@ -46,40 +63,180 @@ pub enum TokenType {
     VAR,
     WHILE,
 
+    // Only scanned when asked for.
+    COMMENT,
+
+    // Only scanned in templates.
+    TemplateText,
+    /// `{{`
+    ExpressionOpen,
+    /// `}}`
+    ExpressionClose,
+    /// `{%`
+    StatementOpen,
+    /// `%}`
+    StatementClose,
+
     EOF,
+    #[default]
     None,
 }
 
-impl Default for TokenType {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
 #[derive(Clone, Debug, PartialEq)]
 pub struct Token {
     pub(crate) token_type: TokenType,
-    pub(crate) lexeme: String,
-    pub(crate) literal: String,
+    pub(crate) lexeme: Substr,
+    pub(crate) literal: Substr,
     pub(crate) line: usize,
+    /// The interned name of an IDENTIFIER token.
+    pub(crate) symbol: Option<Symbol>,
+    /// Where the lexeme starts in the source, in bytes.
+    pub(crate) offset: usize,
+    /// The whitespace and comments around the token, when scanned with
+    /// trivia. Boxed, as the parser keeps many tokens on the stack.
+    pub(crate) trivia: Option<Box<Trivia>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trivia {
+    /// The whitespace and comments before the token that do not trail the
+    /// token before it.
+    pub leading: String,
+    /// The whitespace and comments after the token, up to the end of its
+    /// line.
+    pub trailing: String,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: &String, literal: &String, line: usize) -> Token {
+    pub fn new(token_type: TokenType, lexeme: &str, literal: &str, line: usize) -> Token {
         return Token {
             token_type,
-            lexeme: lexeme.clone(),
-            literal: literal.clone(),
+            lexeme: Substr::from(lexeme),
+            literal: Substr::from(literal),
             line,
+            symbol: None,
+            offset: 0,
+            trivia: None,
         };
     }
 
+    /// The bytes of the source the token covers.
+    pub fn span(&self) -> Range<usize> {
+        return self.offset..self.offset + self.lexeme.len();
+    }
+
     pub fn empty() -> Token {
         Token {
             token_type: TokenType::None,
-            lexeme: String::from(""),
-            literal: String::from(""),
+            lexeme: Substr::from(""),
+            literal: Substr::from(""),
             line: 0,
+            symbol: None,
+            offset: 0,
+            trivia: None,
+        }
+    }
+}
+
+/// Text of a token: a range of the source it was scanned from, which all
+/// its tokens share, so scanning copies no text. Text that is not in the
+/// source, like a string literal with escapes, gets a source of its own.
+#[derive(Clone)]
+pub struct Substr {
+    source: Arc<str>,
+    start: u32,
+    end: u32,
+}
+
+impl Substr {
+    /// Bytes `start..end` of `source`, which must be on character
+    /// boundaries. Sources are limited to 4 GiB.
+    pub(crate) fn new(source: &Arc<str>, start: usize, end: usize) -> Substr {
+        assert!(source.is_char_boundary(start) && source.is_char_boundary(end));
+        let offset = |offset: usize| u32::try_from(offset).expect("source is over 4 GiB");
+        return Substr {
+            source: Arc::clone(source),
+            start: offset(start),
+            end: offset(end),
+        };
+    }
+
+    pub fn as_str(&self) -> &str {
+        return &self.source[self.start as usize..self.end as usize];
+    }
+
+    /// The same text in `to`, which is `from` edited, with everything from
+    /// here on moved by `shift` bytes. Text not in `from` is kept as is.
+    pub(crate) fn moved(&self, from: &Arc<str>, to: &Arc<str>, shift: isize) -> Substr {
+        if !Arc::ptr_eq(&self.source, from) {
+            return self.clone();
+        }
+        let start = (self.start as isize + shift) as usize;
+        return Substr::new(to, start, start + self.len());
+    }
+}
+
+impl Deref for Substr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        return self.as_str();
+    }
+}
+
+impl From<&str> for Substr {
+    fn from(text: &str) -> Substr {
+        return Substr::from(String::from(text));
+    }
+}
+
+impl From<String> for Substr {
+    fn from(text: String) -> Substr {
+        let end = text.len();
+        return Substr::new(&Arc::from(text), 0, end);
+    }
+}
+
+impl fmt::Display for Substr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return fmt::Display::fmt(self.as_str(), f);
+    }
+}
+
+impl fmt::Debug for Substr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return fmt::Debug::fmt(self.as_str(), f);
+    }
+}
+
+impl PartialEq for Substr {
+    fn eq(&self, other: &Substr) -> bool {
+        return self.as_str() == other.as_str();
+    }
+}
+
+impl PartialEq<str> for Substr {
+    fn eq(&self, other: &str) -> bool {
+        return self.as_str() == other;
+    }
+}
+
+impl PartialEq<&str> for Substr {
+    fn eq(&self, other: &&str) -> bool {
+        return self.as_str() == *other;
+    }
+}
+
+/// Writes tokens scanned with trivia back as the source they came from.
+pub fn source(tokens: &[Token]) -> String {
+    let mut source = String::new();
+    for token in tokens {
+        let trivia = token.trivia.as_deref();
+        source.push_str(trivia.map_or("", |trivia| &trivia.leading));
+        if token.token_type != TokenType::EOF {
+            source.push_str(&token.lexeme);
         }
+        source.push_str(trivia.map_or("", |trivia| &trivia.trailing));
     }
+    return source;
 }
@@ -0,0 +1,63 @@
+use std::io::Write;
+
+use crate::{chunk::Chunk, disassembler, value::Value, vm::Observer};
+
+/// Writes every instruction the VM runs, after the stack it runs on:
+///
+/// ```text
+///           [ 1 ][ 2 ]
+/// 0004    | OP_ADD
+/// ```
+pub struct Tracer<W: Write> {
+    output: W,
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(output: W) -> Tracer<W> {
+        return Tracer { output };
+    }
+}
+
+impl<W: Write> Observer for Tracer<W> {
+    fn before_instruction(&mut self, chunk: &Chunk, offset: usize, stack: &[Value]) {
+        let mut trace = String::from("          ");
+        for value in stack {
+            trace.push_str(&format!("[ {} ]", value));
+        }
+        trace.push('\n');
+        disassembler::disassemble_instruction(chunk, offset, &mut trace);
+        // A trace that cannot be written is not worth stopping the program for.
+        let _ = self.output.write_all(trace.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compiler, parser::Parser, scanner::Scanner, vm::Vm};
+
+    use super::*;
+
+    #[test]
+    fn test_trace() -> Result<(), String> {
+        let tokens = Scanner::new("-1 + \"a\"").scan_tokens().unwrap();
+        let chunk = compiler::compile(&Parser::new(&tokens).parse()?)?;
+        let mut output = Vec::new();
+
+        let result = Vm::new().interpret_with(&chunk, &mut Tracer::new(&mut output));
+
+        assert!(result.is_err());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "          \n\
+             0000    1 OP_CONSTANT         0 '1'\n          \
+             [ 1 ]\n\
+             0002    | OP_NEGATE\n          \
+             [ -1 ]\n\
+             0003    | OP_CONSTANT         1 'a'\n          \
+             [ -1 ][ a ]\n\
+             0005    | OP_ADD\n"
+        );
+
+        Ok(())
+    }
+}
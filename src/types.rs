@@ -0,0 +1,294 @@
+use std::fmt;
+
+use crate::{
+    ast::{Ast, Expr, ExprId, Program, Stmt},
+    error::{report_at, Diagnostic},
+    token::{Token, TokenType},
+};
+
+/// The static type of an expression, as far as it can be told without
+/// running the program.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    Number,
+    String,
+    Bool,
+    Nil,
+    Tuple,
+    Unknown,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Type::Number => "number",
+            Type::String => "string",
+            Type::Bool => "bool",
+            Type::Nil => "nil",
+            Type::Tuple => "tuple",
+            Type::Unknown => "unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Reports operations that are certain to fail at runtime, like `"a" - 1`.
+/// Anything involving a type that is not known is given the benefit of the
+/// doubt.
+pub fn check(program: &Program) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        ast: &program.ast,
+        errors: Vec::new(),
+    };
+    for stmt in &program.statements {
+        match stmt {
+            Stmt::Expression(expr) => {
+                checker.infer(*expr);
+            }
+        }
+    }
+    return checker.errors;
+}
+
+struct Checker<'a> {
+    ast: &'a Ast,
+    errors: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn infer(&mut self, id: ExprId) -> Type {
+        let ast = self.ast;
+        match &ast[id] {
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.infer(*left);
+                let right = self.infer(*right);
+                return self.binary(operator, left, right);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.infer(*callee);
+                for argument in arguments {
+                    self.infer(*argument);
+                }
+                return Type::Unknown;
+            }
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.infer(*condition);
+                let then_type = self.infer(*then_branch);
+                let else_type = self.infer(*else_branch);
+                if then_type == else_type {
+                    return then_type;
+                }
+                return Type::Unknown;
+            }
+            Expr::Get { object, name } => {
+                let object = self.infer(*object);
+                if name.lexeme == "length" && matches!(object, Type::String | Type::Tuple) {
+                    return Type::Number;
+                }
+                return Type::Unknown;
+            }
+            Expr::Grouping(expr) => return self.infer(*expr),
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = self.infer(*object);
+                let index = self.infer(*index);
+                if !matches!(object, Type::Tuple | Type::String | Type::Unknown) {
+                    self.error(bracket, "Only tuples and strings can be indexed.");
+                } else if !matches!(index, Type::Number | Type::Unknown) {
+                    self.error(bracket, "Index must be an int.");
+                }
+                if object == Type::String {
+                    return Type::String;
+                }
+                return Type::Unknown;
+            }
+            Expr::Literal(token) => match token.token_type {
+                TokenType::NUMBER => return Type::Number,
+                TokenType::STRING => return Type::String,
+                TokenType::TRUE | TokenType::FALSE => return Type::Bool,
+                _ => return Type::Nil,
+            },
+            Expr::Match { subject, arms, .. } => {
+                self.infer(*subject);
+                let mut types = Vec::new();
+                for arm in arms {
+                    if let Some(guard) = arm.guard {
+                        self.infer(guard);
+                    }
+                    types.push(self.infer(arm.body));
+                }
+                if types.iter().all(|t| *t == types[0]) {
+                    return types[0];
+                }
+                return Type::Unknown;
+            }
+            Expr::Tuple { elements, .. } => {
+                for element in elements {
+                    self.infer(*element);
+                }
+                return Type::Tuple;
+            }
+            Expr::Unary { operator, right } => {
+                let right = self.infer(*right);
+                if operator.token_type == TokenType::BANG {
+                    return Type::Bool;
+                }
+                if !matches!(right, Type::Number | Type::Unknown) {
+                    self.error(operator, "Operand must be a number.");
+                }
+                return Type::Number;
+            }
+            Expr::Variable(_) => return Type::Unknown,
+        }
+    }
+
+    fn binary(&mut self, operator: &Token, left: Type, right: Type) -> Type {
+        match operator.token_type {
+            TokenType::QuestionQuestion => match left {
+                Type::Nil => return right,
+                Type::Unknown => return Type::Unknown,
+                _ => return left,
+            },
+            TokenType::EqualEqual | TokenType::BangEqual => return Type::Bool,
+            TokenType::Plus => {
+                let addable = |t: Type| matches!(t, Type::Number | Type::String | Type::Unknown);
+                let mismatched = matches!(
+                    (left, right),
+                    (Type::Number, Type::String) | (Type::String, Type::Number)
+                );
+                if !addable(left) || !addable(right) || mismatched {
+                    self.error(operator, "Operands must be two numbers or two strings.");
+                    return Type::Unknown;
+                }
+                if left == Type::Unknown {
+                    return right;
+                }
+                return left;
+            }
+            _ => {
+                let number = |t: Type| matches!(t, Type::Number | Type::Unknown);
+                if !number(left) || !number(right) {
+                    self.error(operator, "Operands must be numbers.");
+                }
+                match operator.token_type {
+                    TokenType::Minus | TokenType::STAR | TokenType::SLASH => return Type::Number,
+                    _ => return Type::Bool,
+                }
+            }
+        }
+    }
+
+    fn error(&mut self, operator: &Token, message: &str) {
+        let location = format!(" at '{}'", operator.lexeme);
+        self.errors.push(report_at(operator, &location, message));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::Parser, scanner::Scanner};
+
+    use super::*;
+
+    fn check_source(source: &str) -> Vec<String> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let errors = check(&Parser::new(&tokens).parse().unwrap());
+        errors.into_iter().map(String::from).collect()
+    }
+
+    fn infer(source: &str) -> Type {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let mut parser = Parser::new(&tokens);
+        let expr = parser.parse_expression().unwrap();
+        let mut checker = Checker {
+            ast: &parser.ast,
+            errors: Vec::new(),
+        };
+        checker.infer(expr)
+    }
+
+    #[test]
+    fn test_infer() -> Result<(), String> {
+        assert_eq!(infer("1 + 2 * 3"), Type::Number);
+        assert_eq!(infer("\"a\" + \"b\""), Type::String);
+        assert_eq!(infer("x + \"b\""), Type::String);
+        assert_eq!(infer("!x"), Type::Bool);
+        assert_eq!(infer("1 < x"), Type::Bool);
+        assert_eq!(infer("x ? 1 : 2"), Type::Number);
+        assert_eq!(infer("x ? 1 : nil"), Type::Unknown);
+        assert_eq!(infer("(nil)"), Type::Nil);
+        assert_eq!(infer("(nil,)"), Type::Tuple);
+        assert_eq!(infer("nil ?? \"a\""), Type::String);
+        assert_eq!(infer("1 ?? x"), Type::Number);
+        assert_eq!(infer("x ?? 1"), Type::Unknown);
+        assert_eq!(infer("(1, 2)[x]"), Type::Unknown);
+        assert_eq!(infer("\"ab\"[x]"), Type::String);
+        assert_eq!(infer("(1, 2).length"), Type::Number);
+        assert_eq!(infer("\"ab\".trim()"), Type::Unknown);
+        assert_eq!(infer("match x { (a,) => a + 1, _ => 0 }"), Type::Number);
+        assert_eq!(infer("match x { 1 => \"one\", _ => nil }"), Type::Unknown);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_errors() -> Result<(), String> {
+        assert_eq!(
+            check_source("\"a\" - 1"),
+            vec![String::from(
+                "[line 1] Error at '-': Operands must be numbers."
+            )]
+        );
+        assert_eq!(
+            check_source("1 +\n(true ? \"a\" : \"b\")"),
+            vec![String::from(
+                "[line 1] Error at '+': Operands must be two numbers or two strings."
+            )]
+        );
+        assert_eq!(
+            check_source("-nil"),
+            vec![String::from(
+                "[line 1] Error at '-': Operand must be a number."
+            )]
+        );
+        assert_eq!(check_source("(1 < true) + nil").len(), 2);
+        assert_eq!(
+            check_source("true[0]"),
+            vec![String::from(
+                "[line 1] Error at '[': Only tuples and strings can be indexed."
+            )]
+        );
+        assert_eq!(
+            check_source("(1, 2)[\"0\"] + (3,)"),
+            vec![
+                String::from("[line 1] Error at '[': Index must be an int."),
+                String::from("[line 1] Error at '+': Operands must be two numbers or two strings."),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_types_pass() -> Result<(), String> {
+        assert!(check_source("x - 1").is_empty());
+        assert!(check_source("x + y").is_empty());
+        assert!(check_source("-x * (y ? 1 : 2)").is_empty());
+        assert!(check_source("1 == \"a\"").is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,402 @@
+use crate::{
+    ast::{Ast, Expr, ExprId, Pattern},
+    parser::{Associativity, BINARY_OPERATORS},
+};
+
+/// Writes an expression back as source, adding the parentheses that
+/// precedence and associativity require beyond its own groupings.
+/// Parsing the result gives back the same tree, apart from the added
+/// groupings.
+pub fn unparse(ast: &Ast, id: ExprId) -> String {
+    match &ast[id] {
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        } => {
+            let (precedence, associativity) = binding(&ast[id]).unwrap();
+            let left_parens = match binding(&ast[*left]) {
+                Some((left, _)) => {
+                    left < precedence
+                        || (left == precedence && associativity == Associativity::Right)
+                }
+                None => matches!(ast[*left], Expr::Conditional { .. }),
+            };
+            let right_parens = match binding(&ast[*right]) {
+                Some((right, _)) => {
+                    right < precedence
+                        || (right == precedence && associativity == Associativity::Left)
+                }
+                None => matches!(ast[*right], Expr::Conditional { .. }),
+            };
+            return format!(
+                "{} {} {}",
+                parenthesized(ast, *left, left_parens),
+                operator.lexeme,
+                parenthesized(ast, *right, right_parens)
+            );
+        }
+        Expr::Call {
+            callee, arguments, ..
+        } => {
+            let arguments: Vec<String> = arguments
+                .iter()
+                .map(|argument| unparse(ast, *argument))
+                .collect();
+            return format!("{}({})", operand(ast, *callee), arguments.join(", "));
+        }
+        Expr::Conditional {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            let condition_parens = matches!(ast[*condition], Expr::Conditional { .. });
+            return format!(
+                "{} ? {} : {}",
+                parenthesized(ast, *condition, condition_parens),
+                unparse(ast, *then_branch),
+                unparse(ast, *else_branch)
+            );
+        }
+        Expr::Get { object, name } => {
+            return format!("{}.{}", operand(ast, *object), name.lexeme);
+        }
+        Expr::Grouping(expr) => return parenthesized(ast, *expr, true),
+        Expr::Index { object, index, .. } => {
+            return format!("{}[{}]", operand(ast, *object), unparse(ast, *index));
+        }
+        Expr::Literal(token) | Expr::Variable(token) => return token.lexeme.to_string(),
+        Expr::Match { subject, arms, .. } => {
+            let arms: Vec<String> = arms
+                .iter()
+                .map(|arm| {
+                    let guard = match arm.guard {
+                        Some(guard) => format!(" if {}", unparse(ast, guard)),
+                        None => String::new(),
+                    };
+                    format!(
+                        "{}{} => {}",
+                        unparse_pattern(&arm.pattern),
+                        guard,
+                        unparse(ast, arm.body)
+                    )
+                })
+                .collect();
+            return format!("match {} {{ {} }}", unparse(ast, *subject), arms.join(", "));
+        }
+        Expr::Tuple { elements, .. } => {
+            let elements: Vec<String> = elements
+                .iter()
+                .map(|element| unparse(ast, *element))
+                .collect();
+            if elements.len() == 1 {
+                return format!("({},)", elements[0]);
+            }
+            return format!("({})", elements.join(", "));
+        }
+        Expr::Unary { operator, right } => {
+            let parens = matches!(ast[*right], Expr::Binary { .. } | Expr::Conditional { .. });
+            return format!("{}{}", operator.lexeme, parenthesized(ast, *right, parens));
+        }
+    }
+}
+
+fn unparse_pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Wildcard(token) | Pattern::Binding(token) | Pattern::Literal(token) => {
+            return token.lexeme.to_string();
+        }
+        Pattern::Tuple { elements, .. } => {
+            let elements: Vec<String> = elements.iter().map(unparse_pattern).collect();
+            if elements.len() == 1 {
+                return format!("({},)", elements[0]);
+            }
+            return format!("({})", elements.join(", "));
+        }
+    }
+}
+
+fn parenthesized(ast: &Ast, id: ExprId, parens: bool) -> String {
+    if parens {
+        return format!("({})", unparse(ast, id));
+    }
+    return unparse(ast, id);
+}
+
+/// The object of a call, property or index, which binds tighter than any
+/// operator.
+fn operand(ast: &Ast, id: ExprId) -> String {
+    let parens = matches!(
+        ast[id],
+        Expr::Binary { .. } | Expr::Conditional { .. } | Expr::Unary { .. }
+    );
+    return parenthesized(ast, id, parens);
+}
+
+/// The precedence and associativity of a binary expression.
+fn binding(expr: &Expr) -> Option<(u8, Associativity)> {
+    let Expr::Binary { operator, .. } = expr else {
+        return None;
+    };
+    return BINARY_OPERATORS
+        .iter()
+        .find(|(token_type, _, _)| *token_type == operator.token_type)
+        .map(|&(_, precedence, associativity)| (precedence, associativity));
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::{
+        ast::Arm,
+        parser::Parser,
+        scanner::Scanner,
+        token::{Token, TokenType},
+    };
+
+    use super::*;
+
+    /// An expression and the tree it is in.
+    type Tree = (Ast, ExprId);
+
+    fn parse(source: &str) -> Tree {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let mut parser = Parser::new(&tokens);
+        let expr = parser.parse_expression().unwrap();
+        (parser.ast, expr)
+    }
+
+    /// The tree without its groupings, which only matter to the parser.
+    fn ungrouped((mut ast, expr): Tree) -> String {
+        for id in ast.ids() {
+            while let Expr::Grouping(inner) = ast[id] {
+                ast[id] = ast[inner].clone();
+            }
+        }
+        ast.display(expr).to_string()
+    }
+
+    fn token(token_type: TokenType, lexeme: &str) -> Token {
+        Token::new(token_type, lexeme, "", 1)
+    }
+
+    /// A tree of one expression made of those in `trees`.
+    fn node(trees: Vec<Tree>, expr: impl FnOnce(Vec<ExprId>) -> Expr) -> Tree {
+        let mut ast = Ast::new();
+        let children = trees
+            .into_iter()
+            .map(|(tree, child)| ast.graft(tree, child))
+            .collect();
+        let expr = ast.add(expr(children));
+        (ast, expr)
+    }
+
+    fn leaf(expr: Expr) -> Tree {
+        node(Vec::new(), |_| expr)
+    }
+
+    fn leaves() -> impl Strategy<Value = Tree> {
+        prop_oneof![
+            (0..1000u32)
+                .prop_map(|n| leaf(Expr::Literal(token(TokenType::NUMBER, &n.to_string())))),
+            (0..100u32, 1..100u32).prop_map(|(n, fraction)| leaf(Expr::Literal(token(
+                TokenType::NUMBER,
+                &format!("{}.{}", n, fraction)
+            )))),
+            "[a-z ]{0,4}".prop_map(|s| leaf(Expr::Literal(token(
+                TokenType::STRING,
+                &format!("\"{}\"", s)
+            )))),
+            prop::sample::select(vec![
+                (TokenType::TRUE, "true"),
+                (TokenType::FALSE, "false"),
+                (TokenType::NIL, "nil"),
+            ])
+            .prop_map(|(token_type, lexeme)| leaf(Expr::Literal(token(token_type, lexeme)))),
+            prop::sample::select(vec!["x", "count", "_tmp", "ø"])
+                .prop_map(|name| leaf(Expr::Variable(token(TokenType::IDENTIFIER, name)))),
+        ]
+    }
+
+    fn pattern() -> impl Strategy<Value = Pattern> {
+        let leaves = prop_oneof![
+            Just(Pattern::Wildcard(token(TokenType::IDENTIFIER, "_"))),
+            prop::sample::select(vec!["a", "rest"])
+                .prop_map(|name| Pattern::Binding(token(TokenType::IDENTIFIER, name))),
+            (0..10u32).prop_map(|n| Pattern::Literal(token(TokenType::NUMBER, &n.to_string()))),
+            Just(Pattern::Literal(token(TokenType::STRING, "\"s\""))),
+        ];
+        leaves.prop_recursive(3, 8, 3, |inner| {
+            prop::collection::vec(inner, 0..3).prop_map(|elements| Pattern::Tuple {
+                paren: token(TokenType::LeftParen, "("),
+                elements,
+            })
+        })
+    }
+
+    fn expr() -> impl Strategy<Value = Tree> {
+        let operators: Vec<Token> = ["??", "!=", "==", ">", ">=", "<", "<=", "-", "+", "/", "*"]
+            .iter()
+            .map(|lexeme| Scanner::new(lexeme).scan_tokens().unwrap()[0].clone())
+            .collect();
+        leaves().prop_recursive(6, 64, 3, move |inner| {
+            prop_oneof![
+                (
+                    inner.clone(),
+                    prop::sample::select(operators.clone()),
+                    inner.clone()
+                )
+                    .prop_map(|(left, operator, right)| node(
+                        vec![left, right],
+                        |children| Expr::Binary {
+                            left: children[0],
+                            operator,
+                            right: children[1],
+                        }
+                    )),
+                (prop::sample::select(vec!["-", "!"]), inner.clone()).prop_map(
+                    |(operator, right)| {
+                        let token_type = match operator {
+                            "-" => TokenType::Minus,
+                            _ => TokenType::BANG,
+                        };
+                        node(vec![right], |children| Expr::Unary {
+                            operator: token(token_type, operator),
+                            right: children[0],
+                        })
+                    }
+                ),
+                inner
+                    .clone()
+                    .prop_map(|expr| node(vec![expr], |children| Expr::Grouping(children[0]))),
+                (inner.clone(), inner.clone()).prop_map(|(object, index)| node(
+                    vec![object, index],
+                    |children| Expr::Index {
+                        object: children[0],
+                        bracket: token(TokenType::LeftBracket, "["),
+                        index: children[1],
+                    }
+                )),
+                (inner.clone(), prop::sample::select(vec!["length", "trim"])).prop_map(
+                    |(object, name)| node(vec![object], |children| Expr::Get {
+                        object: children[0],
+                        name: token(TokenType::IDENTIFIER, name),
+                    })
+                ),
+                (inner.clone(), prop::collection::vec(inner.clone(), 0..3)).prop_map(
+                    |(callee, arguments)| {
+                        let trees = std::iter::once(callee).chain(arguments).collect();
+                        node(trees, |children| Expr::Call {
+                            callee: children[0],
+                            paren: token(TokenType::LeftParen, "("),
+                            arguments: children[1..].to_vec(),
+                        })
+                    }
+                ),
+                prop::collection::vec(inner.clone(), 0..4).prop_map(|elements| node(
+                    elements,
+                    |children| Expr::Tuple {
+                        paren: token(TokenType::LeftParen, "("),
+                        elements: children,
+                    }
+                )),
+                (
+                    inner.clone(),
+                    prop::collection::vec(
+                        (pattern(), prop::option::of(inner.clone()), inner.clone()),
+                        1..3
+                    )
+                )
+                    .prop_map(|(subject, arms)| {
+                        let mut trees = vec![subject];
+                        let mut patterns = Vec::new();
+                        for (pattern, guard, body) in arms {
+                            patterns.push((pattern, guard.is_some()));
+                            trees.extend(guard);
+                            trees.push(body);
+                        }
+                        node(trees, |children| {
+                            let mut children = children.into_iter();
+                            let subject = children.next().unwrap();
+                            let arms = patterns
+                                .into_iter()
+                                .map(|(pattern, guarded)| Arm {
+                                    pattern,
+                                    guard: if guarded { children.next() } else { None },
+                                    body: children.next().unwrap(),
+                                })
+                                .collect();
+                            Expr::Match {
+                                keyword: token(TokenType::MATCH, "match"),
+                                subject,
+                                arms,
+                            }
+                        })
+                    }),
+                (inner.clone(), inner.clone(), inner).prop_map(
+                    |(condition, then_branch, else_branch)| node(
+                        vec![condition, then_branch, else_branch],
+                        |children| Expr::Conditional {
+                            condition: children[0],
+                            then_branch: children[1],
+                            else_branch: children[2],
+                        }
+                    )
+                ),
+            ]
+        })
+    }
+
+    #[test]
+    fn test_unparse() -> Result<(), String> {
+        for (source, expected) in [
+            ("1 - (2 - 3)", "1 - (2 - 3)"),
+            ("(1 - 2) - 3", "(1 - 2) - 3"),
+            ("-(1 + x) * !nil", "-(1 + x) * !nil"),
+            ("(a ? b : c) ? d : e ? f : g", "(a ? b : c) ? d : e ? f : g"),
+            ("(-t)[0][i + 1]", "(-t)[0][i + 1]"),
+            (
+                "(a + b).trim().slice(0, (1, 2)[0])",
+                "(a + b).trim().slice(0, (1, 2)[0])",
+            ),
+            ("((1,), (), (a, b ? c : d))", "((1,), (), (a, b ? c : d))"),
+            (
+                "match (a,1) {(x, _) if x>1 => x,\n(\"s\",) => -1, _ => nil}",
+                "match (a, 1) { (x, _) if x > 1 => x, (\"s\",) => -1, _ => nil }",
+            ),
+            ("-match x { ((a)) => a } * 2", "-match x { a => a } * 2"),
+        ] {
+            let (ast, expr) = parse(source);
+            assert_eq!(unparse(&ast, expr), expected);
+        }
+
+        let (mut ast, sum) = parse("(1 - 2) - 3");
+        let Expr::Binary { left, .. } = ast[sum] else {
+            panic!("expected a binary expression");
+        };
+        let Expr::Grouping(difference) = ast[left] else {
+            panic!("expected a grouping");
+        };
+        ast[left] = ast[difference].clone();
+        assert_eq!(unparse(&ast, sum), "1 - 2 - 3");
+        let (ast, difference) = parse("1 - (2 - 3)");
+        assert_eq!(unparse(&ast, difference), "1 - (2 - 3)");
+        assert_eq!(ungrouped(parse("((1) - (2 - 3))")), "(- 1 (- 2 3))");
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip((ast, expr) in expr()) {
+            let source = unparse(&ast, expr);
+            prop_assert_eq!(
+                ungrouped(parse(&source)),
+                ungrouped((ast, expr)),
+                "source: {}",
+                source
+            );
+        }
+    }
+}
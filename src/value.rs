@@ -0,0 +1,423 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+
+/// A runtime value. Everything bigger than a number is behind a thin
+/// pointer, so a value takes 16 bytes and copying a string or tuple only
+/// bumps a count. The count is atomic, so values, and the chunks holding
+/// them as constants, can be shared between threads.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Value {
+    #[default]
+    Nil,
+    Bool(bool),
+    Int(i64),
+    /// An int too large for `i64`. Ints that fit are always [`Value::Int`].
+    #[cfg(feature = "bigint")]
+    BigInt(Box<BigInt>),
+    Number(f64),
+    String(Arc<String>),
+    Tuple(Arc<Vec<Value>>),
+}
+
+/// The operators that work on numbers of either kind.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Value {
+    /// The value of a NUMBER literal: an int unless it has a fraction or
+    /// exponent, or is too large for one without the `bigint` feature.
+    pub fn number(literal: &str) -> Value {
+        if let Ok(value) = literal.parse() {
+            return Value::Int(value);
+        }
+        #[cfg(feature = "bigint")]
+        if let Ok(value) = literal.parse::<BigInt>() {
+            return Value::BigInt(Box::new(value));
+        }
+        return Value::Number(literal.parse().unwrap_or(f64::NAN));
+    }
+
+    /// `nil` and `false` are falsey, everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        return !matches!(self, Value::Nil | Value::Bool(false));
+    }
+
+    pub fn is_number(&self) -> bool {
+        #[cfg(feature = "bigint")]
+        if let Value::BigInt(_) = self {
+            return true;
+        }
+        return matches!(self, Value::Int(_) | Value::Number(_));
+    }
+
+    /// Equality as the language sees it, where an int equals the float
+    /// with the same value.
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Int(a), Value::Number(b)) | (Value::Number(b), Value::Int(a)) => {
+                return *a as f64 == *b;
+            }
+            #[cfg(feature = "bigint")]
+            (Value::BigInt(_), Value::Number(_)) | (Value::Number(_), Value::BigInt(_)) => {
+                return self.as_float() == other.as_float();
+            }
+            (Value::Tuple(a), Value::Tuple(b)) => {
+                return a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.equals(b));
+            }
+            _ => return self == other,
+        }
+    }
+
+    /// The element of a tuple, or the character of a string, at an int
+    /// index.
+    pub fn index(&self, index: &Value) -> Result<Value, &'static str> {
+        let index = match index {
+            Value::Int(index) => usize::try_from(*index).ok(),
+            #[cfg(feature = "bigint")]
+            Value::BigInt(_) => None,
+            _ => return Err("Index must be an int."),
+        };
+        let element = match self {
+            Value::Tuple(elements) => index.and_then(|index| elements.get(index)).cloned(),
+            Value::String(string) => index
+                .and_then(|index| string.chars().nth(index))
+                .map(|c| Value::from(c.to_string())),
+            _ => return Err("Only tuples and strings can be indexed."),
+        };
+        return element.ok_or("Index out of range.");
+    }
+
+    /// Applies `op` to two numbers. Two ints give an int, and a float on
+    /// either side gives a float. Int division truncates. Ints that
+    /// overflow become a [`Value::BigInt`] with the `bigint` feature and
+    /// are an error without it.
+    pub fn arithmetic(op: Arithmetic, left: &Value, right: &Value) -> Result<Value, &'static str> {
+        match (left, right) {
+            (Value::Int(a), Value::Int(b)) => {
+                let result = match op {
+                    Arithmetic::Add => a.checked_add(*b),
+                    Arithmetic::Subtract => a.checked_sub(*b),
+                    Arithmetic::Multiply => a.checked_mul(*b),
+                    Arithmetic::Divide if *b == 0 => return Err("Division by zero."),
+                    Arithmetic::Divide => a.checked_div(*b),
+                };
+                #[cfg(feature = "bigint")]
+                if result.is_none() {
+                    return Ok(big_arithmetic(op, BigInt::from(*a), BigInt::from(*b)));
+                }
+                return result.map(Value::Int).ok_or("Integer overflow.");
+            }
+            #[cfg(feature = "bigint")]
+            (Value::Int(_) | Value::BigInt(_), Value::Int(_) | Value::BigInt(_)) => {
+                let (Some(a), Some(b)) = (left.as_big_int(), right.as_big_int()) else {
+                    unreachable!("both operands are ints");
+                };
+                if op == Arithmetic::Divide && b == BigInt::ZERO {
+                    return Err("Division by zero.");
+                }
+                return Ok(big_arithmetic(op, a, b));
+            }
+            _ => {
+                let (Some(a), Some(b)) = (left.as_float(), right.as_float()) else {
+                    return Err("Operands must be numbers.");
+                };
+                let result = match op {
+                    Arithmetic::Add => a + b,
+                    Arithmetic::Subtract => a - b,
+                    Arithmetic::Multiply => a * b,
+                    Arithmetic::Divide => a / b,
+                };
+                return Ok(Value::Number(result));
+            }
+        }
+    }
+
+    /// Orders two numbers, or gives `None` when one of them is NaN.
+    pub fn compare(left: &Value, right: &Value) -> Result<Option<Ordering>, &'static str> {
+        if let (Value::Int(a), Value::Int(b)) = (left, right) {
+            return Ok(Some(a.cmp(b)));
+        }
+        #[cfg(feature = "bigint")]
+        if let (Some(a), Some(b)) = (left.as_big_int(), right.as_big_int()) {
+            return Ok(Some(a.cmp(&b)));
+        }
+        match (left.as_float(), right.as_float()) {
+            (Some(a), Some(b)) => return Ok(a.partial_cmp(&b)),
+            _ => return Err("Operands must be numbers."),
+        }
+    }
+
+    pub fn negate(&self) -> Result<Value, &'static str> {
+        match self {
+            #[cfg(feature = "bigint")]
+            Value::Int(i64::MIN) => return Ok(Value::from(-BigInt::from(i64::MIN))),
+            Value::Int(value) => {
+                return value
+                    .checked_neg()
+                    .map(Value::Int)
+                    .ok_or("Integer overflow.")
+            }
+            #[cfg(feature = "bigint")]
+            Value::BigInt(value) => return Ok(Value::from(-&**value)),
+            Value::Number(value) => return Ok(Value::Number(-value)),
+            _ => return Err("Operand must be a number."),
+        }
+    }
+
+    pub(crate) fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Int(value) => return Some(*value as f64),
+            // Rounds to the nearest float, or infinity when out of range.
+            #[cfg(feature = "bigint")]
+            Value::BigInt(value) => return value.to_string().parse().ok(),
+            Value::Number(value) => return Some(*value),
+            _ => return None,
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    fn as_big_int(&self) -> Option<BigInt> {
+        match self {
+            Value::Int(value) => return Some(BigInt::from(*value)),
+            Value::BigInt(value) => return Some((**value).clone()),
+            _ => return None,
+        }
+    }
+}
+
+/// An int value, which is only a [`Value::BigInt`] if it does not fit an `i64`.
+#[cfg(feature = "bigint")]
+impl From<BigInt> for Value {
+    fn from(value: BigInt) -> Value {
+        match i64::try_from(&value) {
+            Ok(value) => return Value::Int(value),
+            Err(_) => return Value::BigInt(Box::new(value)),
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Value {
+        return Value::String(Arc::new(value));
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Value {
+        return Value::String(Arc::new(value.to_string()));
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(elements: Vec<Value>) -> Value {
+        return Value::Tuple(Arc::new(elements));
+    }
+}
+
+/// Ints never overflow here. The caller checks for division by zero.
+#[cfg(feature = "bigint")]
+fn big_arithmetic(op: Arithmetic, a: BigInt, b: BigInt) -> Value {
+    let result = match op {
+        Arithmetic::Add => a + b,
+        Arithmetic::Subtract => a - b,
+        Arithmetic::Multiply => a * b,
+        Arithmetic::Divide => a / b,
+    };
+    return Value::from(result);
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Int(value) => write!(f, "{}", value),
+            #[cfg(feature = "bigint")]
+            Value::BigInt(value) => write!(f, "{}", value),
+            // Whole floats keep a `.0`, so they read back as floats.
+            Value::Number(value) if value.is_finite() && value.fract() == 0.0 => {
+                write!(f, "{}.0", value)
+            }
+            Value::Number(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Tuple(elements) => {
+                write!(f, "(")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    // Strings are quoted so `("a, b",)` is not `("a", "b")`.
+                    match element {
+                        Value::String(value) => write!(f, "{:?}", value)?,
+                        _ => write!(f, "{}", element)?,
+                    }
+                }
+                if elements.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size() -> Result<(), String> {
+        assert_eq!(std::mem::size_of::<Value>(), 16);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_number_literals() -> Result<(), String> {
+        assert_eq!(Value::number("31"), Value::Int(31));
+        assert_eq!(Value::number("2.5"), Value::Number(2.5));
+        assert_eq!(Value::number("1000.0"), Value::Number(1000.0));
+        #[cfg(not(feature = "bigint"))]
+        assert_eq!(
+            Value::number("9223372036854775808"),
+            Value::Number(9223372036854775808.0)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_display() -> Result<(), String> {
+        assert_eq!(Value::Int(-3).to_string(), "-3");
+        assert_eq!(Value::Number(3.0).to_string(), "3.0");
+        assert_eq!(Value::Number(-0.0).to_string(), "-0.0");
+        assert_eq!(Value::Number(0.25).to_string(), "0.25");
+        assert_eq!(Value::Number(f64::INFINITY).to_string(), "inf");
+        let tuple = |elements: Vec<Value>| Value::Tuple(elements.into());
+        assert_eq!(tuple(vec![]).to_string(), "()");
+        assert_eq!(tuple(vec![Value::Nil]).to_string(), "(nil,)");
+        assert_eq!(
+            tuple(vec![
+                Value::Int(1),
+                Value::from("a\"b"),
+                tuple(vec![Value::Number(2.0), Value::Bool(true)])
+            ])
+            .to_string(),
+            "(1, \"a\\\"b\", (2.0, true))"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_arithmetic() -> Result<(), String> {
+        use Arithmetic::*;
+        let int = Value::Int;
+        let float = Value::Number;
+
+        assert_eq!(Value::arithmetic(Add, &int(2), &int(3)), Ok(int(5)));
+        assert_eq!(Value::arithmetic(Divide, &int(7), &int(2)), Ok(int(3)));
+        assert_eq!(Value::arithmetic(Divide, &int(-7), &int(2)), Ok(int(-3)));
+        assert_eq!(
+            Value::arithmetic(Divide, &int(7), &float(2.0)),
+            Ok(float(3.5))
+        );
+        assert_eq!(
+            Value::arithmetic(Multiply, &float(0.5), &int(3)),
+            Ok(float(1.5))
+        );
+        assert_eq!(
+            Value::arithmetic(Divide, &int(1), &int(0)),
+            Err("Division by zero.")
+        );
+        assert_eq!(
+            Value::arithmetic(Subtract, &int(1), &Value::Nil),
+            Err("Operands must be numbers.")
+        );
+        #[cfg(not(feature = "bigint"))]
+        {
+            assert_eq!(
+                Value::arithmetic(Add, &int(i64::MAX), &int(1)),
+                Err("Integer overflow.")
+            );
+            assert_eq!(int(i64::MIN).negate(), Err("Integer overflow."));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mixed_comparison() -> Result<(), String> {
+        assert!(Value::Int(1).equals(&Value::Number(1.0)));
+        assert!(!Value::Int(1).equals(&Value::from("1")));
+        let tuple = |elements: Vec<Value>| Value::Tuple(elements.into());
+        assert!(tuple(vec![Value::Int(1), Value::Nil])
+            .equals(&tuple(vec![Value::Number(1.0), Value::Nil])));
+        assert!(!tuple(vec![Value::Int(1)]).equals(&tuple(vec![Value::Int(1), Value::Int(1)])));
+        assert_eq!(
+            Value::compare(&Value::Int(2), &Value::Number(1.5)),
+            Ok(Some(Ordering::Greater))
+        );
+        assert_eq!(
+            Value::compare(&Value::Int(2), &Value::Number(f64::NAN)),
+            Ok(None)
+        );
+
+        Ok(())
+    }
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_big_ints() -> Result<(), String> {
+        use Arithmetic::*;
+        let int = Value::Int;
+        let big = |literal: &str| Value::BigInt(Box::new(literal.parse().unwrap()));
+
+        let max_plus_one = big("9223372036854775808");
+        assert_eq!(Value::number("9223372036854775808"), max_plus_one);
+        assert_eq!(
+            Value::arithmetic(Add, &int(i64::MAX), &int(1)),
+            Ok(max_plus_one.clone())
+        );
+        assert_eq!(
+            Value::arithmetic(Multiply, &max_plus_one, &int(2)),
+            Ok(big("18446744073709551616"))
+        );
+        assert_eq!(
+            Value::arithmetic(Subtract, &max_plus_one, &int(1)),
+            Ok(int(i64::MAX))
+        );
+        assert_eq!(
+            Value::arithmetic(Divide, &max_plus_one, &int(0)),
+            Err("Division by zero.")
+        );
+        assert_eq!(
+            Value::arithmetic(Divide, &max_plus_one, &Value::Number(2.0)),
+            Ok(Value::Number(4611686018427387904.0))
+        );
+        assert_eq!(int(i64::MIN).negate(), Ok(max_plus_one.clone()));
+        assert_eq!(max_plus_one.negate(), Ok(int(i64::MIN)));
+
+        assert_eq!(
+            Value::compare(&max_plus_one, &int(i64::MAX)),
+            Ok(Some(Ordering::Greater))
+        );
+        assert_eq!(
+            Value::compare(&max_plus_one, &Value::Number(1e19)),
+            Ok(Some(Ordering::Less))
+        );
+        assert!(max_plus_one.equals(&Value::Number(9223372036854775808.0)));
+        assert!(!max_plus_one.equals(&int(i64::MAX)));
+        assert_eq!(max_plus_one.to_string(), "9223372036854775808");
+
+        Ok(())
+    }
+}
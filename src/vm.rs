@@ -0,0 +1,809 @@
+use std::cmp::Ordering;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{
+    chunk::{Chunk, OpCode},
+    error::error,
+    methods, natives,
+    value::{Arithmetic, Value},
+};
+
+/// How much a program may do before the VM stops it with an
+/// "Execution limit exceeded" error. `None` means no limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub max_stack_depth: usize,
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        return Limits {
+            max_steps: None,
+            max_stack_depth: DEFAULT_MAX_STACK_DEPTH,
+            timeout: None,
+        };
+    }
+}
+
+pub const DEFAULT_MAX_STACK_DEPTH: usize = 1 << 16;
+
+/// The clock is only read every this many instructions.
+const TIMEOUT_CHECK_INTERVAL: u64 = 1024;
+
+/// Watches a program run, e.g. to trace or debug it.
+pub trait Observer {
+    /// Called before the instruction at `offset` runs.
+    fn before_instruction(&mut self, chunk: &Chunk, offset: usize, stack: &[Value]);
+}
+
+/// A stack machine running the bytecode produced by [`crate::compiler`].
+#[derive(Debug, Default)]
+pub struct Vm {
+    stack: Vec<Value>,
+    /// The values match patterns bind, by slot.
+    locals: Vec<Value>,
+    limits: Limits,
+    /// What `args()` gives the program.
+    arguments: Vec<String>,
+    capabilities: natives::Capabilities,
+    log_level: natives::LogLevel,
+    exit_code: Option<u8>,
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        return Vm::default();
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn set_arguments(&mut self, arguments: Vec<String>) {
+        self.arguments = arguments;
+    }
+
+    /// Allows the natives that reach outside the program, like `exec`.
+    pub fn set_capabilities(&mut self, capabilities: natives::Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    /// The least important messages `log.info(...)` and friends write.
+    pub fn set_log_level(&mut self, log_level: natives::LogLevel) {
+        self.log_level = log_level;
+    }
+
+    /// The code the last program asked to exit with, if it called `exit`.
+    pub fn exit_code(&self) -> Option<u8> {
+        return self.exit_code;
+    }
+
+    /// Runs `chunk` and returns the value it returns.
+    pub fn interpret(&mut self, chunk: &Chunk) -> Result<Value, String> {
+        return self.run(chunk, None, None);
+    }
+
+    /// Like [`Vm::interpret`], telling `observer` about every instruction.
+    pub fn interpret_with(
+        &mut self,
+        chunk: &Chunk,
+        observer: &mut dyn Observer,
+    ) -> Result<Value, String> {
+        return self.run(chunk, Some(observer), None);
+    }
+
+    /// Like [`Vm::interpret`], calling `on_yield` after every `every`
+    /// instructions, so a host can keep its event loop going during a long
+    /// run. The run stops with an error when `on_yield` breaks.
+    pub fn interpret_yielding(
+        &mut self,
+        chunk: &Chunk,
+        every: u64,
+        on_yield: &mut dyn FnMut() -> ControlFlow<()>,
+    ) -> Result<Value, String> {
+        return self.run(chunk, None, Some((every.max(1), on_yield)));
+    }
+
+    fn run(
+        &mut self,
+        chunk: &Chunk,
+        mut observer: Option<&mut dyn Observer>,
+        mut yielding: Option<(u64, &mut dyn FnMut() -> ControlFlow<()>)>,
+    ) -> Result<Value, String> {
+        self.stack.clear();
+        self.locals.clear();
+        self.exit_code = None;
+        let mut state = State {
+            chunk,
+            ip: 0,
+            line: 0,
+            started: self.start_time(chunk),
+        };
+        let mut steps: u64 = 0;
+
+        loop {
+            state.line = chunk.lines[state.ip];
+            if let Some((every, on_yield)) = yielding.as_mut() {
+                if steps > 0 && steps.is_multiple_of(*every) && on_yield().is_break() {
+                    return Err(error(state.line, "Execution cancelled."));
+                }
+            }
+            self.check_limits(state.line, steps, state.started)?;
+            steps += 1;
+            if let Some(observer) = observer.as_mut() {
+                observer.before_instruction(chunk, state.ip, &self.stack);
+            }
+            let byte = chunk.code[state.ip];
+            state.ip += 1;
+            if let Some(value) = self.dispatch(byte, &mut state)? {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Runs the instruction `byte`, whose operands follow at `state.ip`.
+    #[cfg(not(feature = "table-dispatch"))]
+    fn dispatch(&mut self, byte: u8, state: &mut State) -> Result<Option<Value>, String> {
+        let op =
+            OpCode::from_byte(byte).ok_or_else(|| error(state.line, "Invalid instruction."))?;
+        return self.execute(op, state);
+    }
+
+    /// Runs the instruction `byte` through [`HANDLERS`] instead of a match.
+    #[cfg(feature = "table-dispatch")]
+    fn dispatch(&mut self, byte: u8, state: &mut State) -> Result<Option<Value>, String> {
+        let handler = HANDLERS
+            .get(byte as usize)
+            .ok_or_else(|| error(state.line, "Invalid instruction."))?;
+        return handler(self, state);
+    }
+
+    /// Runs `op` and returns what the program returns if `op` ends it.
+    #[inline(always)]
+    fn execute(&mut self, op: OpCode, state: &mut State) -> Result<Option<Value>, String> {
+        let chunk = state.chunk;
+        let line = state.line;
+        match op {
+            OpCode::Constant => {
+                let index = chunk.code[state.ip] as usize;
+                state.ip += 1;
+                self.stack.push(chunk.constants[index].clone());
+            }
+            OpCode::Nil => self.stack.push(Value::Nil),
+            OpCode::True => self.stack.push(Value::Bool(true)),
+            OpCode::False => self.stack.push(Value::Bool(false)),
+            OpCode::Pop => {
+                self.pop();
+            }
+            OpCode::Equal => {
+                let right = self.pop();
+                let left = self.pop();
+                self.stack.push(Value::Bool(left.equals(&right)));
+            }
+            OpCode::Greater => self.comparison(line, |order| order.is_gt())?,
+            OpCode::GreaterEqual => self.comparison(line, |order| order.is_ge())?,
+            OpCode::Less => self.comparison(line, |order| order.is_lt())?,
+            OpCode::LessEqual => self.comparison(line, |order| order.is_le())?,
+            OpCode::Add => {
+                let right = self.pop();
+                let left = self.pop();
+                let value = match (left, right) {
+                    (Value::String(mut a), Value::String(b)) => {
+                        // Appends in place when nothing else holds `a`.
+                        Arc::make_mut(&mut a).push_str(&b);
+                        Value::String(a)
+                    }
+                    (a, b) if a.is_number() && b.is_number() => {
+                        Value::arithmetic(Arithmetic::Add, &a, &b)
+                            .map_err(|message| error(line, message))?
+                    }
+                    _ => return Err(error(line, "Operands must be two numbers or two strings.")),
+                };
+                self.stack.push(value);
+            }
+            OpCode::Subtract => self.arithmetic(line, Arithmetic::Subtract)?,
+            OpCode::Multiply => self.arithmetic(line, Arithmetic::Multiply)?,
+            OpCode::Divide => self.arithmetic(line, Arithmetic::Divide)?,
+            OpCode::Not => {
+                let value = self.pop();
+                self.stack.push(Value::Bool(!value.is_truthy()));
+            }
+            OpCode::Negate => {
+                let value = self
+                    .pop()
+                    .negate()
+                    .map_err(|message| error(line, message))?;
+                self.stack.push(value);
+            }
+            OpCode::Jump => {
+                state.ip += read_offset(chunk, state.ip) + 2;
+            }
+            OpCode::JumpIfFalse => {
+                let offset = read_offset(chunk, state.ip);
+                state.ip += 2;
+                if !self.stack.last().is_some_and(Value::is_truthy) {
+                    state.ip += offset;
+                }
+            }
+            OpCode::Return => return Ok(Some(self.pop())),
+            OpCode::JumpIfNotNil => {
+                let offset = read_offset(chunk, state.ip);
+                state.ip += 2;
+                if !matches!(self.stack.last(), None | Some(Value::Nil)) {
+                    state.ip += offset;
+                }
+            }
+            OpCode::GetProperty => {
+                let name = read_name(chunk, state.ip, line)?;
+                state.ip += 1;
+                let value = methods::property(&self.pop(), name)
+                    .map_err(|message| error(line, &message))?;
+                self.stack.push(value);
+            }
+            OpCode::Invoke => {
+                let name = read_name(chunk, state.ip, line)?;
+                let count = chunk.code[state.ip + 1] as usize;
+                state.ip += 2;
+                let arguments = self.stack.split_off(self.stack.len().saturating_sub(count));
+                let value = methods::invoke(&self.pop(), name, &arguments)
+                    .map_err(|message| error(line, &message))?;
+                self.stack.push(value);
+            }
+            OpCode::CallNative => {
+                let name = read_name(chunk, state.ip, line)?;
+                let count = chunk.code[state.ip + 1] as usize;
+                state.ip += 2;
+                let arguments = self.stack.split_off(self.stack.len().saturating_sub(count));
+                let mut context = natives::Context {
+                    limits: &self.limits,
+                    capabilities: &self.capabilities,
+                    started: state.started,
+                    arguments: &self.arguments,
+                    exit_code: None,
+                    line,
+                    log_level: self.log_level,
+                };
+                let value = natives::call(name, &arguments, &mut context)
+                    .map_err(|message| error(line, &message))?;
+                if let Some(code) = context.exit_code {
+                    self.exit_code = Some(code);
+                    return Ok(Some(Value::Nil));
+                }
+                self.stack.push(value);
+            }
+            OpCode::Tuple => {
+                let length = chunk.code[state.ip] as usize;
+                state.ip += 1;
+                let elements = self
+                    .stack
+                    .split_off(self.stack.len().saturating_sub(length));
+                self.stack.push(Value::Tuple(elements.into()));
+            }
+            OpCode::Index => {
+                let index = self.pop();
+                let value = self
+                    .pop()
+                    .index(&index)
+                    .map_err(|message| error(line, message))?;
+                self.stack.push(value);
+            }
+            OpCode::Bind => {
+                let value = self.pop();
+                self.locals.push(value);
+            }
+            OpCode::GetLocal => {
+                let slot = chunk.code[state.ip] as usize;
+                state.ip += 1;
+                let value = self
+                    .locals
+                    .get(slot)
+                    .ok_or_else(|| error(line, "Invalid instruction."))?;
+                self.stack.push(value.clone());
+            }
+            OpCode::Unbind => {
+                let slot = chunk.code[state.ip] as usize;
+                state.ip += 1;
+                self.locals.truncate(slot);
+            }
+            OpCode::MatchTuple => {
+                let length = chunk.code[state.ip] as usize;
+                state.ip += 1;
+                let value = self.pop();
+                let matched = matches!(&value, Value::Tuple(elements) if elements.len() == length);
+                self.stack.push(Value::Bool(matched));
+            }
+            OpCode::NoMatch => {
+                let value = match self.pop() {
+                    Value::String(value) => format!("{:?}", value),
+                    value => value.to_string(),
+                };
+                return Err(error(line, &format!("No match arm for {}.", value)));
+            }
+        }
+        return Ok(None);
+    }
+
+    /// When a run of `chunk` starts, if anything needs to know: the
+    /// timeout or `clock()`. Otherwise the clock is not read, so programs
+    /// also run where there is none, like `wasm32-unknown-unknown`.
+    fn start_time(&self, chunk: &Chunk) -> Option<Instant> {
+        let clock = chunk
+            .constants
+            .iter()
+            .any(|constant| matches!(constant, Value::String(name) if name.as_str() == "clock"));
+        if self.limits.timeout.is_none() && !clock {
+            return None;
+        }
+        return Some(Instant::now());
+    }
+
+    fn check_limits(
+        &self,
+        line: usize,
+        steps: u64,
+        started: Option<Instant>,
+    ) -> Result<(), String> {
+        let exceeded = |what: String| {
+            let message = format!("Execution limit exceeded: {}.", what);
+            return Err(error(line, &message));
+        };
+        if let Some(max_steps) = self.limits.max_steps {
+            if steps >= max_steps {
+                return exceeded(format!("more than {} steps", max_steps));
+            }
+        }
+        if self.stack.len() > self.limits.max_stack_depth {
+            return exceeded(format!(
+                "more than {} values on the stack",
+                self.limits.max_stack_depth
+            ));
+        }
+        if let (Some(timeout), Some(started)) = (self.limits.timeout, started) {
+            if steps.is_multiple_of(TIMEOUT_CHECK_INTERVAL) && started.elapsed() >= timeout {
+                return exceeded(format!("ran for more than {:?}", timeout));
+            }
+        }
+        return Ok(());
+    }
+
+    fn pop(&mut self) -> Value {
+        return self.stack.pop().unwrap_or_default();
+    }
+
+    fn arithmetic(&mut self, line: usize, op: Arithmetic) -> Result<(), String> {
+        let right = self.pop();
+        let left = self.pop();
+        let value = Value::arithmetic(op, &left, &right).map_err(|message| error(line, message))?;
+        self.stack.push(value);
+        return Ok(());
+    }
+
+    /// Pushes whether the two numbers on top of the stack are ordered as
+    /// `op` wants. Nothing is ordered with NaN.
+    fn comparison(&mut self, line: usize, op: impl Fn(Ordering) -> bool) -> Result<(), String> {
+        let right = self.pop();
+        let left = self.pop();
+        let order = Value::compare(&left, &right).map_err(|message| error(line, message))?;
+        self.stack.push(Value::Bool(order.is_some_and(op)));
+        return Ok(());
+    }
+}
+
+/// Where a run is in its chunk.
+struct State<'a> {
+    chunk: &'a Chunk,
+    ip: usize,
+    /// The line of the instruction being run.
+    line: usize,
+    /// When the run started, if the clock is read at all.
+    started: Option<Instant>,
+}
+
+/// Runs one kind of instruction, like [`Vm::execute`] does for any.
+#[cfg(feature = "table-dispatch")]
+type Handler = fn(&mut Vm, &mut State) -> Result<Option<Value>, String>;
+
+/// The handler of every opcode, by its byte.
+#[cfg(feature = "table-dispatch")]
+const HANDLERS: [Handler; OpCode::ALL.len()] = [
+    handler::<0>,
+    handler::<1>,
+    handler::<2>,
+    handler::<3>,
+    handler::<4>,
+    handler::<5>,
+    handler::<6>,
+    handler::<7>,
+    handler::<8>,
+    handler::<9>,
+    handler::<10>,
+    handler::<11>,
+    handler::<12>,
+    handler::<13>,
+    handler::<14>,
+    handler::<15>,
+    handler::<16>,
+    handler::<17>,
+    handler::<18>,
+    handler::<19>,
+    handler::<20>,
+    handler::<21>,
+    handler::<22>,
+    handler::<23>,
+    handler::<24>,
+    handler::<25>,
+    handler::<26>,
+    handler::<27>,
+    handler::<28>,
+    handler::<29>,
+];
+
+/// [`Vm::execute`] with `op` known at compile time, so that the match in
+/// it folds away and leaves only the code for `op`.
+#[cfg(feature = "table-dispatch")]
+fn handler<const OP: u8>(vm: &mut Vm, state: &mut State) -> Result<Option<Value>, String> {
+    return vm.execute(OpCode::ALL[OP as usize], state);
+}
+
+fn read_offset(chunk: &Chunk, ip: usize) -> usize {
+    return u16::from_be_bytes([chunk.code[ip], chunk.code[ip + 1]]) as usize;
+}
+
+/// The property or method name whose constant index is at `ip`.
+fn read_name(chunk: &Chunk, ip: usize, line: usize) -> Result<&str, String> {
+    match &chunk.constants[chunk.code[ip] as usize] {
+        Value::String(name) => return Ok(name),
+        _ => return Err(error(line, "Invalid instruction.")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compiler, parser::Parser, scanner::Scanner};
+
+    use super::*;
+
+    fn interpret(source: &str) -> Result<Value, String> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let program = Parser::new(&tokens).parse()?;
+        let chunk = compiler::compile(&program)?;
+        Vm::new().interpret(&chunk)
+    }
+
+    #[test]
+    fn test_arithmetic() -> Result<(), String> {
+        assert_eq!(interpret("1 + 2 * 3")?, Value::Int(7));
+        assert_eq!(interpret("(1 + 2) * 3")?, Value::Int(9));
+        assert_eq!(interpret("10 - 4 - 3")?, Value::Int(3));
+        assert_eq!(interpret("-(3 / 2.0)")?, Value::Number(-1.5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ints_and_floats() -> Result<(), String> {
+        assert_eq!(interpret("7 / 2")?, Value::Int(3));
+        assert_eq!(interpret("2 * 0.5")?, Value::Number(1.0));
+        assert_eq!(interpret("1 == 1.0")?, Value::Bool(true));
+        assert_eq!(interpret("2 > 1.5")?, Value::Bool(true));
+        assert_eq!(
+            interpret("1 / 0").unwrap_err(),
+            "[line 1] Error: Division by zero."
+        );
+        #[cfg(not(feature = "bigint"))]
+        assert_eq!(
+            interpret("9223372036854775807 + 1").unwrap_err(),
+            "[line 1] Error: Integer overflow."
+        );
+        #[cfg(feature = "bigint")]
+        assert_eq!(
+            interpret("9223372036854775807 + 1 > 9223372036854775807")?,
+            Value::Bool(true)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_comparison_and_equality() -> Result<(), String> {
+        assert_eq!(interpret("1 < 2")?, Value::Bool(true));
+        assert_eq!(interpret("2 <= 1")?, Value::Bool(false));
+        assert_eq!(interpret("0.0 / 0 >= 0")?, Value::Bool(false));
+        assert_eq!(interpret("1 == 1")?, Value::Bool(true));
+        assert_eq!(interpret("\"a\" != \"a\"")?, Value::Bool(false));
+        assert_eq!(interpret("nil == false")?, Value::Bool(false));
+        assert_eq!(interpret("!nil")?, Value::Bool(true));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strings() -> Result<(), String> {
+        assert_eq!(interpret("\"foo\" + \"bar\"")?, Value::from("foobar"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conditional() -> Result<(), String> {
+        assert_eq!(interpret("true ? 1 : 2")?, Value::Int(1));
+        assert_eq!(interpret("nil ? 1 : false ? 2 : 3")?, Value::Int(3));
+        assert_eq!(
+            interpret("0 ? \"zero is truthy\" : 2")?.to_string(),
+            "zero is truthy"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tuples() -> Result<(), String> {
+        assert_eq!(interpret("(1, \"a\")")?.to_string(), "(1, \"a\")");
+        assert_eq!(interpret("(1 + 1,)")?.to_string(), "(2,)");
+        assert_eq!(interpret("(1, (2, 3))[1][0]")?, Value::Int(2));
+        assert_eq!(interpret("(1, \"a\") == (1.0, \"a\")")?, Value::Bool(true));
+        assert_eq!(interpret("(1, 2) != (2, 1)")?, Value::Bool(true));
+        assert_eq!(interpret("() == ()")?, Value::Bool(true));
+        assert_eq!(
+            interpret("(1, 2)[2]").unwrap_err(),
+            "[line 1] Error: Index out of range."
+        );
+        assert_eq!(
+            interpret("(1, 2)[-1]").unwrap_err(),
+            "[line 1] Error: Index out of range."
+        );
+        assert_eq!(
+            interpret("(1, 2)[0.0]").unwrap_err(),
+            "[line 1] Error: Index must be an int."
+        );
+        assert_eq!(
+            interpret("true\n[0]").unwrap_err(),
+            "[line 2] Error: Only tuples and strings can be indexed."
+        );
+        assert_eq!(
+            interpret("(1,) + (2,)").unwrap_err(),
+            "[line 1] Error: Operands must be two numbers or two strings."
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_match() -> Result<(), String> {
+        assert_eq!(
+            interpret("match 2 { 1 => \"one\", 2 => \"two\", _ => \"many\" }")?.to_string(),
+            "two"
+        );
+        assert_eq!(
+            interpret("match 9 { 1 => \"one\", _ => \"many\" }")?.to_string(),
+            "many"
+        );
+        assert_eq!(
+            interpret("match (1, (2, 3)) { (a, (b, c)) => a + b * c }")?,
+            Value::Int(7)
+        );
+        assert_eq!(
+            interpret("match (1, 2) { (a,) => a, (a, b, c) => c, (a, b) => b }")?,
+            Value::Int(2)
+        );
+        assert_eq!(
+            interpret("match (\"x\", 5) { (\"y\", n) => n, (\"x\", n) => -n }")?,
+            Value::Int(-5)
+        );
+        assert_eq!(
+            interpret("match 3 { n if n > 5 => 1, n if n > 2 => 2, _ => 3 }")?,
+            Value::Int(2)
+        );
+        assert_eq!(
+            interpret("match 4 { n => match n * 2 { m => (n, m) } }")?.to_string(),
+            "(4, 8)"
+        );
+        assert_eq!(interpret("match 4 { (a, b) => a, n => n }")?, Value::Int(4));
+        assert_eq!(
+            interpret("match (1, 2) { (a, _) if a > 1 => a, (_, b) => b }")?,
+            Value::Int(2)
+        );
+        assert_eq!(
+            interpret("match 1 { x => x } + match 2 { y => y }")?,
+            Value::Int(3)
+        );
+        assert_eq!(interpret("match -1 { len => len }")?, Value::Int(-1));
+        assert_eq!(
+            interpret("match 3 { 1 => 1, 2 => 2 }").unwrap_err(),
+            "[line 1] Error: No match arm for 3."
+        );
+        assert_eq!(
+            interpret("match\n(\"a\",) { (1,) => 1 }").unwrap_err(),
+            "[line 1] Error: No match arm for (\"a\",)."
+        );
+        assert_eq!(
+            interpret("match (1, 1) { (a, a) => a }").unwrap_err(),
+            "[line 1] Error at 'a': Name is already bound in this pattern."
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nil_coalescing() -> Result<(), String> {
+        assert_eq!(interpret("nil ?? 2")?, Value::Int(2));
+        assert_eq!(interpret("false ?? 2")?, Value::Bool(false));
+        assert_eq!(interpret("nil ?? nil ?? \"c\"")?.to_string(), "c");
+        assert_eq!(interpret("1 ?? -\"not evaluated\"")?, Value::Int(1));
+        assert_eq!(
+            interpret("nil ?? -\"a\"").unwrap_err(),
+            "[line 1] Error: Operand must be a number."
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_methods() -> Result<(), String> {
+        assert_eq!(interpret("\"høst\"[1]")?.to_string(), "ø");
+        assert_eq!(interpret("\"høst\".length")?, Value::Int(4));
+        assert_eq!(
+            interpret("\" a-b \".trim().replace(\"-\", \"+\").slice(1, 3)")?.to_string(),
+            "+b"
+        );
+        assert_eq!(
+            interpret("(1, 2).length + \"ab\".indexOf(\"b\")")?,
+            Value::Int(3)
+        );
+        assert_eq!(
+            interpret("\"a\".trim(\n1)").unwrap_err(),
+            "[line 1] Error: Expected 0 arguments but got 1."
+        );
+        assert_eq!(
+            interpret("nil.length").unwrap_err(),
+            "[line 1] Error: Undefined property 'length'."
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_natives() -> Result<(), String> {
+        assert_eq!(interpret("formatTime(0, \"%Y\")")?.to_string(), "1970");
+        assert_eq!(interpret("sleep(0) ?? now() > 0")?, Value::Bool(true));
+        assert_eq!(
+            interpret("now(1)").unwrap_err(),
+            "[line 1] Error: Expected 0 arguments but got 1."
+        );
+        assert_eq!(
+            interpret("today()").unwrap_err(),
+            "[line 1] Error: Undefined function 'today'."
+        );
+        assert_eq!(interpret("log.debug(\"not written\")")?, Value::Nil);
+        assert_eq!(
+            interpret("log.verbose(1)").unwrap_err(),
+            "[line 1] Error: Undefined function 'log.verbose'."
+        );
+        assert_eq!(
+            interpret("match 1 { log => log.verbose(1) }").unwrap_err(),
+            "[line 1] Error: Undefined method 'verbose'."
+        );
+        assert_eq!(
+            interpret("(clock)()").unwrap_err(),
+            "[line 1] Error at '(': Can only call methods and built-in functions."
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exit() -> Result<(), String> {
+        let tokens = Scanner::new("args().length > 0 ? args()[0] : exit(2) ?? -\"not run\"")
+            .scan_tokens()
+            .unwrap();
+        let chunk = compiler::compile(&Parser::new(&tokens).parse()?)?;
+        let mut vm = Vm::new();
+
+        vm.set_arguments(vec![String::from("a")]);
+        assert_eq!(vm.interpret(&chunk)?.to_string(), "a");
+        assert_eq!(vm.exit_code(), None);
+
+        vm.set_arguments(Vec::new());
+        assert_eq!(vm.interpret(&chunk)?, Value::Nil);
+        assert_eq!(vm.exit_code(), Some(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_limits() -> Result<(), String> {
+        let tokens = Scanner::new("1 + 2 + 3").scan_tokens().unwrap();
+        let chunk = compiler::compile(&Parser::new(&tokens).parse()?)?;
+        let mut vm = Vm::new();
+
+        vm.set_limits(Limits {
+            max_steps: Some(3),
+            ..Limits::default()
+        });
+        assert_eq!(
+            vm.interpret(&chunk).unwrap_err(),
+            "[line 1] Error: Execution limit exceeded: more than 3 steps."
+        );
+
+        vm.set_limits(Limits {
+            max_stack_depth: 1,
+            ..Limits::default()
+        });
+        assert_eq!(
+            vm.interpret(&chunk).unwrap_err(),
+            "[line 1] Error: Execution limit exceeded: more than 1 values on the stack."
+        );
+
+        vm.set_limits(Limits {
+            timeout: Some(Duration::ZERO),
+            ..Limits::default()
+        });
+        assert!(vm.interpret(&chunk).is_err());
+
+        vm.set_limits(Limits {
+            max_steps: Some(6),
+            max_stack_depth: 2,
+            timeout: Some(Duration::from_secs(60)),
+        });
+        assert_eq!(vm.interpret(&chunk)?, Value::Int(6));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_time() -> Result<(), String> {
+        let compile = |source: &str| {
+            let tokens = Scanner::new(source).scan_tokens().unwrap();
+            compiler::compile(&Parser::new(&tokens).parse().unwrap()).unwrap()
+        };
+        let mut vm = Vm::new();
+
+        // Nothing reads the clock, which a browser does not have.
+        assert_eq!(vm.start_time(&compile("1 + 2")), None);
+        assert_eq!(vm.start_time(&compile("\"a\".length")), None);
+        assert!(vm.start_time(&compile("clock() + 1")).is_some());
+        assert_eq!(interpret("clock() >= 0")?, Value::Bool(true));
+
+        vm.set_limits(Limits {
+            timeout: Some(Duration::from_secs(60)),
+            ..Limits::default()
+        });
+        assert!(vm.start_time(&compile("1 + 2")).is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_instruction() -> Result<(), String> {
+        let mut chunk = Chunk::new();
+        chunk.write(OpCode::ALL.len() as u8, 3);
+        assert_eq!(
+            Vm::new().interpret(&chunk).unwrap_err(),
+            "[line 3] Error: Invalid instruction."
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_runtime_errors() -> Result<(), String> {
+        assert_eq!(
+            interpret("1 +\n\"a\"").unwrap_err(),
+            "[line 1] Error: Operands must be two numbers or two strings."
+        );
+        assert_eq!(
+            interpret("-\"a\"").unwrap_err(),
+            "[line 1] Error: Operand must be a number."
+        );
+        assert_eq!(
+            interpret("true < 1").unwrap_err(),
+            "[line 1] Error: Operands must be numbers."
+        );
+
+        Ok(())
+    }
+}
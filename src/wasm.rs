@@ -0,0 +1,31 @@
+//! The browser API, built with `--features wasm` for `wasm32-unknown-unknown`.
+
+use js_sys::{Array, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::{interpreter::Interpreter, scanner::Scanner};
+
+/// Runs `source` and returns its value, or its errors one per line.
+#[wasm_bindgen]
+pub fn eval(source: &str) -> String {
+    match Interpreter::new().eval(source) {
+        Ok(value) => value.to_string(),
+        Err(errors) => errors.join("\n"),
+    }
+}
+
+/// Returns the tokens of `source` as an array of `{ type, lexeme, line }`
+/// objects. Characters that cannot be scanned are skipped.
+#[wasm_bindgen]
+pub fn tokenize(source: &str) -> JsValue {
+    let tokens = Array::new();
+    for token in Scanner::new(source).flatten() {
+        let object = Object::new();
+        let token_type = format!("{:?}", token.token_type);
+        Reflect::set(&object, &"type".into(), &token_type.into()).unwrap();
+        Reflect::set(&object, &"lexeme".into(), &token.lexeme.as_str().into()).unwrap();
+        Reflect::set(&object, &"line".into(), &(token.line as u32).into()).unwrap();
+        tokens.push(&object);
+    }
+    return tokens.into();
+}